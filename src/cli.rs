@@ -0,0 +1,129 @@
+// command line of the renderer :
+//     ray_tracer [options] [scene.yml] [output.png]
+
+pub const USAGE:&str = "usage: ray_tracer [options] [scene] [output]
+
+  scene            scene description to render (default in.yml)
+  output           image to write (default out.png)
+
+options:
+  --width <px>     override the width of the image
+  --height <px>    override the height of the image
+  --fov <degrees>  override the field of view of the camera
+  --samples <n>    override the number of samples per pixel
+  -q, --quiet      do not show the progress bar nor the rendering time
+  -h, --help       print this help";
+
+// exit codes
+pub const EXIT_LOAD:i32   = 1;
+pub const EXIT_USAGE:i32  = 2;
+pub const EXIT_OUTPUT:i32 = 3;
+pub const EXIT_RENDER:i32 = 4;
+
+#[derive(Debug, PartialEq)]
+pub struct Options
+{
+    pub scene:String,
+    pub output:String,
+    pub width:Option<usize>,
+    pub height:Option<usize>,
+    pub fov:Option<f64>,
+    pub samples:Option<usize>,
+    pub quiet:bool,
+    pub help:bool
+}
+
+fn value<T: std::str::FromStr>(name:&str,value:Option<String>) -> Result<T,String>
+{
+    let value = value.ok_or_else(|| format!("{} needs a value",name))?;
+    value.parse::<T>().map_err(|_| format!("invalid value {} for {}",value,name))
+}
+
+// args without the program name
+pub fn parse<I: Iterator<Item=String>>(args:I) -> Result<Options,String>
+{
+    let mut options = Options{
+        scene:String::from("in.yml"),
+        output:String::from("out.png"),
+        width:None,
+        height:None,
+        fov:None,
+        samples:None,
+        quiet:false,
+        help:false
+    };
+    let mut positional = Vec::<String>::new();
+    let mut args = args;
+
+    while let Some(arg) = args.next()
+    {
+        // --name=value is the same as --name value
+        let (name,inline) = match arg.find('=') {
+            Some(i) if arg.starts_with("--") => (arg[..i].to_string(),Some(arg[i+1..].to_string())),
+            _                                => (arg.clone(),None)
+        };
+        let mut next = || inline.clone().or_else(|| args.next());
+        match name.as_str() {
+            "--width"       => options.width   = Some(value(&name,next())?),
+            "--height"      => options.height  = Some(value(&name,next())?),
+            "--fov"         => options.fov     = Some(value(&name,next())?),
+            "--samples"     => options.samples = Some(value(&name,next())?),
+            "-q" | "--quiet" => options.quiet  = true,
+            "-h" | "--help" => options.help    = true,
+            _ if name.starts_with('-') && name.len() > 1 => return Err(format!("unknown option {}",name)),
+            _               => positional.push(arg)
+        }
+    }
+
+    if positional.len() > 2 { return Err(format!("unexpected argument {}",positional[2])); }
+    let mut positional = positional.into_iter();
+    if let Some(scene)  = positional.next() { options.scene  = scene; }
+    if let Some(output) = positional.next() { options.output = output; }
+
+    if options.width == Some(0) || options.height == Some(0) { return Err(String::from("the image needs at least one pixel")); }
+    if options.samples == Some(0) { return Err(String::from("at least one sample per pixel is needed")); }
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(line:&str) -> Result<Options,String>
+    {
+        parse(line.split_whitespace().map(String::from))
+    }
+
+    #[test]
+    fn test_defaults() {
+        let options = args("").unwrap();
+
+        assert_eq!("in.yml",options.scene);
+        assert_eq!("out.png",options.output);
+        assert_eq!(None,options.width);
+        assert!(!options.quiet);
+    }
+
+    #[test]
+    fn test_overrides() {
+        let options = args("--width 320 scenes/a.yml --height=200 -q --fov 45.5 renders/a.png --samples 64").unwrap();
+
+        assert_eq!("scenes/a.yml",options.scene);
+        assert_eq!("renders/a.png",options.output);
+        assert_eq!(Some(320),options.width);
+        assert_eq!(Some(200),options.height);
+        assert_eq!(Some(45.5),options.fov);
+        assert_eq!(Some(64),options.samples);
+        assert!(options.quiet);
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(args("--width").is_err());
+        assert!(args("--width wide").is_err());
+        assert!(args("--width 0").is_err());
+        assert!(args("--samples 0").is_err());
+        assert!(args("--verbose").is_err());
+        assert!(args("a.yml b.png c.png").is_err());
+    }
+}
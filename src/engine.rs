@@ -1,2 +1,19 @@
 pub mod camera;
 pub mod tracer;
+pub mod bvh;
+pub mod shape;
+pub mod scene;
+pub mod color;
+pub mod material;
+pub mod light;
+pub mod integrator;
+pub mod framebuffer;
+pub mod filter;
+pub mod tonemap;
+pub mod aov;
+pub mod triangle;
+pub mod ray;
+pub mod texture;
+pub mod microfacet;
+pub mod bsdf;
+pub mod environment;
\ No newline at end of file
@@ -0,0 +1,53 @@
+use crate::math::vector3::Vec3;
+use crate::engine::color::Color;
+
+// auxiliary images rendered next to the beauty one
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Pass
+{
+	// camera space depth
+	Depth,
+	// world space normal facing the camera
+	Normal,
+	// diffuse color of the material
+	Albedo,
+	// index of the triangle, or of the analytic object counted after the
+	// triangles, see Scene::primitive_id
+	Primitive,
+	// mesh group or analytic object, see Scene::object_id
+	Object,
+	// coverage of the pixel
+	Alpha
+}
+
+// first surface met by the first camera ray of a pixel, which goes through
+// its center when the pixel has a single sample
+#[derive(Debug, Clone)]
+pub struct Surface
+{
+	pub depth:f64,
+	pub normal:Vec3,
+	pub albedo:Color,
+	pub primitive:usize,
+	pub object:usize
+}
+
+impl Pass {
+	pub fn name(&self) -> &'static str
+	{
+		match self {
+			Pass::Depth     => "depth",
+			Pass::Normal    => "normal",
+			Pass::Albedo    => "albedo",
+			Pass::Primitive => "primitive",
+			Pass::Object    => "object",
+			Pass::Alpha     => "alpha"
+		}
+	}
+
+	// ids and depths need more than the 11 bits of a half float
+	pub fn needs_float(&self) -> bool
+	{
+		matches!(self,Pass::Depth | Pass::Primitive | Pass::Object)
+	}
+}
@@ -0,0 +1,724 @@
+use crate::math::vector3::Vec3;
+use crate::math::random::cosine_hemisphere;
+use crate::engine::color::Color;
+use crate::engine::material::Material;
+use crate::engine::microfacet::{Ggx,SMOOTH_ALPHA,gtr1,sample_gtr1};
+
+use std::f64::consts::PI;
+
+// How a surface scatters light. Directions are unit vectors of the shading
+// frame, z being the shading normal on the side of wo, and both point away
+// from the surface : wo to the viewer, wi to the light.
+pub trait Bsdf
+{
+	// radiance sent to wo per unit of irradiance coming from wi
+	fn evaluate(&self, wo:&Vec3, wi:&Vec3) -> Color;
+	// wi drawn for wo with u uniform in [0,1)^3, none when the path ends
+	fn sample(&self, wo:&Vec3, u:&[f64;3]) -> Option<BsdfSample>;
+	// density of the directions drawn by sample, without its specular ones
+	fn pdf(&self, wo:&Vec3, wi:&Vec3) -> f64;
+	// perfect mirror and refraction directions with their weights, all
+	// followed at once by the whitted integrator
+	fn specular(&self, _wo:&Vec3) -> Vec<(Vec3,Color)>
+	{
+		Vec::new()
+	}
+}
+
+pub struct BsdfSample
+{
+	pub direction:Vec3,
+	// evaluate*|cos|/pdf, what the throughput of the path is multiplied by
+	pub weight:Color,
+	pub pdf:f64,
+	// a mirror or refraction direction, lights can not be sampled for them
+	pub specular:bool
+}
+
+// orthonormal frame around a shading normal
+pub struct Frame
+{
+	pub tangent:Vec3,
+	pub bitangent:Vec3,
+	pub normal:Vec3
+}
+
+impl Frame {
+	pub fn new(normal:&Vec3) -> Frame
+	{
+		let (tangent,bitangent) = normal.basis();
+		Frame{tangent,bitangent,normal:normal.clone()}
+	}
+
+	pub fn to_local(&self, v:&Vec3) -> Vec3
+	{
+		Vec3::new(v.dot(&self.tangent),v.dot(&self.bitangent),v.dot(&self.normal))
+	}
+
+	pub fn to_world(&self, v:&Vec3) -> Vec3
+	{
+		self.tangent.mul(v.x).add(&self.bitangent.mul(v.y)).add(&self.normal.mul(v.z))
+	}
+}
+
+fn mirror(wo:&Vec3) -> Vec3
+{
+	Vec3::new(-wo.x,-wo.y,wo.z)
+}
+
+// wo reflected by the microfacet of normal h
+fn reflect(wo:&Vec3, h:&Vec3) -> Vec3
+{
+	h.mul(2.0*wo.dot(h)).sub(wo)
+}
+
+fn mix(a:&Color, b:&Color, t:f64) -> Color
+{
+	a.mul(1.0 - t).add(&b.mul(t))
+}
+
+// sample of a non specular direction, weighted by the whole bsdf
+fn glossy_sample(bsdf:&dyn Bsdf, wo:&Vec3, wi:Vec3) -> Option<BsdfSample>
+{
+	let pdf = bsdf.pdf(wo,&wi);
+	if pdf <= 0.0 { return None; }
+	Some(BsdfSample{weight:bsdf.evaluate(wo,&wi).mul(wi.z.abs()/pdf),direction:wi,pdf,specular:false})
+}
+
+// share of the light reflected by a dielectric interface, cos_i being the
+// cosine of the incident angle and eta the ratio n2/n1 of the indices
+pub fn fresnel_dielectric(cos_i:f64, eta:f64) -> f64
+{
+	let cos_i  = cos_i.clamp(0.0,1.0);
+	let sin2_t = (1.0 - cos_i*cos_i)/(eta*eta);
+	if sin2_t >= 1.0 { return 1.0; }
+	let cos_t  = (1.0 - sin2_t).sqrt();
+	let rs     = (cos_i - eta*cos_t)/(cos_i + eta*cos_t);
+	let rp     = (eta*cos_i - cos_t)/(eta*cos_i + cos_t);
+	0.5*(rs*rs + rp*rp)
+}
+
+// reflectance of a metal of complex index eta + i*k, per channel
+pub fn fresnel_conductor(cos_i:f64, eta:&Color, k:&Color) -> Color
+{
+	let cos2 = cos_i.clamp(0.0,1.0).powi(2);
+	let sin2 = 1.0 - cos2;
+	let channel = |eta:f64,k:f64| {
+		let t0   = eta*eta - k*k - sin2;
+		let a2b2 = (t0*t0 + 4.0*eta*eta*k*k).sqrt();
+		let a    = (0.5*(a2b2 + t0)).max(0.0).sqrt();
+		let t1   = a2b2 + cos2;
+		let t2   = 2.0*cos2.sqrt()*a;
+		let rs   = (t1 - t2)/(t1 + t2);
+		let t3   = cos2*a2b2 + sin2*sin2;
+		let t4   = t2*sin2;
+		let rp   = rs*(t3 - t4)/(t3 + t4);
+		0.5*(rs + rp)
+	};
+	Color::new(channel(eta.r,k.r),channel(eta.g,k.g),channel(eta.b,k.b))
+}
+
+// measured metals, indices at 650, 550 and 450nm
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Metal
+{
+	Gold,
+	Copper,
+	Aluminium
+}
+
+impl Metal {
+	// eta and k
+	pub fn ior(&self) -> (Color,Color)
+	{
+		match self {
+			Metal::Gold      => (Color::new(0.143,0.374,1.442),Color::new(3.983,2.385,1.603)),
+			Metal::Copper    => (Color::new(0.200,0.924,1.102),Color::new(3.912,2.452,2.142)),
+			Metal::Aluminium => (Color::new(1.657,0.880,0.521),Color::new(9.224,6.270,4.837))
+		}
+	}
+}
+
+// the historical material : lambert and blinn-phong weighted by what the
+// mirror and transparent shares leave
+pub struct Phong<'a>
+{
+	pub material:&'a Material,
+	pub albedo:Color,
+	// n1/n2 of the interface, as Vec3::refract takes it
+	pub eta:f64
+}
+
+impl Phong<'_> {
+	// diffuse, mirror and refraction shares seen from wo
+	fn shares(&self, wo:&Vec3) -> (f64,f64,f64)
+	{
+		let m       = self.material;
+		let fresnel = if m.transparency > 0.0 { m.fresnel(wo.z,self.eta) } else { 0.0 };
+		((1.0 - m.reflectivity - m.transparency).max(0.0),m.reflectivity + m.transparency*fresnel,m.transparency*(1.0 - fresnel))
+	}
+
+	// probability of drawing the highlight rather than the lambertian lobe
+	fn highlight_share(&self) -> f64
+	{
+		let (kd,ks) = (self.albedo.luminance(),self.material.specular.luminance());
+		if ks <= 0.0 { 0.0 } else { ks/(kd + ks) }
+	}
+
+	// density of wi drawn from a half vector of density proportional to
+	// cos^shininess, the shape of the highlight
+	fn highlight_pdf(&self, wo:&Vec3, wi:&Vec3) -> f64
+	{
+		let h = wi.add(wo);
+		if h.norm() == 0.0 { return 0.0; }
+		let h = h.normalize();
+		if h.z <= 0.0 || wo.dot(&h) <= 0.0 { return 0.0; }
+		let shininess = self.material.shininess;
+		(shininess + 1.0)/(2.0*PI)*h.z.powf(shininess)/(4.0*wo.dot(&h))
+	}
+}
+
+impl Bsdf for Phong<'_> {
+	fn evaluate(&self, wo:&Vec3, wi:&Vec3) -> Color
+	{
+		let (diffuse,_,_) = self.shares(wo);
+		if wi.z <= 0.0 || diffuse == 0.0 { return Color::black(); }
+
+		let mut f = self.albedo.mul(1.0/PI);
+		if !self.material.specular.is_black()
+		{
+			let highlight = wi.add(wo).normalize().z.max(0.0).powf(self.material.shininess);
+			f = f.add(&self.material.specular.mul(highlight/(PI*wi.z)));
+		}
+		f.mul(diffuse)
+	}
+
+	fn sample(&self, wo:&Vec3, u:&[f64;3]) -> Option<BsdfSample>
+	{
+		let (diffuse,mirrored,refract) = self.shares(wo);
+		let total = diffuse + mirrored + refract;
+		if total <= 0.0 { return None; }
+
+		// picking an event with a probability proportional to its weight
+		// leaves only the sum of the weights in the estimator
+		let event = u[0]*total;
+		if event >= diffuse + mirrored
+		{
+			if let Some(refracted) = wo.mul(-1.0).refract(&Vec3::new(0.0,0.0,1.0),self.eta) {
+				return Some(BsdfSample{direction:refracted,weight:Color::white().mul(total),pdf:refract/total,specular:true});
+			}
+		}
+		if event >= diffuse
+		{
+			return Some(BsdfSample{direction:mirror(wo),weight:Color::white().mul(total),pdf:mirrored/total,specular:true});
+		}
+		// the rest of u[0] picks the lobe
+		let wi = if event/diffuse < self.highlight_share() {
+			let cos = u[1].powf(1.0/(self.material.shininess + 1.0));
+			let sin = (1.0 - cos*cos).max(0.0).sqrt();
+			let phi = 2.0*PI*u[2];
+			reflect(wo,&Vec3::new(sin*phi.cos(),sin*phi.sin(),cos))
+		} else {
+			cosine_hemisphere(u[1],u[2])
+		};
+		glossy_sample(self,wo,wi)
+	}
+
+	fn pdf(&self, wo:&Vec3, wi:&Vec3) -> f64
+	{
+		let (diffuse,reflect,refract) = self.shares(wo);
+		if wi.z <= 0.0 || diffuse == 0.0 { return 0.0; }
+		let highlight = self.highlight_share();
+		diffuse/(diffuse + reflect + refract)*((1.0 - highlight)*wi.z/PI + highlight*self.highlight_pdf(wo,wi))
+	}
+
+	fn specular(&self, wo:&Vec3) -> Vec<(Vec3,Color)>
+	{
+		let (_,reflect,refract) = self.shares(wo);
+		let mut directions = Vec::new();
+		if refract > 0.0
+		{
+			if let Some(refracted) = wo.mul(-1.0).refract(&Vec3::new(0.0,0.0,1.0),self.eta) {
+				directions.push((refracted,Color::white().mul(refract)));
+			}
+		}
+		if reflect > 0.0 { directions.push((mirror(wo),Color::white().mul(reflect))); }
+		directions
+	}
+}
+
+pub struct Lambert
+{
+	pub albedo:Color
+}
+
+impl Bsdf for Lambert {
+	fn evaluate(&self, _wo:&Vec3, wi:&Vec3) -> Color
+	{
+		if wi.z <= 0.0 { return Color::black(); }
+		self.albedo.mul(1.0/PI)
+	}
+
+	fn sample(&self, wo:&Vec3, u:&[f64;3]) -> Option<BsdfSample>
+	{
+		let wi = cosine_hemisphere(u[1],u[2]);
+		Some(BsdfSample{pdf:self.pdf(wo,&wi),weight:self.albedo,direction:wi,specular:false})
+	}
+
+	fn pdf(&self, _wo:&Vec3, wi:&Vec3) -> f64
+	{
+		wi.z.max(0.0)/PI
+	}
+}
+
+// metal with GGX microfacets, a perfect mirror when smooth
+pub struct Conductor
+{
+	pub ggx:Ggx,
+	pub eta:Color,
+	pub k:Color
+}
+
+impl Bsdf for Conductor {
+	fn evaluate(&self, wo:&Vec3, wi:&Vec3) -> Color
+	{
+		if self.ggx.is_smooth() || wo.z <= 0.0 || wi.z <= 0.0 { return Color::black(); }
+		let h = wo.add(wi).normalize();
+		fresnel_conductor(wo.dot(&h),&self.eta,&self.k).mul(self.ggx.d(&h)*self.ggx.g(wo,wi)/(4.0*wo.z*wi.z))
+	}
+
+	fn sample(&self, wo:&Vec3, u:&[f64;3]) -> Option<BsdfSample>
+	{
+		if wo.z <= 0.0 { return None; }
+		if self.ggx.is_smooth()
+		{
+			return Some(BsdfSample{direction:mirror(wo),weight:fresnel_conductor(wo.z,&self.eta,&self.k),pdf:1.0,specular:true});
+		}
+		let wi = reflect(wo,&self.ggx.sample_visible(wo,u[1],u[2]));
+		if wi.z <= 0.0 { return None; }
+		glossy_sample(self,wo,wi)
+	}
+
+	fn pdf(&self, wo:&Vec3, wi:&Vec3) -> f64
+	{
+		if self.ggx.is_smooth() || wo.z <= 0.0 || wi.z <= 0.0 { return 0.0; }
+		let h = wo.add(wi).normalize();
+		self.ggx.visible_pdf(wo,&h)/(4.0*wo.dot(&h))
+	}
+
+	fn specular(&self, wo:&Vec3) -> Vec<(Vec3,Color)>
+	{
+		if !self.ggx.is_smooth() || wo.z <= 0.0 { return Vec::new(); }
+		vec![(mirror(wo),fresnel_conductor(wo.z,&self.eta,&self.k))]
+	}
+}
+
+// glass like interface reflecting and refracting through GGX microfacets
+// (Walter et al. 2007). Radiance is scaled by 1/eta^2 going through, which
+// cancels out on the way back.
+pub struct Dielectric
+{
+	pub ggx:Ggx,
+	// n2/n1, the index of the side wi goes to over the one of wo
+	pub eta:f64
+}
+
+impl Dielectric {
+	// generalized half vector of a refraction, on the side of wo
+	fn refraction_half(&self, wo:&Vec3, wi:&Vec3) -> Option<Vec3>
+	{
+		let h = wo.add(&wi.mul(self.eta));
+		if h.norm() == 0.0 { return None; }
+		let h = if h.z < 0.0 { h.normalize().mul(-1.0) } else { h.normalize() };
+		if wo.dot(&h) <= 0.0 || wi.dot(&h) >= 0.0 { return None; }
+		Some(h)
+	}
+}
+
+impl Bsdf for Dielectric {
+	fn evaluate(&self, wo:&Vec3, wi:&Vec3) -> Color
+	{
+		if self.ggx.is_smooth() || wo.z <= 0.0 || wi.z == 0.0 { return Color::black(); }
+		if wi.z > 0.0
+		{
+			let h = wo.add(wi).normalize();
+			let f = fresnel_dielectric(wo.dot(&h),self.eta);
+			return Color::white().mul(f*self.ggx.d(&h)*self.ggx.g(wo,wi)/(4.0*wo.z*wi.z));
+		}
+		let h = match self.refraction_half(wo,wi) {
+			Some(h) => h,
+			None    => return Color::black()
+		};
+		let (oh,ih) = (wo.dot(&h),wi.dot(&h));
+		let denom   = oh + self.eta*ih;
+		let t       = 1.0 - fresnel_dielectric(oh,self.eta);
+		Color::white().mul(t*self.ggx.d(&h)*self.ggx.g(wo,wi)*(ih*oh).abs()/((wi.z*wo.z).abs()*denom*denom))
+	}
+
+	fn sample(&self, wo:&Vec3, u:&[f64;3]) -> Option<BsdfSample>
+	{
+		if wo.z <= 0.0 { return None; }
+		let up = Vec3::new(0.0,0.0,1.0);
+		if self.ggx.is_smooth()
+		{
+			let f = fresnel_dielectric(wo.z,self.eta);
+			if u[0] < f { return Some(BsdfSample{direction:mirror(wo),weight:Color::white(),pdf:f,specular:true}); }
+			let refracted = wo.mul(-1.0).refract(&up,1.0/self.eta)?;
+			return Some(BsdfSample{direction:refracted,weight:Color::white().mul(1.0/(self.eta*self.eta)),pdf:1.0 - f,specular:true});
+		}
+
+		let h = self.ggx.sample_visible(wo,u[1],u[2]);
+		let wi = if u[0] < fresnel_dielectric(wo.dot(&h),self.eta) {
+			let wi = reflect(wo,&h);
+			if wi.z <= 0.0 { return None; }
+			wi
+		} else {
+			let wi = wo.mul(-1.0).refract(&h,1.0/self.eta)?;
+			if wi.z >= 0.0 { return None; }
+			wi
+		};
+		glossy_sample(self,wo,wi)
+	}
+
+	fn pdf(&self, wo:&Vec3, wi:&Vec3) -> f64
+	{
+		if self.ggx.is_smooth() || wo.z <= 0.0 || wi.z == 0.0 { return 0.0; }
+		if wi.z > 0.0
+		{
+			let h = wo.add(wi).normalize();
+			return fresnel_dielectric(wo.dot(&h),self.eta)*self.ggx.visible_pdf(wo,&h)/(4.0*wo.dot(&h));
+		}
+		let h = match self.refraction_half(wo,wi) {
+			Some(h) => h,
+			None    => return 0.0
+		};
+		let (oh,ih) = (wo.dot(&h),wi.dot(&h));
+		let denom   = oh + self.eta*ih;
+		// jacobian of the refraction from the half vector to wi
+		let dh_dwi  = self.eta*self.eta*ih.abs()/(denom*denom);
+		(1.0 - fresnel_dielectric(oh,self.eta))*self.ggx.visible_pdf(wo,&h)*dh_dwi
+	}
+
+	fn specular(&self, wo:&Vec3) -> Vec<(Vec3,Color)>
+	{
+		if !self.ggx.is_smooth() || wo.z <= 0.0 { return Vec::new(); }
+		let f = fresnel_dielectric(wo.z,self.eta);
+		let mut directions = Vec::new();
+		if let Some(refracted) = wo.mul(-1.0).refract(&Vec3::new(0.0,0.0,1.0),1.0/self.eta) {
+			directions.push((refracted,Color::white().mul((1.0 - f)/(self.eta*self.eta))));
+		}
+		directions.push((mirror(wo),Color::white().mul(f)));
+		directions
+	}
+}
+
+// Burley's principled brdf from Disney (2012) without its transmission :
+// a retro-reflective diffuse base with sheen, a GGX specular layer going
+// from dielectric to metal and a clear coat on top
+pub struct Principled
+{
+	pub base_color:Color,
+	pub metallic:f64,
+	pub roughness:f64,
+	// reflectance at normal incidence of the dielectric, 0.5 being 4%
+	pub specular:f64,
+	pub sheen:f64,
+	pub clearcoat:f64,
+	pub clearcoat_gloss:f64
+}
+
+// (1-cos)^5 of schlick's approximation
+fn schlick_weight(cos:f64) -> f64
+{
+	(1.0 - cos.clamp(0.0,1.0)).powi(5)
+}
+
+impl Principled {
+	fn ggx(&self) -> Ggx
+	{
+		let ggx = Ggx::from_roughness(self.roughness);
+		Ggx{alpha:ggx.alpha.max(SMOOTH_ALPHA)}
+	}
+
+	fn clearcoat_alpha(&self) -> f64
+	{
+		0.1 + (0.001 - 0.1)*self.clearcoat_gloss.clamp(0.0,1.0)
+	}
+
+	// probabilities of sampling the diffuse, specular and clear coat lobes
+	fn lobes(&self) -> (f64,f64,f64)
+	{
+		let diffuse = 1.0 - self.metallic.clamp(0.0,1.0);
+		let coat    = 0.25*self.clearcoat.max(0.0);
+		let total   = diffuse + 1.0 + coat;
+		(diffuse/total,1.0/total,coat/total)
+	}
+}
+
+impl Bsdf for Principled {
+	fn evaluate(&self, wo:&Vec3, wi:&Vec3) -> Color
+	{
+		if wo.z <= 0.0 || wi.z <= 0.0 { return Color::black(); }
+		let h        = wo.add(wi).normalize();
+		let cos_d    = wi.dot(&h);
+		let fh       = schlick_weight(cos_d);
+		let metallic = self.metallic.clamp(0.0,1.0);
+
+		// diffuse getting brighter at grazing angles on rough surfaces, and
+		// sheen half tinted by the hue of the base
+		let fd90  = 0.5 + 2.0*cos_d*cos_d*self.roughness;
+		let fd    = (1.0 + (fd90 - 1.0)*schlick_weight(wi.z))*(1.0 + (fd90 - 1.0)*schlick_weight(wo.z));
+		let lum   = self.base_color.luminance();
+		let tint  = if lum > 0.0 { self.base_color.mul(1.0/lum) } else { Color::white() };
+		let sheen = mix(&Color::white(),&tint,0.5).mul(fh*self.sheen);
+		let diffuse = self.base_color.mul(fd/PI).add(&sheen).mul(1.0 - metallic);
+
+		let ggx  = self.ggx();
+		let f0   = mix(&Color::white().mul(0.08*self.specular),&self.base_color,metallic);
+		let spec = mix(&f0,&Color::white(),fh).mul(ggx.d(&h)*ggx.g(wo,wi)/(4.0*wo.z*wi.z));
+
+		let coat_g = Ggx{alpha:0.25};
+		let coat   = 0.25*self.clearcoat*(0.04 + 0.96*fh)*gtr1(h.z,self.clearcoat_alpha())*coat_g.g1(wo)*coat_g.g1(wi)/(4.0*wo.z*wi.z);
+
+		diffuse.add(&spec).add(&Color::white().mul(coat))
+	}
+
+	fn sample(&self, wo:&Vec3, u:&[f64;3]) -> Option<BsdfSample>
+	{
+		if wo.z <= 0.0 { return None; }
+		let (diffuse,specular,_) = self.lobes();
+		let wi = if u[0] < diffuse {
+			cosine_hemisphere(u[1],u[2])
+		} else if u[0] < diffuse + specular {
+			reflect(wo,&self.ggx().sample_visible(wo,u[1],u[2]))
+		} else {
+			reflect(wo,&sample_gtr1(self.clearcoat_alpha(),u[1],u[2]))
+		};
+		if wi.z <= 0.0 { return None; }
+		glossy_sample(self,wo,wi)
+	}
+
+	fn pdf(&self, wo:&Vec3, wi:&Vec3) -> f64
+	{
+		if wo.z <= 0.0 || wi.z <= 0.0 { return 0.0; }
+		let (diffuse,specular,coat) = self.lobes();
+		let h  = wo.add(wi).normalize();
+		let oh = wo.dot(&h);
+		if oh <= 0.0 { return diffuse*wi.z/PI; }
+		diffuse*wi.z/PI
+			+ specular*self.ggx().visible_pdf(wo,&h)/(4.0*oh)
+			+ coat*gtr1(h.z,self.clearcoat_alpha())*h.z/(4.0*oh)
+	}
+}
+
+// the bsdf of one of the models, kept on the stack while a point is shaded
+pub enum Scattering<'a>
+{
+	Phong(Phong<'a>),
+	Lambert(Lambert),
+	Conductor(Conductor),
+	Dielectric(Dielectric),
+	Principled(Principled)
+}
+
+impl Scattering<'_> {
+	fn model(&self) -> &dyn Bsdf
+	{
+		match self {
+			Scattering::Phong(bsdf)      => bsdf,
+			Scattering::Lambert(bsdf)    => bsdf,
+			Scattering::Conductor(bsdf)  => bsdf,
+			Scattering::Dielectric(bsdf) => bsdf,
+			Scattering::Principled(bsdf) => bsdf
+		}
+	}
+}
+
+impl Bsdf for Scattering<'_> {
+	fn evaluate(&self, wo:&Vec3, wi:&Vec3) -> Color
+	{
+		self.model().evaluate(wo,wi)
+	}
+
+	fn sample(&self, wo:&Vec3, u:&[f64;3]) -> Option<BsdfSample>
+	{
+		self.model().sample(wo,u)
+	}
+
+	fn pdf(&self, wo:&Vec3, wi:&Vec3) -> f64
+	{
+		self.model().pdf(wo,wi)
+	}
+
+	fn specular(&self, wo:&Vec3) -> Vec<(Vec3,Color)>
+	{
+		self.model().specular(wo)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::random::Rng;
+
+	// albedo of the bsdf seen from wo, by importance sampling
+	fn albedo(bsdf:&dyn Bsdf, wo:&Vec3) -> Color
+	{
+		let mut rng = Rng::new(7,3);
+		let n       = 100000;
+		let mut sum = Color::black();
+		for _ in 0..n
+		{
+			if let Some(sample) = bsdf.sample(wo,&[rng.next_f64(),rng.next_f64(),rng.next_f64()]) {
+				sum = sum.add(&sample.weight);
+			}
+		}
+		sum.mul(1.0/(n as f64))
+	}
+
+	// the same albedo by uniform sampling of the sphere with evaluate, which
+	// checks sample against evaluate and pdf
+	fn albedo_uniform(bsdf:&dyn Bsdf, wo:&Vec3) -> (Color,f64)
+	{
+		let mut rng   = Rng::new(11,5);
+		let n         = 400000;
+		let mut sum   = Color::black();
+		let mut total = 0.0;
+		for _ in 0..n
+		{
+			let z   = 1.0 - 2.0*rng.next_f64();
+			let r   = (1.0 - z*z).sqrt();
+			let phi = 2.0*PI*rng.next_f64();
+			let wi  = Vec3::new(r*phi.cos(),r*phi.sin(),z);
+			sum    = sum.add(&bsdf.evaluate(wo,&wi).mul(z.abs()));
+			total += bsdf.pdf(wo,&wi);
+		}
+		let scale = 4.0*PI/(n as f64);
+		(sum.mul(scale),total*scale)
+	}
+
+	fn assert_close(expected:&Color, value:&Color, tolerance:f64)
+	{
+		let d = (expected.r-value.r).abs().max((expected.g-value.g).abs()).max((expected.b-value.b).abs());
+		assert!(d < tolerance,"{:?} != {:?}",expected,value);
+	}
+
+	fn wo() -> Vec3
+	{
+		Vec3::new(0.5,0.1,0.75f64.sqrt()).normalize()
+	}
+
+	#[test]
+	fn test_frame() {
+		let frame = Frame::new(&Vec3::new(1.0,2.0,-2.0).normalize());
+		let v     = Vec3::new(0.3,-0.2,0.9);
+
+		assert!(frame.to_world(&frame.to_local(&v)).sub(&v).norm() < 1e-12);
+		assert!((frame.to_local(&frame.normal).z-1.0).abs() < 1e-12);
+	}
+
+	#[test]
+	fn test_fresnel() {
+		// 4% for glass at normal incidence, everything past the critical angle
+		assert!((fresnel_dielectric(1.0,1.5)-0.04).abs() < 1e-12);
+		assert_eq!(1.0,fresnel_dielectric(0.3,1.0/1.5));
+		assert!((fresnel_dielectric(0.0,1.5)-1.0).abs() < 1e-12);
+
+		// gold reflects red more than blue, every metal reflects all at grazing angles
+		let (eta,k) = Metal::Gold.ior();
+		let gold    = fresnel_conductor(1.0,&eta,&k);
+		assert!(gold.r > 0.9 && gold.b < 0.5,"{:?}",gold);
+		assert_close(&Color::white(),&fresnel_conductor(0.0,&eta,&k),1e-9);
+		// without absorption it is the one of a dielectric
+		let glass = fresnel_conductor(0.6,&Color::new(1.5,1.5,1.5),&Color::black());
+		assert!((glass.g-fresnel_dielectric(0.6,1.5)).abs() < 1e-9);
+	}
+
+	#[test]
+	fn test_lambert() {
+		let lambert = Lambert{albedo:Color::new(0.5,0.25,1.0)};
+		assert_close(&lambert.albedo,&albedo(&lambert,&wo()),1e-12);
+
+		let (uniform,pdf) = albedo_uniform(&lambert,&wo());
+		assert_close(&lambert.albedo,&uniform,0.02);
+		assert!((pdf-1.0).abs() < 0.02);
+	}
+
+	#[test]
+	fn test_phong() {
+		let material = Material{diffuse:Color::new(0.4,0.2,0.1),specular:Color::new(0.5,0.5,0.5),shininess:20.0,reflectivity:0.2,..Material::default()};
+		let phong    = Phong{material:&material,albedo:material.diffuse,eta:1.0/1.5};
+
+		// the highlight is drawn as evaluate and pdf say, the mirror share
+		// taking the rest
+		let sampled       = albedo(&phong,&wo());
+		let (uniform,pdf) = albedo_uniform(&phong,&wo());
+		assert_close(&sampled,&uniform.add(&Color::new(0.2,0.2,0.2)),0.02);
+		assert!(pdf > 0.7 && pdf <= 0.81,"{}",pdf);
+	}
+
+	#[test]
+	fn test_conductor() {
+		let (eta,k) = Metal::Copper.ior();
+		let rough   = Conductor{ggx:Ggx::from_roughness(0.5),eta,k};
+
+		// sampling agrees with evaluate, and a metal loses a bit of energy
+		// to the masking of its microfacets
+		let sampled       = albedo(&rough,&wo());
+		let (uniform,pdf) = albedo_uniform(&rough,&wo());
+		assert_close(&sampled,&uniform,0.02);
+		assert!(pdf > 0.9 && pdf <= 1.01,"{}",pdf);
+		assert!(sampled.r < 1.0 && sampled.r > 0.7,"{:?}",sampled);
+
+		let smooth = Conductor{ggx:Ggx::from_roughness(0.0),eta,k};
+		let sample = smooth.sample(&wo(),&[0.5,0.5,0.5]).unwrap();
+		assert!(sample.specular);
+		assert!(sample.direction.sub(&mirror(&wo())).norm() < 1e-12);
+		assert_eq!(1,smooth.specular(&wo()).len());
+		assert!(smooth.evaluate(&wo(),&mirror(&wo())).is_black());
+	}
+
+	#[test]
+	fn test_dielectric() {
+		let rough = Dielectric{ggx:Ggx::from_roughness(0.4),eta:1.5};
+
+		// reflection and refraction drawn as evaluate and pdf say
+		let sampled       = albedo(&rough,&wo());
+		let (uniform,pdf) = albedo_uniform(&rough,&wo());
+		assert_close(&sampled,&uniform,0.02);
+		assert!((pdf-1.0).abs() < 0.03,"{}",pdf);
+
+		// a smooth interface splits the light by the fresnel term
+		let smooth     = Dielectric{ggx:Ggx::from_roughness(0.0),eta:1.5};
+		let directions = smooth.specular(&Vec3::new(0.0,0.0,1.0));
+		assert_eq!(2,directions.len());
+		assert!((directions[0].0.z+1.0).abs() < 1e-12);
+		assert!((directions[0].1.g-0.96/2.25).abs() < 1e-12);
+		assert!((directions[1].1.g-0.04).abs() < 1e-12);
+		// total internal reflection going out
+		let out = Dielectric{ggx:Ggx::from_roughness(0.0),eta:1.0/1.5};
+		assert_eq!(1,out.specular(&Vec3::new(0.9,0.0,0.19f64.sqrt())).len());
+	}
+
+	#[test]
+	fn test_principled() {
+		let plastic = Principled{
+			base_color:Color::new(0.8,0.2,0.1),
+			metallic:0.0,
+			roughness:0.5,
+			specular:0.5,
+			sheen:0.5,
+			clearcoat:1.0,
+			clearcoat_gloss:0.8
+		};
+		let sampled       = albedo(&plastic,&wo());
+		let (uniform,pdf) = albedo_uniform(&plastic,&wo());
+		assert_close(&sampled,&uniform,0.03);
+		assert!(pdf > 0.9 && pdf <= 1.01,"{}",pdf);
+
+		// a metal has no diffuse part and takes the base color as reflectance
+		let metal = Principled{metallic:1.0,sheen:0.0,clearcoat:0.0,..plastic};
+		let f     = metal.evaluate(&Vec3::new(0.0,0.0,1.0),&Vec3::new(0.6,0.0,0.8));
+		assert!(f.r > f.g && f.g > f.b,"{:?}",f);
+	}
+}
@@ -0,0 +1,322 @@
+use crate::math::vector3::Vec3;
+use crate::math::aabb::Aabb;
+
+const BIN_COUNT:usize      = 16;
+const MAX_LEAF_SIZE:usize  = 8;
+const TRAVERSAL_COST:f64   = 1.0;
+const INTERSECT_COST:f64   = 1.0;
+
+// a leaf references indices[first..first+count], an inner node has its two
+// children stored next to each other at nodes[first] and nodes[first+1]
+struct Node
+{
+	bounds:Aabb,
+	first:usize,
+	count:usize
+}
+
+pub struct Bvh
+{
+	nodes:Vec<Node>,
+	indices:Vec<usize>
+}
+
+// what the primitive test of closest gives back : the ray parameter of the
+// hit and whatever else was found along with it
+pub trait Distance: Copy
+{
+	fn t(&self) -> f64;
+}
+
+impl Distance for f64
+{
+	fn t(&self) -> f64 { *self }
+}
+
+fn axis(v:&Vec3,a:usize) -> f64
+{
+	match a {
+		0 => v.x,
+		1 => v.y,
+		_ => v.z
+	}
+}
+
+impl Bvh {
+	pub fn new(boxes:&[Aabb]) -> Bvh
+	{
+		let mut bvh = Bvh{
+			nodes:Vec::<Node>::with_capacity(2*boxes.len()),
+			indices:(0..boxes.len()).collect()
+		};
+		if boxes.is_empty() { return bvh; }
+
+		let centroids:Vec<Vec3> = boxes.iter().map(|b| b.centroid()).collect();
+		bvh.nodes.push(Node{bounds:Aabb::empty(),first:0,count:0});
+		bvh.build(0,boxes,&centroids,0,boxes.len());
+		bvh
+	}
+
+	pub fn from_mesh(mesh:&[[Vec3;3]]) -> Bvh
+	{
+		let boxes:Vec<Aabb> = mesh.iter().map(Aabb::from_triangle).collect();
+		Bvh::new(&boxes)
+	}
+
+	fn build(&mut self,node:usize,boxes:&[Aabb],centroids:&[Vec3],start:usize,end:usize)
+	{
+		let mut bounds   = Aabb::empty();
+		let mut centers  = Aabb::empty();
+		for i in &self.indices[start..end]
+		{
+			bounds  = bounds.union(&boxes[*i]);
+			centers = centers.grow(&centroids[*i]);
+		}
+
+		let count = end - start;
+		self.nodes[node] = Node{bounds:bounds.clone(),first:start,count};
+		if count <= 2 { return; }
+
+		let split = self.find_split(boxes,centroids,start,end,&bounds,&centers);
+		let (split_axis,split_pos) = match split {
+			None                    => return,
+			Some((a,pos,cost)) => {
+				if cost >= INTERSECT_COST*(count as f64) && count <= MAX_LEAF_SIZE { return; }
+				(a,pos)
+			}
+		};
+
+		let mut mid = start;
+		for i in start..end
+		{
+			if axis(&centroids[self.indices[i]],split_axis) < split_pos
+			{
+				self.indices.swap(i,mid);
+				mid += 1;
+			}
+		}
+		// binning can fail to separate primitives whose centroids are too
+		// close : fall back to a median cut so the recursion always ends
+		if mid == start || mid == end
+		{
+			mid = start + count/2;
+		}
+
+		let left = self.nodes.len();
+		self.nodes.push(Node{bounds:Aabb::empty(),first:0,count:0});
+		self.nodes.push(Node{bounds:Aabb::empty(),first:0,count:0});
+		self.nodes[node].first = left;
+		self.nodes[node].count = 0;
+
+		self.build(left  ,boxes,centroids,start,mid);
+		self.build(left+1,boxes,centroids,mid  ,end);
+	}
+
+	// surface area heuristic evaluated on BIN_COUNT buckets per axis,
+	// returns the best (axis, position, relative cost)
+	fn find_split(&self,boxes:&[Aabb],centroids:&[Vec3],start:usize,end:usize,bounds:&Aabb,centers:&Aabb) -> Option<(usize,f64,f64)>
+	{
+		let area = bounds.surface_area();
+		let mut best:Option<(usize,f64,f64)> = None;
+
+		for a in 0..3
+		{
+			let min    = axis(&centers.min,a);
+			let extent = axis(&centers.max,a) - min;
+			if extent <= 0.0 { continue; }
+
+			let mut bin_bounds:Vec<Aabb> = (0..BIN_COUNT).map(|_| Aabb::empty()).collect();
+			let mut bin_count = [0_usize;BIN_COUNT];
+			for i in &self.indices[start..end]
+			{
+				let b = (((axis(&centroids[*i],a) - min)/extent*(BIN_COUNT as f64)) as usize).min(BIN_COUNT-1);
+				bin_bounds[b] = bin_bounds[b].union(&boxes[*i]);
+				bin_count[b] += 1;
+			}
+
+			let mut right_area  = [0.0_f64;BIN_COUNT];
+			let mut right_count = [0_usize;BIN_COUNT];
+			let mut acc_bounds  = Aabb::empty();
+			let mut acc_count   = 0;
+			for b in (1..BIN_COUNT).rev()
+			{
+				acc_bounds = acc_bounds.union(&bin_bounds[b]);
+				acc_count += bin_count[b];
+				right_area[b]  = acc_bounds.surface_area();
+				right_count[b] = acc_count;
+			}
+
+			let mut acc_bounds = Aabb::empty();
+			let mut acc_count  = 0;
+			for b in 1..BIN_COUNT
+			{
+				acc_bounds = acc_bounds.union(&bin_bounds[b-1]);
+				acc_count += bin_count[b-1];
+				if acc_count == 0 || right_count[b] == 0 { continue; }
+
+				let cost = TRAVERSAL_COST + INTERSECT_COST*(
+					acc_bounds.surface_area()*(acc_count as f64) +
+					right_area[b]*(right_count[b] as f64)
+				)/area;
+
+				let better = match best {
+					None             => true,
+					Some((_,_,best)) => cost < best
+				};
+				if better
+				{
+					best = Some((a,min + extent*(b as f64)/(BIN_COUNT as f64),cost));
+				}
+			}
+		}
+		best
+	}
+
+	// walks the hierarchy front to back and returns the index and the hit of
+	// the closest primitive accepted by `hit` strictly below t_max. Equal
+	// distances resolve to the lowest index, like a linear scan would.
+	pub fn closest<H,F>(&self,origin:&Vec3,ray:&Vec3,t_max:f64,hit:F) -> Option<(usize,H)>
+		where H: Distance, F: Fn(usize) -> Option<H>
+	{
+		if self.nodes.is_empty() { return None; }
+
+		let inv_ray = Vec3::new(1.0/ray.x,1.0/ray.y,1.0/ray.z);
+		let mut best:Option<(usize,H)> = None;
+		let mut min = t_max;
+
+		let mut stack = Vec::<usize>::with_capacity(64);
+		if self.nodes[0].bounds.intersect(origin,&inv_ray,min).is_some()
+		{
+			stack.push(0);
+		}
+
+		while let Some(n) = stack.pop()
+		{
+			let node = &self.nodes[n];
+			if node.count > 0
+			{
+				for i in &self.indices[node.first..node.first+node.count]
+				{
+					if let Some(h) = hit(*i)
+					{
+						let t      = h.t();
+						let closer = match best {
+							None        => t < min,
+							Some((j,_)) => t < min || (t == min && *i < j)
+						};
+						if closer
+						{
+							min  = t;
+							best = Some((*i,h));
+						}
+					}
+				}
+				continue;
+			}
+
+			let left  = node.first;
+			let right = node.first+1;
+			let t_left  = self.nodes[left ].bounds.intersect(origin,&inv_ray,min);
+			let t_right = self.nodes[right].bounds.intersect(origin,&inv_ray,min);
+
+			match (t_left,t_right) {
+				(Some(tl),Some(tr)) => {
+					if tl <= tr { stack.push(right); stack.push(left); }
+					else        { stack.push(left); stack.push(right); }
+				},
+				(Some(_),None) => stack.push(left),
+				(None,Some(_)) => stack.push(right),
+				(None,None)    => {}
+			}
+		}
+		best
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn grid_mesh(n:usize) -> Vec<[Vec3;3]>
+	{
+		let mut mesh = Vec::<[Vec3;3]>::new();
+		for j in 0..n
+		{
+			for i in 0..n
+			{
+				let x = i as f64;
+				let y = j as f64;
+				let z = ((i*7+j*13)%5) as f64;
+				mesh.push([Vec3::new(x,y,z),Vec3::new(x+1.0,y,z),Vec3::new(x,y+1.0,z)]);
+			}
+		}
+		mesh
+	}
+
+	fn hit_plane(triangle:&[Vec3;3],origin:&Vec3,ray:&Vec3) -> Option<f64>
+	{
+		let t = (triangle[0].z - origin.z)/ray.z;
+		let p = origin.add(&ray.mul(t));
+		let u = p.x - triangle[0].x;
+		let v = p.y - triangle[0].y;
+		if t > 0.0 && u >= 0.0 && v >= 0.0 && u+v <= 1.0 { Some(t) } else { None }
+	}
+
+	#[test]
+	fn test_empty() {
+		let bvh = Bvh::from_mesh(&[]);
+		let out = bvh.closest(&Vec3::null(),&Vec3::new(0.0,0.0,1.0),100.0,|_| Some(1.0));
+
+		assert!(out.is_none());
+	}
+
+	#[test]
+	fn test_closest_matches_linear_scan() {
+		let mesh   = grid_mesh(20);
+		let bvh    = Bvh::from_mesh(&mesh);
+		let origin = Vec3::new(10.0,10.0,-10.0);
+
+		for j in 0..30
+		{
+			for i in 0..30
+			{
+				let ray = Vec3::new((i as f64)-15.0,(j as f64)-15.0,10.0);
+
+				let mut expected:Option<(usize,f64)> = None;
+				let mut min = 100.0;
+				for (index,triangle) in mesh.iter().enumerate()
+				{
+					if let Some(t) = hit_plane(triangle,&origin,&ray) {
+						if min > t { min = t; expected = Some((index,t)); }
+					}
+				}
+
+				let out = bvh.closest(&origin,&ray,100.0,|index| hit_plane(&mesh[index],&origin,&ray));
+				assert_eq!(expected,out);
+			}
+		}
+	}
+
+	#[test]
+	fn test_closest_prefers_lowest_index_on_ties() {
+		let triangle = [Vec3::new(-1.0,-1.0,1.0),Vec3::new(2.0,-1.0,1.0),Vec3::new(-1.0,2.0,1.0)];
+		let mesh:Vec<[Vec3;3]> = (0..10).map(|_| [triangle[0].clone(),triangle[1].clone(),triangle[2].clone()]).collect();
+		let bvh = Bvh::from_mesh(&mesh);
+
+		let out = bvh.closest(&Vec3::null(),&Vec3::new(0.0,0.0,1.0),100.0,|_| Some(1.0));
+
+		assert_eq!(Some((0,1.0)),out);
+	}
+
+	#[test]
+	fn test_closest_respects_t_max() {
+		let mesh = grid_mesh(4);
+		let bvh  = Bvh::from_mesh(&mesh);
+		let origin = Vec3::new(0.2,0.2,-10.0);
+		let ray    = Vec3::new(0.0,0.0,1.0);
+
+		let out = bvh.closest(&origin,&ray,5.0,|index| hit_plane(&mesh[index],&origin,&ray));
+
+		assert!(out.is_none());
+	}
+}
@@ -1,18 +1,40 @@
 use crate::math::vector3::Vec3;
 use crate::math::matrix4::Mat4;
+use crate::engine::ray::Ray;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Projection
+{
+	// rays go through a planar sensor, straight lines stay straight
+	Perspective,
+	// rays are spread with constant angular steps between pixels
+	Fisheye
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FovAxis
+{
+	Horizontal,
+	Vertical
+}
 
 pub struct Camera{
 	pub position:Vec3, 
 	front:Vec3, 
 	left:Vec3,
 	up:Vec3,
-	fov_x:f64
+	fov:f64,
+	pub projection:Projection,
+	pub fov_axis:FovAxis,
+	// clip planes, as distances along the viewing axis
+	pub near:f64,
+	pub far:f64
 }
 
 impl Camera{
 	pub fn new(p:&Vec3,d:&Vec3,u:&Vec3,f:f64) -> Camera
 	{
-		let mut l = u.cross(&d);
+		let mut l = u.cross(d);
 		l = l.div(l.norm());
 		let mut u = d.cross(&l);
 		u = u.div(u.norm());
@@ -22,30 +44,47 @@ impl Camera{
 			front:d.clone(),
 			left:l.clone(),
 			up:u.clone(),
-			fov_x:f
+			fov:f,
+			projection:Projection::Perspective,
+			fov_axis:FovAxis::Horizontal,
+			near:0.0,
+			far:f64::INFINITY
 		}
 	}
 
+	// horizontal and vertical field of view in degrees of the fisheye, whose
+	// angles grow linearly across the image
+	fn fovs(&self, columns:usize, rows:usize) -> (f64,f64)
+	{
+		let ratio = (columns as f64)/(rows as f64);
+		match self.fov_axis {
+			FovAxis::Horizontal => (self.fov, self.fov/ratio),
+			FovAxis::Vertical   => (self.fov*ratio, self.fov)
+		}
+	}
 
 	fn build_rotation_table(&self, columns:usize, rows:usize) -> Vec<(f64,f64)>
 	{
-		let ratio = (columns as f64)/(rows as f64);
 		let mut out = Vec::<(f64,f64)>::with_capacity(columns*rows);
 		for j in 0..rows
 		{
-			let j = j as f64;
-			let v_rot_angle  = j*self.fov_x/ratio/((rows as f64) -1.0)-self.fov_x/ratio/2.0;
-
 			for i in 0..columns
 			{
-				let i = i as f64;
-				let h_rot_angle  = i*self.fov_x/((columns as f64)-1.0)-self.fov_x/2.0;
-				out.push((v_rot_angle,h_rot_angle));
+				out.push(self.fisheye_angles(i as f64, j as f64, columns, rows));
 			}
 		}
 		out
 	}
 
+	// x and y are measured from the center of the top left pixel
+	fn fisheye_angles(&self, x:f64, y:f64, columns:usize, rows:usize) -> (f64,f64)
+	{
+		let (fov_x,fov_y) = self.fovs(columns,rows);
+		let v_rot_angle   = y*fov_y/((rows as f64) -1.0)-fov_y/2.0;
+		let h_rot_angle   = x*fov_x/((columns as f64)-1.0)-fov_x/2.0;
+		(v_rot_angle,h_rot_angle)
+	}
+
 	fn angles_to_ray(&self,v_rot:f64,h_rot:f64) -> Vec3
 	{
 		let v_rot_matrix = Mat4::rotation(&self.left , v_rot);
@@ -55,14 +94,77 @@ impl Camera{
 		h_rot_matrix.mul_vec3(&ray)
 	}
 
+	// the sensor sits at the tip of `front`, so rays keep its length scale
+	// and cross the sensor at a ray parameter of 1. The fov sets the size of
+	// the sensor along its axis, the other one follows from the aspect ratio
+	// so that pixels stay square.
+	fn sensor_ray(&self, x:f64, y:f64, columns:usize, rows:usize) -> Vec3
+	{
+		let ratio = (columns as f64)/(rows as f64);
+		let half  = (self.fov.to_radians()/2.0).tan()*self.front.norm();
+		let (half_width,half_height) = match self.fov_axis {
+			FovAxis::Horizontal => (half,half/ratio),
+			FovAxis::Vertical   => (half*ratio,half)
+		};
+
+		let sx = 2.0*x/(columns as f64) - 1.0;
+		let sy = 2.0*y/(rows as f64)    - 1.0;
+
+		self.front
+			.add(&self.left.mul(sx*half_width))
+			.sub(&self.up.mul(sy*half_height))
+	}
+
+	// ray through the point (x,y) of the image, pixel (i,j) covering
+	// [i,i+1[ x [j,j+1[ from the top left corner
+	pub fn ray(&self,x:f64,y:f64,columns:usize,rows:usize) -> Vec3
+	{
+		match self.projection {
+			Projection::Perspective => self.sensor_ray(x,y,columns,rows),
+			Projection::Fisheye     => {
+				let (v_rot,h_rot) = self.fisheye_angles(x-0.5,y-0.5,columns,rows);
+				self.angles_to_ray(v_rot,h_rot)
+			}
+		}
+	}
+
+	// distance along the viewing axis of the point at t on a camera ray
+	pub fn depth(&self,ray:&Vec3,t:f64) -> f64
+	{
+		t*ray.dot(&self.front.normalize())
+	}
+
+	// camera ray through the point (x,y) of the image, clipped by the near
+	// and far planes. Rays going sideways or backwards, which only a fisheye
+	// makes, never cross the planes and are not clipped.
+	pub fn primary(&self,x:f64,y:f64,columns:usize,rows:usize) -> Ray
+	{
+		let ray   = self.ray(x,y,columns,rows);
+		let along = self.depth(&ray,1.0);
+		if along <= 0.0 { return Ray::new(&self.position,&ray); }
+		Ray::segment(&self.position,&ray,self.near/along,self.far/along)
+	}
+
 	pub fn get_rays(&self,columns:usize,rows:usize) -> Vec<Vec3>
 	{
-		let mut ray_list:Vec<Vec3> = Vec::new();
-		let angles = self.build_rotation_table(columns,rows);
+		let mut ray_list:Vec<Vec3> = Vec::with_capacity(columns*rows);
 
-		for angle in &angles
+		if self.projection == Projection::Fisheye
+		{
+			let angles = self.build_rotation_table(columns,rows);
+			for angle in &angles
+			{
+				ray_list.push(self.angles_to_ray(angle.0,angle.1));
+			}
+			return ray_list;
+		}
+
+		for j in 0..rows
 		{
-			ray_list.push(self.angles_to_ray(angle.0,angle.1));
+			for i in 0..columns
+			{
+				ray_list.push(self.sensor_ray((i as f64)+0.5,(j as f64)+0.5,columns,rows));
+			}
 		}
 		ray_list
 	}
@@ -94,13 +196,106 @@ mod tests {
 		assert_eq!(u.y,cam.up.y);
 		assert_eq!(u.z,cam.up.z);
 
-		assert_eq!(fov,cam.fov_x);
+		assert_eq!(fov,cam.fov);
+		assert_eq!(Projection::Perspective,cam.projection);
 	}
 
 
 	#[test]
 	fn test_build_rotation_table() {
+		let cam   = Camera::new(&Vec3::null(),&Vec3::new(0.0,0.0,1.0),&Vec3::new(0.0,1.0,0.0),90.0);
+		let table = cam.build_rotation_table(5,3);
+
+		assert_eq!(15,table.len());
+		assert_eq!((-27.0,-45.0),table[0]);
+		assert_eq!((  0.0,  0.0),table[7]);
+		assert_eq!(( 27.0, 45.0),table[14]);
+	}
+
+	#[test]
+	fn test_perspective_center_ray() {
+		let d   = Vec3::new(0.0,0.0,10.0);
+		let cam = Camera::new(&Vec3::null(),&d,&Vec3::new(0.0,1.0,0.0),70.0);
+		let ray = cam.sensor_ray(2.5,1.5,5,3);
+
+		assert_eq!(d.x,ray.x);
+		assert_eq!(d.y,ray.y);
+		assert_eq!(d.z,ray.z);
+	}
+
+	#[test]
+	fn test_perspective_fov() {
+		let cam = Camera::new(&Vec3::null(),&Vec3::new(0.0,0.0,2.0),&Vec3::new(0.0,1.0,0.0),90.0);
+
+		// the left edge of the sensor is seen at half the horizontal fov
+		let edge = cam.sensor_ray(0.0,1.5,4,3);
+		assert!((edge.x.abs()-edge.z).abs() < 1e-9);
+		assert!(edge.y.abs() < 1e-9);
+
+		let mut cam = cam;
+		cam.fov_axis = FovAxis::Vertical;
+		let top = cam.sensor_ray(2.0,0.0,4,3);
+		assert!((top.y-top.z).abs() < 1e-9);
+		assert!(top.x.abs() < 1e-9);
+	}
+
+	#[test]
+	fn test_perspective_square_pixels() {
+		let mut cam = Camera::new(&Vec3::null(),&Vec3::new(0.0,0.0,1.0),&Vec3::new(0.0,1.0,0.0),70.0);
+		for (fov_axis,fov,columns,rows) in [(FovAxis::Horizontal,70.0,400,300),(FovAxis::Vertical,120.0,200,100)]
+		{
+			cam.fov_axis = fov_axis;
+			cam.fov      = fov;
+			let corner   = cam.sensor_ray(10.0,20.0,columns,rows);
+			let width    = cam.sensor_ray(11.0,20.0,columns,rows).sub(&corner).norm();
+			let height   = cam.sensor_ray(10.0,21.0,columns,rows).sub(&corner).norm();
+			assert!((width-height).abs() < 1e-12,"{} {}",width,height);
+		}
+	}
+
+	#[test]
+	fn test_ray_matches_pixel_centers() {
+		let mut cam = Camera::new(&Vec3::null(),&Vec3::new(0.0,0.0,1.0),&Vec3::new(0.0,1.0,0.0),90.0);
+		for projection in &[Projection::Perspective,Projection::Fisheye]
+		{
+			cam.projection = *projection;
+			let rays = cam.get_rays(5,3);
+			let ray  = cam.ray(3.5,1.5,5,3);
+			assert!(ray.sub(&rays[8]).norm() < 1e-12);
+		}
+	}
+
+	#[test]
+	fn test_clip_planes() {
+		let mut cam = Camera::new(&Vec3::null(),&Vec3::new(0.0,0.0,2.0),&Vec3::new(0.0,1.0,0.0),90.0);
+		cam.near    = 1.0;
+		cam.far     = 5.0;
+
+		// the planes are crossed at the same depth on every ray
+		for (x,y) in &[(2.0,1.5),(0.0,0.0),(3.5,2.5)]
+		{
+			let ray = cam.primary(*x,*y,4,3);
+			assert!((ray.at(ray.t_min).z-1.0).abs() < 1e-12);
+			assert!((ray.at(ray.t_max).z-5.0).abs() < 1e-12);
+		}
+		assert_eq!(f64::INFINITY,Camera::new(&Vec3::null(),&Vec3::new(0.0,0.0,2.0),&Vec3::new(0.0,1.0,0.0),90.0).primary(2.0,1.5,4,3).t_max);
+	}
+
+	#[test]
+	fn test_perspective_keeps_lines_straight() {
+		let cam  = Camera::new(&Vec3::null(),&Vec3::new(0.0,0.0,1.0),&Vec3::new(0.0,1.0,0.0),90.0);
+		let rays = cam.get_rays(8,6);
 
+		// every ray of a row hits the plane z=1 at the same height
+		for j in 0..6
+		{
+			let height = rays[j*8].y/rays[j*8].z;
+			for i in 0..8
+			{
+				let ray = &rays[j*8+i];
+				assert!((ray.y/ray.z-height).abs() < 1e-12);
+			}
+		}
 	}
 
 
@@ -0,0 +1,83 @@
+// linear rgb, 1.0 being the brightest displayable value of a channel
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Color
+{
+	pub r:f64,
+	pub g:f64,
+	pub b:f64,
+}
+
+impl Color {
+	pub fn new(r:f64,g:f64,b:f64) -> Color { Color{r,g,b} }
+	pub fn black() -> Color { Color::new(0.0,0.0,0.0) }
+	pub fn white() -> Color { Color::new(1.0,1.0,1.0) }
+	pub fn add(&self, c:&Color) -> Color
+	{
+		Color::new(
+			self.r + c.r,
+			self.g + c.g,
+			self.b + c.b
+		)
+	}
+	pub fn mul(&self, s:f64) -> Color
+	{
+		Color::new(
+			self.r * s,
+			self.g * s,
+			self.b * s
+		)
+	}
+	pub fn mul_color(&self, c:&Color) -> Color
+	{
+		Color::new(
+			self.r * c.r,
+			self.g * c.g,
+			self.b * c.b
+		)
+	}
+	pub fn max_component(&self) -> f64
+	{
+		self.r.max(self.g).max(self.b)
+	}
+	pub fn is_black(&self) -> bool
+	{
+		self.r == 0.0 && self.g == 0.0 && self.b == 0.0
+	}
+	// brightness as perceived, rec. 709 weights
+	pub fn luminance(&self) -> f64
+	{
+		0.2126*self.r + 0.7152*self.g + 0.0722*self.b
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_add() {
+		let c = Color::new(0.1,0.2,0.3).add(&Color::new(0.5,0.5,0.5));
+
+		assert_eq!(Color::new(0.1+0.5,0.2+0.5,0.3+0.5),c);
+	}
+
+	#[test]
+	fn test_mul() {
+		let c = Color::new(0.1,0.2,0.3).mul(2.0);
+
+		assert_eq!(Color::new(0.2,0.4,0.6),c);
+	}
+
+	#[test]
+	fn test_mul_color() {
+		let c = Color::new(0.1,0.2,0.3).mul_color(&Color::new(1.0,0.5,0.0));
+
+		assert_eq!(Color::new(0.1,0.1,0.0),c);
+	}
+
+	#[test]
+	fn test_is_black() {
+		assert!(Color::black().is_black());
+		assert!(!Color::new(0.0,0.01,0.0).is_black());
+	}
+}
@@ -0,0 +1,140 @@
+use crate::math::vector3::Vec3;
+use crate::math::distribution::Distribution2d;
+use crate::engine::color::Color;
+use crate::engine::texture::Image;
+
+use std::f64::consts::PI;
+
+// light coming from infinitely far away in every direction, read from an
+// equirectangular (latitude-longitude) image : the top row looks up along
+// y, the center of the image along z and its right along x
+pub struct Environment
+{
+	pub image:Image,
+	// turn around the y axis, in radians
+	pub rotation:f64,
+	pub intensity:f64,
+	// texels by luminance, for importance sampling
+	distribution:Distribution2d
+}
+
+impl Environment {
+	pub fn new(image:Image,rotation:f64,intensity:f64) -> Environment
+	{
+		// texels shrink towards the poles
+		let mut weights = Vec::with_capacity(image.texels.len());
+		for y in 0..image.height
+		{
+			let sin_theta = (PI*((y as f64) + 0.5)/(image.height as f64)).sin();
+			for x in 0..image.width
+			{
+				weights.push(image.texels[y*image.width + x].luminance()*sin_theta);
+			}
+		}
+		let distribution = Distribution2d::new(&weights,image.width,image.height);
+		Environment{image,rotation,intensity,distribution}
+	}
+
+	// position in the image of a unit direction, both in [0,1) from the top
+	// left corner
+	fn position(&self, direction:&Vec3) -> (f64,f64)
+	{
+		let phi = direction.x.atan2(direction.z) - self.rotation;
+		let u   = (0.5 + phi/(2.0*PI)).rem_euclid(1.0);
+		let v   = direction.y.clamp(-1.0,1.0).acos()/PI;
+		(u,v.min(1.0 - f64::EPSILON))
+	}
+
+	fn direction(&self, u:f64, v:f64) -> Vec3
+	{
+		let phi   = 2.0*PI*(u - 0.5) + self.rotation;
+		let theta = PI*v;
+		Vec3::new(theta.sin()*phi.sin(),theta.cos(),theta.sin()*phi.cos())
+	}
+
+	// radiance coming along the opposite of a unit direction
+	pub fn radiance(&self, direction:&Vec3) -> Color
+	{
+		let (u,v) = self.position(direction);
+		let x     = ((u*(self.image.width as f64)) as usize).min(self.image.width - 1);
+		let y     = ((v*(self.image.height as f64)) as usize).min(self.image.height - 1);
+		self.image.texels[y*self.image.width + x].mul(self.intensity)
+	}
+
+	// direction drawn with a density following the luminance of the image,
+	// u1 and u2 being uniform in [0,1), and its solid angle density
+	pub fn sample(&self, u1:f64, u2:f64) -> Option<(Vec3,f64)>
+	{
+		let ((u,v),pdf) = self.distribution.sample(u1,u2);
+		let sin_theta   = (PI*v).sin();
+		if pdf <= 0.0 || sin_theta <= 0.0 { return None; }
+		Some((self.direction(u,v),pdf/(2.0*PI*PI*sin_theta)))
+	}
+
+	pub fn pdf(&self, direction:&Vec3) -> f64
+	{
+		let (u,v)     = self.position(direction);
+		let sin_theta = (PI*v).sin();
+		if sin_theta <= 0.0 { return 0.0; }
+		self.distribution.pdf(u,v)/(2.0*PI*PI*sin_theta)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::random::Rng;
+
+	// 4x2 black map with one bright texel in the upper row, toward +x
+	fn sun() -> Environment
+	{
+		let mut texels = vec![Color::black();8];
+		texels[3] = Color::new(8.0,8.0,8.0);
+		Environment::new(Image{width:4,height:2,texels},0.0,0.5)
+	}
+
+	#[test]
+	fn test_mapping() {
+		let env = sun();
+
+		// the center of the image is along z, its right along x
+		assert_eq!((0.5,0.5),env.position(&Vec3::new(0.0,0.0,1.0)));
+		assert_eq!((0.75,0.5),env.position(&Vec3::new(1.0,0.0,0.0)));
+		assert_eq!(0.0,env.position(&Vec3::new(0.0,1.0,0.0)).1);
+		let d = Vec3::new(0.3,-0.4,0.5).normalize();
+		let (u,v) = env.position(&d);
+		assert!(env.direction(u,v).sub(&d).norm() < 1e-12);
+
+		assert_eq!(Color::new(4.0,4.0,4.0),env.radiance(&Vec3::new(1.0,0.5,-0.1).normalize()));
+		assert!(env.radiance(&Vec3::new(1.0,-0.5,-0.1).normalize()).is_black());
+		assert!(env.radiance(&Vec3::new(1.0,0.5,0.1).normalize()).is_black());
+
+		// turned a quarter, what was along x is along -z
+		let turned = Environment::new(sun().image,PI/2.0,1.0);
+		assert!(!turned.radiance(&Vec3::new(-0.1,0.5,-1.0).normalize()).is_black());
+	}
+
+	#[test]
+	fn test_sampling() {
+		let env = sun();
+		let mut rng = Rng::new(6,1);
+
+		// every sample goes toward the bright texel, with the density pdf
+		// says, which integrates to one over the sphere
+		for _ in 0..1000
+		{
+			let (d,pdf) = env.sample(rng.next_f64(),rng.next_f64()).unwrap();
+			assert!(d.x >= -1e-12 && d.y >= -1e-12 && d.z <= 1e-12,"{:?}",d);
+			assert!((pdf-env.pdf(&d)).abs() < 1e-9*pdf);
+		}
+		let mut total = 0.0;
+		for _ in 0..100000
+		{
+			let z   = 1.0 - 2.0*rng.next_f64();
+			let r   = (1.0 - z*z).sqrt();
+			let phi = 2.0*PI*rng.next_f64();
+			total += env.pdf(&Vec3::new(r*phi.cos(),z,r*phi.sin()))*4.0*PI/100000.0;
+		}
+		assert!((total-1.0).abs() < 0.02,"{}",total);
+	}
+}
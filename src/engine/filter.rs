@@ -0,0 +1,93 @@
+// pixel reconstruction filters : weight of a sample according to its offset
+// from the center of a pixel, in pixels
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Filter
+{
+	// plain average of the samples inside the pixel
+	Box,
+	// linear falloff over one pixel
+	Tent,
+	// gaussian of standard deviation 0.5 cut at 1.5 pixels
+	Gaussian,
+	// Mitchell-Netravali cubic with B = C = 1/3, sharper with slight ringing
+	Mitchell
+}
+
+const GAUSSIAN_SIGMA:f64 = 0.5;
+const MITCHELL_B:f64     = 1.0/3.0;
+const MITCHELL_C:f64     = 1.0/3.0;
+
+impl Filter {
+	// samples further than this on either axis have no weight
+	pub fn radius(&self) -> f64
+	{
+		match self {
+			Filter::Box      => 0.5,
+			Filter::Tent     => 1.0,
+			Filter::Gaussian => 1.5,
+			Filter::Mitchell => 2.0
+		}
+	}
+
+	// filters are separable
+	pub fn weight(&self,dx:f64,dy:f64) -> f64
+	{
+		self.weight_1d(dx)*self.weight_1d(dy)
+	}
+
+	fn weight_1d(&self,d:f64) -> f64
+	{
+		let d = d.abs();
+		if d > self.radius() { return 0.0; }
+		match self {
+			Filter::Box      => 1.0,
+			Filter::Tent     => 1.0 - d,
+			Filter::Gaussian => {
+				// shifted so that the weight reaches 0 at the radius
+				let gaussian = |x:f64| (-x*x/(2.0*GAUSSIAN_SIGMA*GAUSSIAN_SIGMA)).exp();
+				(gaussian(d) - gaussian(self.radius())).max(0.0)
+			},
+			Filter::Mitchell => {
+				let (b,c) = (MITCHELL_B,MITCHELL_C);
+				let value = if d < 1.0 {
+					(12.0 - 9.0*b - 6.0*c)*d*d*d + (-18.0 + 12.0*b + 6.0*c)*d*d + (6.0 - 2.0*b)
+				} else {
+					(-b - 6.0*c)*d*d*d + (6.0*b + 30.0*c)*d*d + (-12.0*b - 48.0*c)*d + (8.0*b + 24.0*c)
+				};
+				value/6.0
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_radius() {
+		for filter in &[Filter::Box,Filter::Tent,Filter::Gaussian,Filter::Mitchell]
+		{
+			assert!(filter.weight(0.0,0.0) > 0.0);
+			assert_eq!(0.0,filter.weight(filter.radius()+0.01,0.0));
+			assert_eq!(0.0,filter.weight(0.0,-filter.radius()-0.01));
+		}
+	}
+
+	#[test]
+	fn test_tent() {
+		assert_eq!(1.0,Filter::Tent.weight(0.0,0.0));
+		assert_eq!(0.25,Filter::Tent.weight(0.5,-0.5));
+	}
+
+	#[test]
+	fn test_mitchell() {
+		// continuous at 1 and 0 at the radius
+		let inside  = Filter::Mitchell.weight_1d(1.0-1e-9);
+		let outside = Filter::Mitchell.weight_1d(1.0+1e-9);
+		assert!((inside-outside).abs() < 1e-6);
+		assert!(Filter::Mitchell.weight_1d(2.0).abs() < 1e-12);
+		// negative lobe
+		assert!(Filter::Mitchell.weight_1d(1.5) < 0.0);
+	}
+}
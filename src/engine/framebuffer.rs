@@ -0,0 +1,115 @@
+use crate::engine::color::Color;
+use crate::engine::tracer::Pixel;
+use crate::engine::tonemap::OutputTransform;
+use crate::engine::aov::{Pass,Surface};
+
+// rendered image in linear high dynamic range rgb plus coverage, row after
+// row from the top left corner
+pub struct Framebuffer
+{
+	pub width:usize,
+	pub height:usize,
+	pub colors:Vec<Color>,
+	pub alpha:Vec<f64>,
+	// what every pixel sees first, when the tracer renders the aovs
+	pub surfaces:Option<Vec<Option<Surface>>>
+}
+
+impl Framebuffer {
+	pub fn new(width:usize,height:usize,colors:Vec<Color>,alpha:Vec<f64>) -> Framebuffer
+	{
+		assert_eq!(width*height,colors.len(),"framebuffer size does not match its pixels");
+		assert_eq!(width*height,alpha.len(),"framebuffer size does not match its coverage");
+		Framebuffer{width,height,colors,alpha,surfaces:None}
+	}
+
+	pub fn color(&self,x:usize,y:usize) -> Color
+	{
+		self.colors[y*self.width+x]
+	}
+
+	// named channels of a pass, the background has an infinite depth, a null
+	// normal and albedo and ids of -1. None when the pass needs the surfaces
+	// and the aovs were not rendered.
+	pub fn channels(&self,pass:Pass) -> Option<Vec<(String,Vec<f64>)>>
+	{
+		if pass == Pass::Alpha { return Some(vec![(String::from("A"),self.alpha.clone())]); }
+
+		let surfaces = self.surfaces.as_ref()?;
+		let channel  = |name:&str,value:&dyn Fn(&Surface) -> f64,background:f64| {
+			let values = surfaces.iter().map(|s| s.as_ref().map_or(background,value)).collect();
+			(String::from(name),values)
+		};
+		Some(match pass {
+			Pass::Depth     => vec![channel("Z",&|s| s.depth,f64::INFINITY)],
+			Pass::Normal    => vec![
+				channel("normal.X",&|s| s.normal.x,0.0),
+				channel("normal.Y",&|s| s.normal.y,0.0),
+				channel("normal.Z",&|s| s.normal.z,0.0)
+			],
+			Pass::Albedo    => vec![
+				channel("albedo.R",&|s| s.albedo.r,0.0),
+				channel("albedo.G",&|s| s.albedo.g,0.0),
+				channel("albedo.B",&|s| s.albedo.b,0.0)
+			],
+			Pass::Primitive => vec![channel("primitive",&|s| s.primitive as f64,-1.0)],
+			Pass::Object    => vec![channel("object",&|s| s.object as f64,-1.0)],
+			Pass::Alpha     => unreachable!()
+		})
+	}
+
+	// 8 bits image ready to be written
+	pub fn to_pixels(&self,transform:&OutputTransform) -> Vec<Pixel>
+	{
+		self.colors.iter().zip(self.alpha.iter())
+			.map(|(c,a)| Pixel::from_color_alpha(&transform.apply(c),*a))
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::engine::tonemap::ToneMap;
+
+	#[test]
+	fn test_color() {
+		let mut colors = vec![Color::black();6];
+		colors[4] = Color::new(4.0,0.5,0.0);
+		let image = Framebuffer::new(3,2,colors,vec![1.0;6]);
+
+		assert_eq!(4.0,image.color(1,1).r);
+		assert_eq!(0.0,image.color(1,0).r);
+	}
+
+	#[test]
+	fn test_to_pixels() {
+		let image     = Framebuffer::new(2,1,vec![Color::new(4.0,0.5,0.0),Color::white()],vec![1.0,0.5]);
+		let transform = OutputTransform{exposure:0.0,tone_map:ToneMap::Clamp,srgb:false};
+		let pixels    = image.to_pixels(&transform);
+
+		assert_eq!(Pixel(255,128,0,255),pixels[0]);
+		assert_eq!(Pixel(255,255,255,128),pixels[1]);
+	}
+
+	#[test]
+	fn test_channels() {
+		use crate::math::vector3::Vec3;
+
+		let mut image  = Framebuffer::new(2,1,vec![Color::black();2],vec![1.0,0.0]);
+		image.surfaces = Some(vec![
+			Some(Surface{depth:2.5,normal:Vec3::new(0.0,1.0,0.0),albedo:Color::white(),primitive:7,object:3}),
+			None
+		]);
+
+		assert_eq!(Some(vec![(String::from("Z"),vec![2.5,f64::INFINITY])]),image.channels(Pass::Depth));
+		assert_eq!(Some(vec![(String::from("object"),vec![3.0,-1.0])]),image.channels(Pass::Object));
+		assert_eq!(3,image.channels(Pass::Normal).unwrap().len());
+		assert_eq!(vec![1.0,0.0],image.channels(Pass::Alpha).unwrap()[0].1);
+
+		// only the alpha pass is there without the aovs
+		image.surfaces = None;
+		assert_eq!(None,image.channels(Pass::Depth));
+		assert!(image.channels(Pass::Alpha).is_some());
+	}
+}
@@ -0,0 +1,284 @@
+use crate::math::random::Rng;
+use crate::engine::tracer::{Tracer,Shading};
+use crate::engine::ray::Ray;
+use crate::engine::scene::{Scene,Hit};
+use crate::engine::color::Color;
+use crate::engine::bsdf::{Bsdf,Frame};
+use crate::engine::light::power_heuristic;
+use crate::math::vector3::Vec3;
+
+// light transport algorithm used to compute the color of a camera ray
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Integrator
+{
+	// direct lighting plus perfect reflection and refraction, one ray per pixel
+	Whitted,
+	// monte carlo path tracing with diffuse interreflections
+	Path
+}
+
+// bounces after which paths may be terminated by russian roulette
+const ROULETTE_DEPTH:usize = 3;
+
+impl Tracer {
+	// one random light path starting with the given ray, which makes the
+	// given hit. At every vertex the lights are sampled directly (next event
+	// estimation) and the path goes on in a direction drawn from the bsdf.
+	// Emitters hit by the path add their light.
+	pub fn path_radiance(&self,scene:&Scene,hit:&Hit,ray:&Ray,rng:&mut Rng) -> Color
+	{
+		let mut radiance   = Color::black();
+		let mut throughput = Color::white();
+		let mut ray        = ray.clone();
+		let mut first      = Some(*hit);
+		// where the ray was sampled from the bsdf and the density of its
+		// direction, none for camera rays and specular bounces
+		let mut sampled:Option<(Vec3,f64)> = None;
+
+		// going through the uncovered part of a surface is not a bounce
+		let mut bounce = 0;
+		while bounce <= self.max_depth
+		{
+			let hit = match first.take().or_else(|| self.closest(scene,&ray)) {
+				Some(hit) => hit,
+				None      => {
+					// the environment was also sampled from the last vertex
+					let weight = match (&sampled,scene.environment()) {
+						(Some((_,pdf)),Some(environment)) => power_heuristic(*pdf,environment.pdf(&ray.direction.normalize())),
+						_                                  => 1.0
+					};
+					radiance = radiance.add(&throughput.mul_color(&self.miss(scene,&ray.direction)).mul(weight));
+					break;
+				}
+			};
+
+			let primitive = hit.primitive;
+			let material  = scene.material(primitive);
+			let point     = ray.at(hit.t);
+			let direction = ray.direction.normalize();
+			if material.opacity < 1.0 && rng.next_f64() >= material.opacity
+			{
+				ray = Ray::spawn(&point,&Tracer::normal(scene,primitive,&point),&direction);
+				continue;
+			}
+			let view      = direction.mul(-1.0);
+			let outward   = Tracer::normal(scene,primitive,&point);
+			let entering  = outward.dot(&view) >= 0.0;
+			let facing    = if entering { outward } else { outward.mul(-1.0) };
+			let lookup    = Tracer::lookup(scene,&hit,&point);
+			let normal    = material.perturb(&lookup,&Tracer::shading_normal(scene,&hit,&facing));
+
+			let frame     = Frame::new(&normal);
+			let wo        = frame.to_local(&view);
+			let shading   = Shading{point:point.clone(),facing:facing.clone(),frame,wo,bsdf:material.bsdf(&lookup,entering)};
+
+			if !material.emission.is_black()
+			{
+				// the lights also reached this emitter from the last vertex
+				let weight = match &sampled {
+					None              => 1.0,
+					Some((from,pdf)) => {
+						let distance  = point.sub(from).norm();
+						let light_pdf = scene.lights().iter().map(|light| light.pdf(scene,primitive,from,distance,facing.dot(&view))).sum();
+						power_heuristic(*pdf,light_pdf)
+					}
+				};
+				radiance = radiance.add(&throughput.mul_color(&material.emission).mul(weight));
+			}
+
+			let direct = self.compute_color(scene,&shading,rng);
+			radiance   = radiance.add(&throughput.mul_color(&direct));
+
+			let sample = match shading.bsdf.sample(&shading.wo,&[rng.next_f64(),rng.next_f64(),rng.next_f64()]) {
+				Some(sample) => sample,
+				None         => break
+			};
+			throughput = throughput.mul_color(&sample.weight);
+			if !sample.specular && bounce >= ROULETTE_DEPTH
+			{
+				let survive = throughput.max_component().clamp(0.05,0.95);
+				if rng.next_f64() >= survive { break; }
+				throughput = throughput.mul(1.0/survive);
+			}
+			if throughput.is_black() { break; }
+
+			ray     = Ray::spawn(&point,&facing,&shading.frame.to_world(&sample.direction));
+			sampled = if sample.specular { None } else { Some((point,sample.pdf)) };
+			bounce += 1;
+		}
+		radiance
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::vector3::Vec3;
+	use crate::engine::tracer::tests::test_tracer;
+	use crate::engine::scene::SceneBuilder;
+	use crate::engine::shape::{Shape,Plane,Rectangle};
+	use crate::engine::material::Material;
+	use crate::engine::environment::Environment;
+	use crate::engine::light::Light;
+	use crate::engine::texture::Image;
+	use crate::engine::bsdf::{Scattering,Phong};
+
+	fn path_tracer() -> Tracer
+	{
+		let mut tracer = test_tracer(1,8);
+		tracer.background = Color::white();
+		tracer.integrator = Integrator::Path;
+		tracer
+	}
+
+	// a grey floor under a white sky : every bounce off the floor escapes
+	// so each path brings back exactly the albedo
+	fn floor(material:Material) -> SceneBuilder
+	{
+		let plane:Box<dyn Shape> = Box::new(Plane{point:Vec3::null(),normal:Vec3::new(0.0,1.0,0.0)});
+		let mut scene = SceneBuilder::new(Vec::new(),vec![plane]);
+		scene.materials = vec![material];
+		scene
+	}
+
+	// path from (0,1,0) along ray, which has to hit something
+	fn radiance(tracer:&Tracer,scene:&Scene,ray:&Vec3,rng:&mut Rng) -> Color
+	{
+		let ray           = Ray::new(&Vec3::new(0.0,1.0,0.0),ray);
+		let hit = tracer.closest(scene,&ray).unwrap();
+		tracer.path_radiance(scene,&hit,&ray,rng)
+	}
+
+	#[test]
+	fn test_diffuse_floor() {
+		let tracer = path_tracer();
+		let scene  = floor(Material{diffuse:Color::new(0.5,0.25,0.0),..Default::default()}).build().unwrap();
+		let mut rng = Rng::new(1,0);
+
+		for _ in 0..100
+		{
+			let color = radiance(&tracer,&scene,&Vec3::new(0.3,-1.0,0.2),&mut rng);
+			assert!((color.r-0.5).abs() < 1e-12);
+			assert!((color.g-0.25).abs() < 1e-12);
+			assert_eq!(0.0,color.b);
+		}
+	}
+
+	#[test]
+	fn test_mirror_floor() {
+		let tracer = path_tracer();
+		let scene  = floor(Material{diffuse:Color::black(),reflectivity:1.0,..Default::default()}).build().unwrap();
+		let mut rng = Rng::new(2,0);
+
+		let color = radiance(&tracer,&scene,&Vec3::new(0.0,-1.0,0.0),&mut rng);
+		assert_eq!(Color::white(),color);
+	}
+
+	// a grey floor under a glowing square, with a black sky : the radiance
+	// under its center is albedo/pi times the irradiance from the square
+	// whether it comes from light samples or bsdf samples
+	#[test]
+	fn test_area_light() {
+		let mut tracer = path_tracer();
+		tracer.background = Color::black();
+		let objects:Vec<Box<dyn Shape>> = vec![
+			Box::new(Plane{point:Vec3::null(),normal:Vec3::new(0.0,1.0,0.0)}),
+			Box::new(Rectangle{corner:Vec3::new(-1.0,2.0,-1.0),edge1:Vec3::new(2.0,0.0,0.0),edge2:Vec3::new(0.0,0.0,2.0)})
+		];
+		let mut scene = SceneBuilder::new(Vec::new(),objects);
+		scene.materials = vec![
+			Material{diffuse:Color::new(0.5,0.5,0.5),..Default::default()},
+			Material{diffuse:Color::black(),emission:Color::white(),..Default::default()}
+		];
+		scene.object_materials = vec![0,1];
+		let scene = scene.build().unwrap();
+		let mut rng = Rng::new(3,0);
+
+		let n = 20000;
+		let mut sum = 0.0;
+		for _ in 0..n
+		{
+			sum += radiance(&tracer,&scene,&Vec3::new(0.0,-1.0,0.0),&mut rng).g;
+		}
+		let mean = sum/(n as f64);
+		assert!((mean-0.1197).abs() < 0.003,"{}",mean);
+
+		// the square itself seen from below
+		let color = radiance(&tracer,&scene,&Vec3::new(0.0,1.0,0.0),&mut rng);
+		assert_eq!(Color::white(),color);
+	}
+
+	// a glossy phong floor under a wide glowing square, whose light samples
+	// rarely fall in the highlight : the path integrator, weighting them
+	// against bsdf samples, converges to the direct lighting of light
+	// samples alone
+	#[test]
+	fn test_phong_highlight() {
+		let mut tracer = path_tracer();
+		tracer.background = Color::black();
+		tracer.max_depth  = 1;
+		let objects:Vec<Box<dyn Shape>> = vec![
+			Box::new(Plane{point:Vec3::null(),normal:Vec3::new(0.0,1.0,0.0)}),
+			Box::new(Rectangle{corner:Vec3::new(-8.5,2.0,-10.0),edge1:Vec3::new(20.0,0.0,0.0),edge2:Vec3::new(0.0,0.0,20.0)})
+		];
+		let glossy    = || Material{diffuse:Color::new(0.2,0.2,0.2),specular:Color::new(0.8,0.8,0.8),shininess:30.0,..Default::default()};
+		let mut scene = SceneBuilder::new(Vec::new(),objects);
+		scene.materials = vec![
+			glossy(),
+			Material{diffuse:Color::black(),emission:Color::white(),..Default::default()}
+		];
+		scene.object_materials = vec![0,1];
+		let scene = scene.build().unwrap();
+		let mut rng = Rng::new(5,0);
+
+		// the floor at (0.5,0,0) seen from (0,1,0)
+		let floor   = glossy();
+		let frame   = Frame::new(&Vec3::new(0.0,1.0,0.0));
+		let view    = Vec3::new(-0.5,1.0,0.0).normalize();
+		let shading = Shading{
+			point:Vec3::new(0.5,0.0,0.0),
+			facing:Vec3::new(0.0,1.0,0.0),
+			wo:frame.to_local(&view),
+			frame,
+			bsdf:Scattering::Phong(Phong{material:&floor,albedo:floor.diffuse,eta:1.0/1.5})
+		};
+		let mut nee = path_tracer();
+		nee.integrator = Integrator::Whitted;
+
+		let n = 40000;
+		let (mut light,mut mis) = (0.0,0.0);
+		for _ in 0..n
+		{
+			light += nee.compute_color(&scene,&shading,&mut rng).g;
+			mis   += radiance(&tracer,&scene,&Vec3::new(0.5,-1.0,0.0),&mut rng).g;
+		}
+		let (light,mis) = (light/(n as f64),mis/(n as f64));
+		assert!(light > 0.3,"{}",light);
+		assert!((light-mis).abs() < 0.03*light,"{} != {}",light,mis);
+	}
+
+	// a grey floor under a uniform environment, the background being
+	// black : light and bsdf samples both see the sky, weighted to albedo*sky
+	#[test]
+	fn test_environment() {
+		let mut tracer = path_tracer();
+		tracer.background = Color::black();
+		let mut scene = floor(Material{diffuse:Color::new(0.5,0.5,0.5),..Default::default()});
+		let sky       = Image{width:4,height:2,texels:vec![Color::new(0.8,0.8,0.8);8]};
+		scene.lights  = vec![Light::Environment(Environment::new(sky,0.0,1.0))];
+		let scene     = scene.build().unwrap();
+		let mut rng = Rng::new(4,0);
+
+		let n = 20000;
+		let mut sum = 0.0;
+		for _ in 0..n
+		{
+			sum += radiance(&tracer,&scene,&Vec3::new(0.2,-1.0,0.1),&mut rng).g;
+		}
+		let mean = sum/(n as f64);
+		assert!((mean-0.4).abs() < 0.005,"{}",mean);
+
+		// rays leaving the scene see the environment, not the background
+		assert_eq!(Color::new(0.8,0.8,0.8),tracer.miss(&scene,&Vec3::new(0.0,2.0,1.0)));
+	}
+}
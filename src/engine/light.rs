@@ -0,0 +1,288 @@
+use crate::math::vector3::Vec3;
+use crate::engine::color::Color;
+use crate::engine::scene::{Scene,Primitive};
+use crate::engine::environment::Environment;
+
+use std::f64::consts::PI;
+
+pub enum Light
+{
+	// infinitely far away light shining along direction
+	Directional{ direction:Vec3, color:Color, intensity:f64 },
+	// omnidirectional light with an inverse square falloff
+	Point{ position:Vec3, color:Color, intensity:f64 },
+	// point light restricted to a cone, full power inside the inner angle
+	// fading to nothing at the outer angle (half angles in degrees)
+	Spot{ position:Vec3, direction:Vec3, inner:f64, outer:f64, color:Color, intensity:f64 },
+	// surfaces of the primitives using the material of index material,
+	// glowing with its emission. cdf accumulates their areas.
+	Area{ material:usize, primitives:Vec<Primitive>, cdf:Vec<f64>, area:f64 },
+	// image of the surroundings, also seen by the rays leaving the scene
+	Environment(Environment)
+}
+
+// what a light brings to a point
+pub struct LightSample
+{
+	// unit vector from the point to the light
+	pub direction:Vec3,
+	// distance to the light, infinite for directional lights
+	pub distance:f64,
+	// what a white lambertian surface facing the light shows of it
+	pub radiance:Color,
+	// solid angle density of direction, infinite for point-like lights
+	pub pdf:f64
+}
+
+// weight of a sample of density pdf amongst the ones of another strategy
+// of density other (multiple importance sampling)
+pub fn power_heuristic(pdf:f64, other:f64) -> f64
+{
+	if pdf.is_infinite() { return 1.0; }
+	let (a,b) = (pdf*pdf,other*other);
+	if a + b == 0.0 { 0.0 } else { a/(a + b) }
+}
+
+fn smoothstep(edge0:f64, edge1:f64, x:f64) -> f64
+{
+	if edge0 >= edge1 { return if x >= edge1 { 1.0 } else { 0.0 }; }
+	let t = ((x - edge0)/(edge1 - edge0)).clamp(0.0,1.0);
+	t*t*(3.0 - 2.0*t)
+}
+
+impl Light {
+	// light reaching point, area lights being sampled at a point of their
+	// surface chosen by u, uniform in [0,1)^3
+	pub fn illuminate(&self, scene:&Scene, point:&Vec3, u:&[f64;3]) -> Option<LightSample>
+	{
+		match self {
+			Light::Directional{direction,color,intensity} => Some(LightSample{
+				direction:direction.mul(-1.0).normalize(),
+				distance:f64::INFINITY,
+				radiance:color.mul(*intensity),
+				pdf:f64::INFINITY
+			}),
+			Light::Point{position,color,intensity} => {
+				let to_light = position.sub(point);
+				let distance = to_light.norm();
+				if distance == 0.0 { return None; }
+				Some(LightSample{
+					direction:to_light.div(distance),
+					distance,
+					radiance:color.mul(intensity/(distance*distance)),
+					pdf:f64::INFINITY
+				})
+			},
+			Light::Spot{position,direction,inner,outer,color,intensity} => {
+				let to_light = position.sub(point);
+				let distance = to_light.norm();
+				if distance == 0.0 { return None; }
+				let l = to_light.div(distance);
+
+				let cos_angle = l.mul(-1.0).dot(&direction.normalize());
+				let cone      = smoothstep(outer.to_radians().cos(),inner.to_radians().cos(),cos_angle);
+				if cone <= 0.0 { return None; }
+
+				Some(LightSample{
+					direction:l,
+					distance,
+					radiance:color.mul(cone*intensity/(distance*distance)),
+					pdf:f64::INFINITY
+				})
+			},
+			Light::Area{primitives,cdf,area,..} => {
+				// primitive picked by area, then a point of it or of the part
+				// of it seen from point
+				let target    = u[0]*area;
+				let index     = cdf.partition_point(|c| *c <= target).min(primitives.len() - 1);
+				let (position,normal) = match scene.sample_visible(primitives[index],point,u[1],u[2]) {
+					Some(visible) => visible,
+					None          => scene.sample(primitives[index],u[1],u[2])?
+				};
+
+				let to_light  = position.sub(point);
+				let distance  = to_light.norm();
+				if distance == 0.0 { return None; }
+				let direction = to_light.div(distance);
+				let pdf       = self.pdf(scene,primitives[index],point,distance,normal.dot(&direction));
+				if pdf <= 0.0 { return None; }
+
+				Some(LightSample{
+					direction,
+					distance,
+					radiance:scene.material(primitives[index]).emission.mul(1.0/(PI*pdf)),
+					pdf
+				})
+			},
+			Light::Environment(environment) => {
+				let (direction,pdf) = environment.sample(u[1],u[2])?;
+				Some(LightSample{
+					radiance:environment.radiance(&direction).mul(1.0/(PI*pdf)),
+					direction,
+					distance:f64::INFINITY,
+					pdf
+				})
+			}
+		}
+	}
+
+	// solid angle density with which illuminate reaches from origin a point
+	// of primitive at distance, cos being the cosine between the direction
+	// and its normal. Zero for the primitives that are not part of the light.
+	pub fn pdf(&self, scene:&Scene, primitive:Primitive, origin:&Vec3, distance:f64, cos:f64) -> f64
+	{
+		match self {
+			Light::Area{material,area,..} => {
+				let share = scene.area(primitive);
+				if scene.material_index(primitive) != *material || share <= 0.0 || cos == 0.0 { return 0.0; }
+				match scene.visible_pdf(primitive,origin) {
+					Some(pdf) => share/area*pdf,
+					None      => distance*distance/(cos.abs()*area)
+				}
+			},
+			_ => 0.0
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::engine::scene::SceneBuilder;
+	use crate::engine::shape::{Shape,Rectangle,Sphere};
+	use crate::engine::material::Material;
+
+	fn empty() -> Scene
+	{
+		SceneBuilder::new(Vec::new(),Vec::new()).build().unwrap()
+	}
+
+	#[test]
+	fn test_directional() {
+		let light  = Light::Directional{direction:Vec3::new(0.0,-2.0,0.0),color:Color::white(),intensity:0.5};
+		let sample = light.illuminate(&empty(),&Vec3::new(3.0,4.0,5.0),&[0.5;3]).unwrap();
+
+		assert_eq!(1.0,sample.direction.y);
+		assert_eq!(f64::INFINITY,sample.distance);
+		assert_eq!(Color::new(0.5,0.5,0.5),sample.radiance);
+	}
+
+	#[test]
+	fn test_point_falloff() {
+		let light  = Light::Point{position:Vec3::new(0.0,2.0,0.0),color:Color::new(1.0,0.5,0.0),intensity:8.0};
+		let sample = light.illuminate(&empty(),&Vec3::null(),&[0.5;3]).unwrap();
+
+		assert_eq!(1.0,sample.direction.y);
+		assert_eq!(2.0,sample.distance);
+		assert_eq!(Color::new(2.0,1.0,0.0),sample.radiance);
+	}
+
+	#[test]
+	fn test_spot_cone() {
+		let light = Light::Spot{
+			position:Vec3::new(0.0,1.0,0.0),
+			direction:Vec3::new(0.0,-1.0,0.0),
+			inner:10.0,
+			outer:20.0,
+			color:Color::white(),
+			intensity:1.0
+		};
+
+		// on the axis
+		let center = light.illuminate(&empty(),&Vec3::null(),&[0.5;3]).unwrap();
+		assert_eq!(Color::white(),center.radiance);
+		// between the inner and outer angles, 15 degrees off axis
+		let edge = light.illuminate(&empty(),&Vec3::new(15.0_f64.to_radians().tan(),0.0,0.0),&[0.5;3]).unwrap();
+		assert!(edge.radiance.r > 0.0 && edge.radiance.r < 1.0);
+		// outside the outer angle, 45 degrees off axis
+		assert!(light.illuminate(&empty(),&Vec3::new(1.0,0.0,0.0),&[0.5;3]).is_none());
+	}
+
+	#[test]
+	fn test_area() {
+		// unit square glowing 2 above the origin, facing it
+		let square:Box<dyn Shape> = Box::new(Rectangle{corner:Vec3::new(-0.5,2.0,-0.5),edge1:Vec3::new(0.0,0.0,1.0),edge2:Vec3::new(1.0,0.0,0.0)});
+		let mut scene = SceneBuilder::new(Vec::new(),vec![square]);
+		scene.materials.push(Material{emission:Color::new(1.0,2.0,3.0),..Material::default()});
+		scene.object_materials = vec![1];
+		let scene = scene.build().unwrap();
+		let light = &scene.lights()[0];
+
+		let center = light.illuminate(&scene,&Vec3::null(),&[0.5;3]).unwrap();
+		assert_eq!(2.0,center.distance);
+		assert_eq!(1.0,center.direction.y);
+		assert_eq!(4.0,center.pdf);
+		assert!((center.radiance.b-0.75/PI).abs() < 1e-12);
+
+		// the irradiance averages to the one of the square, about its area
+		// over the squared distance
+		let mut rng = crate::math::random::Rng::new(2,2);
+		let mut irradiance = 0.0;
+		for _ in 0..10000
+		{
+			let sample = light.illuminate(&scene,&Vec3::null(),&[rng.next_f64(),rng.next_f64(),rng.next_f64()]).unwrap();
+			irradiance += PI*sample.radiance.r*sample.direction.y/10000.0;
+		}
+		assert!((irradiance-0.231).abs() < 0.005,"{}",irradiance);
+
+		// both sides glow
+		assert_eq!(4.0,light.pdf(&scene,Primitive::Object(0),&Vec3::null(),2.0,-1.0));
+		assert_eq!(1.0,power_heuristic(f64::INFINITY,2.0));
+		assert_eq!(0.8,power_heuristic(2.0,1.0));
+	}
+
+	#[test]
+	fn test_sphere() {
+		// unit sphere glowing 4 above the origin : samples stay in the cone it
+		// fills, of half angle asin(1/4)
+		let sphere:Box<dyn Shape> = Box::new(Sphere{center:Vec3::new(0.0,4.0,0.0),radius:1.0});
+		let mut scene = SceneBuilder::new(Vec::new(),vec![sphere]);
+		scene.materials.push(Material{emission:Color::white(),..Material::default()});
+		scene.object_materials = vec![1];
+		let scene = scene.build().unwrap();
+		let light = &scene.lights()[0];
+
+		let cos_max = (15.0_f64/16.0).sqrt();
+		let cone    = 1.0/(2.0*PI*(1.0 - cos_max));
+		let mut rng = crate::math::random::Rng::new(4,4);
+		let mut irradiance = 0.0;
+		for _ in 0..10000
+		{
+			let sample = light.illuminate(&scene,&Vec3::null(),&[rng.next_f64(),rng.next_f64(),rng.next_f64()]).unwrap();
+			assert!(sample.direction.y >= cos_max - 1e-12);
+			assert!((sample.pdf/cone - 1.0).abs() < 1e-9,"{}",sample.pdf);
+			// on the near side of the sphere
+			let point = sample.direction.mul(sample.distance);
+			assert!((point.sub(&Vec3::new(0.0,4.0,0.0)).norm() - 1.0).abs() < 1e-9);
+			assert!(point.y < 4.0);
+			irradiance += PI*sample.radiance.r*sample.direction.y/10000.0;
+		}
+		// pi times the radiance times the squared sine of the half angle
+		assert!((irradiance - PI/16.0).abs() < 0.002,"{}",irradiance);
+		assert!((light.pdf(&scene,Primitive::Object(0),&Vec3::null(),3.0,-1.0)/cone - 1.0).abs() < 1e-9);
+
+		// from inside the whole surface is sampled by area
+		let origin = Vec3::new(0.0,4.5,0.0);
+		let inside = light.illuminate(&scene,&origin,&[0.5,0.5,0.5]).unwrap();
+		let normal = origin.add(&inside.direction.mul(inside.distance)).sub(&Vec3::new(0.0,4.0,0.0));
+		let cos    = normal.dot(&inside.direction);
+		assert!((inside.pdf - inside.distance*inside.distance/(cos*4.0*PI)).abs() < 1e-9);
+	}
+
+	#[test]
+	fn test_environment() {
+		// a uniform sky brings the same as a white lambertian facing it
+		let sky   = crate::engine::texture::Image{width:2,height:2,texels:vec![Color::new(0.5,0.5,0.5);4]};
+		let light = Light::Environment(Environment::new(sky,0.0,2.0));
+		let mut rng = crate::math::random::Rng::new(3,3);
+		let mut irradiance = 0.0;
+		for _ in 0..100000
+		{
+			let sample = light.illuminate(&empty(),&Vec3::null(),&[rng.next_f64(),rng.next_f64(),rng.next_f64()]).unwrap();
+			assert_eq!(f64::INFINITY,sample.distance);
+			irradiance += PI*sample.radiance.r*sample.direction.y.max(0.0)/100000.0;
+		}
+		assert!((irradiance-PI).abs() < 0.03,"{}",irradiance);
+		assert_eq!(0.0,light.pdf(&empty(),Primitive::Object(0),&Vec3::null(),1.0,1.0));
+	}
+}
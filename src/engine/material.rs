@@ -0,0 +1,241 @@
+use crate::engine::color::Color;
+use crate::engine::texture::{Texture,Lookup};
+use crate::engine::bsdf::{Scattering,Phong,Lambert,Conductor,Dielectric,Principled};
+use crate::engine::microfacet::Ggx;
+use crate::math::vector3::Vec3;
+
+// step in texture coordinates of the finite differences of bump maps
+const BUMP_STEP:f64 = 1e-3;
+
+pub struct Material
+{
+	// lambertian albedo
+	pub diffuse:Color,
+	// blinn-phong highlight, black disables it
+	pub specular:Color,
+	pub shininess:f64,
+	// share of the light coming from the mirror direction
+	pub reflectivity:f64,
+	// share of the light going through the surface, split between the
+	// reflected and refracted rays by the fresnel term
+	pub transparency:f64,
+	// index of refraction of the inside of the surface
+	pub ior:f64,
+	// coverage of the surface, the uncovered part letting light through
+	// unchanged as if the surface was not there (leaves, lace, decals)
+	pub opacity:f64,
+	// replaces the diffuse color when set
+	pub texture:Option<Texture>,
+	// tangent space normals, the channels in [0,1] standing for x along u,
+	// y along v and z along the normal in [-1,1]
+	pub normal_map:Option<Texture>,
+	// scales the x and y of the normal map
+	pub normal_strength:f64,
+	// height field, the luminance of the texture times bump_height (in scene
+	// units)
+	pub bump_map:Option<Texture>,
+	pub bump_height:f64,
+	// radiance leaving both sides of the surface, black when it does not glow
+	pub emission:Color,
+	// how the surface scatters light, the albedo being its base color
+	pub model:Model
+}
+
+// scattering models, roughness going from a mirror at 0 to 1
+#[derive(Debug, Clone, PartialEq)]
+pub enum Model
+{
+	// the shares of diffuse, specular, mirror and transparency above
+	Phong,
+	Lambert,
+	// metal of complex index eta + i*k
+	Conductor{eta:Color,k:Color,roughness:f64},
+	// glass of index ior
+	Dielectric{roughness:f64},
+	Principled{metallic:f64,roughness:f64,specular:f64,sheen:f64,clearcoat:f64,clearcoat_gloss:f64}
+}
+
+// plain white diffuse surface, the look of the scenes before materials existed
+impl Default for Material {
+	fn default() -> Material
+	{
+		Material{
+			diffuse:Color::white(),
+			specular:Color::black(),
+			shininess:0.0,
+			reflectivity:0.0,
+			transparency:0.0,
+			ior:1.5,
+			opacity:1.0,
+			texture:None,
+			normal_map:None,
+			normal_strength:1.0,
+			bump_map:None,
+			bump_height:0.01,
+			emission:Color::black(),
+			model:Model::Phong
+		}
+	}
+}
+
+impl Material {
+	// diffuse color at a point of the surface
+	pub fn albedo(&self, lookup:&Lookup) -> Color
+	{
+		match &self.texture {
+			Some(texture) => texture.color(lookup),
+			None          => self.diffuse
+		}
+	}
+
+	// bsdf at a point of the surface, entering telling whether it is seen
+	// from the outside
+	pub fn bsdf(&self, lookup:&Lookup, entering:bool) -> Scattering<'_>
+	{
+		let albedo = self.albedo(lookup);
+		// index of the side light goes to over the one it comes from
+		let eta    = if entering { self.ior } else { 1.0/self.ior };
+		match &self.model {
+			Model::Phong => Scattering::Phong(Phong{material:self,albedo,eta:1.0/eta}),
+			Model::Lambert => Scattering::Lambert(Lambert{albedo}),
+			Model::Conductor{eta,k,roughness} => Scattering::Conductor(Conductor{ggx:Ggx::from_roughness(*roughness),eta:*eta,k:*k}),
+			Model::Dielectric{roughness} => Scattering::Dielectric(Dielectric{ggx:Ggx::from_roughness(*roughness),eta}),
+			Model::Principled{metallic,roughness,specular,sheen,clearcoat,clearcoat_gloss} => Scattering::Principled(Principled{
+				base_color:albedo,
+				metallic:*metallic,
+				roughness:*roughness,
+				specular:*specular,
+				sheen:*sheen,
+				clearcoat:*clearcoat,
+				clearcoat_gloss:*clearcoat_gloss
+			})
+		}
+	}
+
+	// shading normal bent by the bump and normal maps, normal being the one
+	// of the side the surface is seen from
+	pub fn perturb(&self, lookup:&Lookup, normal:&Vec3) -> Vec3
+	{
+		let mut normal = normal.clone();
+		if let Some(bump) = &self.bump_map
+		{
+			// the surface pushed along the normal by the height : its
+			// derivatives gain the slopes of the height field, the shading
+			// normal standing for dpdu x dpdv so that meshes stay smooth
+			let height  = |lookup:&Lookup| bump.color(lookup).luminance()*self.bump_height;
+			let h       = height(lookup);
+			let slope_u = (height(&lookup.shift(BUMP_STEP,0.0)) - h)/BUMP_STEP;
+			let slope_v = (height(&lookup.shift(0.0,BUMP_STEP)) - h)/BUMP_STEP;
+			let surface = lookup.dpdu.cross(&lookup.dpdv);
+			let side    = if surface.dot(&normal) < 0.0 { -1.0 } else { 1.0 };
+			let tilt    = normal.cross(&lookup.dpdv).mul(slope_u).add(&lookup.dpdu.cross(&normal).mul(slope_v));
+			let bumped  = normal.mul(surface.norm()).add(&tilt.mul(side));
+			if bumped.norm() > 0.0 { normal = bumped.normalize(); }
+		}
+		if let Some(map) = &self.normal_map
+		{
+			// tangent along u made orthogonal to the normal, bitangent along v
+			let tangent = lookup.dpdu.sub(&normal.mul(lookup.dpdu.dot(&normal)));
+			if tangent.norm() > 0.0
+			{
+				let tangent   = tangent.normalize();
+				let bitangent = normal.cross(&tangent);
+				let bitangent = if bitangent.dot(&lookup.dpdv) < 0.0 { bitangent.mul(-1.0) } else { bitangent };
+				let c         = map.color(lookup);
+				let mapped    = tangent.mul((2.0*c.r - 1.0)*self.normal_strength)
+					.add(&bitangent.mul((2.0*c.g - 1.0)*self.normal_strength))
+					.add(&normal.mul(2.0*c.b - 1.0));
+				if mapped.norm() > 0.0 { normal = mapped.normalize(); }
+			}
+		}
+		normal
+	}
+
+	// schlick's approximation of the share of light reflected by the surface,
+	// cos_i being the cosine of the incident angle and eta the ratio n1/n2.
+	pub fn fresnel(&self, cos_i:f64, eta:f64) -> f64
+	{
+		let r0 = ((1.0 - self.ior)/(1.0 + self.ior)).powi(2);
+
+		// going to a lower index the transmitted angle is the one that matters
+		let cos = if eta > 1.0 {
+			let sin2_t = eta*eta*(1.0 - cos_i*cos_i);
+			if sin2_t > 1.0 { return 1.0; }
+			(1.0 - sin2_t).sqrt()
+		} else {
+			cos_i
+		};
+		r0 + (1.0 - r0)*(1.0 - cos).powi(5)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::engine::texture::{Image,Filtering,Wrap};
+
+	fn image(width:usize,rgba:&[[u8;4]]) -> Texture
+	{
+		Texture::Image{image:Image::from_rgba(width,1,rgba,false),filtering:Filtering::Bilinear,wrap:Wrap::Clamp}
+	}
+
+	// center of a texture on the plane z=0, u along x and v along y
+	fn lookup() -> Lookup
+	{
+		Lookup{
+			uv:(0.5,0.5),
+			point:Vec3::null(),
+			local:Vec3::null(),
+			dpdu:Vec3::new(1.0,0.0,0.0),
+			dpdv:Vec3::new(0.0,1.0,0.0),
+			local_dpdu:Vec3::new(1.0,0.0,0.0),
+			local_dpdv:Vec3::new(0.0,1.0,0.0)
+		}
+	}
+
+	#[test]
+	fn test_bump_map() {
+		// height going from 0 to 0.5 between the texel centers at u=0.25 and 0.75
+		let ramp = Material{bump_map:Some(image(2,&[[0,0,0,255],[255,255,255,255]])),bump_height:0.5,..Material::default()};
+
+		// the normal leans away from the slope, on the side it is seen from
+		let front = ramp.perturb(&lookup(),&Vec3::new(0.0,0.0,1.0));
+		assert!(front.sub(&Vec3::new(-1.0,0.0,1.0).normalize()).norm() < 1e-9,"{:?}",front);
+		let back  = ramp.perturb(&lookup(),&Vec3::new(0.0,0.0,-1.0));
+		assert!(back.sub(&Vec3::new(-1.0,0.0,-1.0).normalize()).norm() < 1e-9,"{:?}",back);
+
+		// a flat height field keeps the interpolated normal of a mesh
+		let flat     = Material{bump_map:Some(image(1,&[[128,128,128,255]])),bump_height:0.5,..Material::default()};
+		let smooth   = Vec3::new(0.6,0.0,0.8);
+		assert!(flat.perturb(&lookup(),&smooth).sub(&smooth).norm() < 1e-12);
+		// and a slope tilts it rather than the face
+		let tilted   = ramp.perturb(&lookup(),&smooth);
+		let expected = smooth.add(&Vec3::new(-0.8,0.0,0.6)).normalize();
+		assert!(tilted.sub(&expected).norm() < 1e-9,"{:?}",tilted);
+	}
+
+	#[test]
+	fn test_normal_map() {
+		let normal = Vec3::new(0.0,0.0,1.0);
+		let tilted = Material{normal_map:Some(image(1,&[[255,128,255,255]])),..Material::default()};
+
+		// (1,0,1) in tangent space leans along u
+		let n = tilted.perturb(&lookup(),&normal);
+		assert!((n.x-n.z).abs() < 1e-9 && n.y.abs() < 0.01,"{:?}",n);
+		let flat = Material{normal_strength:0.0,..tilted};
+		assert!(flat.perturb(&lookup(),&normal).sub(&normal).norm() < 1e-12);
+		assert!(Material::default().perturb(&lookup(),&normal).sub(&normal).norm() == 0.0);
+	}
+
+	#[test]
+	fn test_fresnel() {
+		let glass = Material{transparency:1.0,ior:1.5,..Material::default()};
+
+		// 4% at normal incidence for glass
+		assert!((glass.fresnel(1.0,1.0/1.5)-0.04).abs() < 1e-12);
+		// everything at grazing angles
+		assert!((glass.fresnel(0.0,1.0/1.5)-1.0).abs() < 1e-12);
+		// total internal reflection when leaving glass
+		assert_eq!(1.0,glass.fresnel(0.5,1.5));
+	}
+}
@@ -0,0 +1,177 @@
+use crate::math::vector3::Vec3;
+
+use std::f64::consts::PI;
+
+// Trowbridge-Reitz (GGX) distribution of microfacet normals around the z
+// axis of the shading frame. Directions are unit vectors of that frame.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ggx
+{
+	pub alpha:f64
+}
+
+// below this the surface is taken as perfectly smooth
+pub const SMOOTH_ALPHA:f64 = 1e-3;
+
+impl Ggx {
+	// perceptual roughness in [0,1], squared as in Disney's model
+	pub fn from_roughness(roughness:f64) -> Ggx
+	{
+		Ggx{alpha:roughness.clamp(0.0,1.0).powi(2)}
+	}
+
+	pub fn is_smooth(&self) -> bool
+	{
+		self.alpha < SMOOTH_ALPHA
+	}
+
+	// density of the microfacet normal h
+	pub fn d(&self,h:&Vec3) -> f64
+	{
+		if h.z <= 0.0 { return 0.0; }
+		let a2 = self.alpha*self.alpha;
+		let t  = h.z*h.z*(a2 - 1.0) + 1.0;
+		a2/(PI*t*t)
+	}
+
+	// Smith's auxiliary function, the share of the surface hidden from w
+	fn lambda(&self,w:&Vec3) -> f64
+	{
+		let cos2 = w.z*w.z;
+		if cos2 == 0.0 { return f64::INFINITY; }
+		let tan2 = (1.0 - cos2).max(0.0)/cos2;
+		0.5*((1.0 + self.alpha*self.alpha*tan2).sqrt() - 1.0)
+	}
+
+	// masking of the microfacets seen from w
+	pub fn g1(&self,w:&Vec3) -> f64
+	{
+		1.0/(1.0 + self.lambda(w))
+	}
+
+	// height correlated masking and shadowing
+	pub fn g(&self,wo:&Vec3,wi:&Vec3) -> f64
+	{
+		1.0/(1.0 + self.lambda(wo) + self.lambda(wi))
+	}
+
+	// density of the normals visible from wo, which sample_visible draws
+	pub fn visible_pdf(&self,wo:&Vec3,h:&Vec3) -> f64
+	{
+		if wo.z <= 0.0 { return 0.0; }
+		self.g1(wo)*wo.dot(h).max(0.0)*self.d(h)/wo.z
+	}
+
+	// Heitz's sampling of the visible normals : the view is stretched to a
+	// unit roughness where the visible normals are a disk projected on a
+	// hemisphere, u1 and u2 being uniform in [0,1)
+	pub fn sample_visible(&self,wo:&Vec3,u1:f64,u2:f64) -> Vec3
+	{
+		let v = Vec3::new(self.alpha*wo.x,self.alpha*wo.y,wo.z).normalize();
+
+		let len2 = v.x*v.x + v.y*v.y;
+		let t1   = if len2 > 0.0 { Vec3::new(-v.y,v.x,0.0).div(len2.sqrt()) } else { Vec3::new(1.0,0.0,0.0) };
+		let t2   = v.cross(&t1);
+
+		// point of the disk, squeezed on the half hidden by the slope
+		let r   = u1.sqrt();
+		let phi = 2.0*PI*u2;
+		let p1  = r*phi.cos();
+		let s   = 0.5*(1.0 + v.z);
+		let p2  = (1.0 - s)*(1.0 - p1*p1).sqrt() + s*r*phi.sin();
+		let n   = t1.mul(p1).add(&t2.mul(p2)).add(&v.mul((1.0 - p1*p1 - p2*p2).max(0.0).sqrt()));
+
+		Vec3::new(self.alpha*n.x,self.alpha*n.y,n.z.max(0.0)).normalize()
+	}
+}
+
+// Burley's generalized Trowbridge-Reitz with an exponent of 1, the long
+// tailed lobe of clear coats
+pub fn gtr1(cos_h:f64,alpha:f64) -> f64
+{
+	if alpha >= 1.0 { return 1.0/PI; }
+	let a2 = alpha*alpha;
+	(a2 - 1.0)/(PI*a2.ln()*(1.0 + (a2 - 1.0)*cos_h*cos_h))
+}
+
+// normal drawn with the density gtr1(h.z)*h.z
+pub fn sample_gtr1(alpha:f64,u1:f64,u2:f64) -> Vec3
+{
+	let a2    = alpha*alpha;
+	let cos_h = if alpha >= 1.0 { (1.0 - u1).sqrt() } else { ((1.0 - a2.powf(1.0 - u1))/(1.0 - a2)).max(0.0).sqrt() };
+	let sin_h = (1.0 - cos_h*cos_h).max(0.0).sqrt();
+	let phi   = 2.0*PI*u2;
+	Vec3::new(sin_h*phi.cos(),sin_h*phi.sin(),cos_h)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::random::Rng;
+
+	// integral over the hemisphere of f, by uniform sampling
+	fn hemisphere(f:impl Fn(&Vec3) -> f64) -> f64
+	{
+		let mut rng = Rng::new(3,1);
+		let n       = 200000;
+		let mut sum = 0.0;
+		for _ in 0..n
+		{
+			let z   = rng.next_f64();
+			let r   = (1.0 - z*z).sqrt();
+			let phi = 2.0*PI*rng.next_f64();
+			sum += f(&Vec3::new(r*phi.cos(),r*phi.sin(),z));
+		}
+		sum*2.0*PI/(n as f64)
+	}
+
+	#[test]
+	fn test_normalized() {
+		// the projected area of the microfacets is the one of the surface
+		for alpha in &[0.1,0.5,1.0]
+		{
+			let ggx = Ggx{alpha:*alpha};
+			let area = hemisphere(|h| ggx.d(h)*h.z);
+			assert!((area-1.0).abs() < 0.03,"{} for {}",area,alpha);
+		}
+		let area = hemisphere(|h| gtr1(h.z,0.3)*h.z);
+		assert!((area-1.0).abs() < 0.03,"{}",area);
+	}
+
+	#[test]
+	fn test_visible_normals() {
+		let ggx = Ggx{alpha:0.4};
+		let wo  = Vec3::new(0.6,0.0,0.8);
+
+		// the visible normals have the density of visible_pdf, which is normalized
+		let total = hemisphere(|h| ggx.visible_pdf(&wo,h));
+		assert!((total-1.0).abs() < 0.03,"{}",total);
+
+		let mut rng  = Rng::new(5,2);
+		let mut mean = 0.0;
+		let mut expected = 0.0;
+		let mut integral = Rng::new(3,1);
+		for _ in 0..100000
+		{
+			mean += ggx.sample_visible(&wo,rng.next_f64(),rng.next_f64()).x;
+
+			let z   = integral.next_f64();
+			let r   = (1.0 - z*z).sqrt();
+			let phi = 2.0*PI*integral.next_f64();
+			let h   = Vec3::new(r*phi.cos(),r*phi.sin(),z);
+			expected += h.x*ggx.visible_pdf(&wo,&h)*2.0*PI;
+		}
+		assert!((mean-expected).abs()/100000.0 < 0.01,"{} != {}",mean/100000.0,expected/100000.0);
+	}
+
+	#[test]
+	fn test_masking() {
+		let ggx = Ggx{alpha:0.5};
+		let up  = Vec3::new(0.0,0.0,1.0);
+
+		assert_eq!(1.0,ggx.g1(&up));
+		assert!(ggx.g1(&Vec3::new(0.99f64.sqrt(),0.0,0.1)) < 0.9);
+		assert!(ggx.g(&up,&Vec3::new(0.8,0.0,0.6)) <= ggx.g1(&Vec3::new(0.8,0.0,0.6)));
+		assert!(Ggx::from_roughness(0.01).is_smooth());
+	}
+}
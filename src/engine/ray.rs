@@ -0,0 +1,115 @@
+use crate::math::vector3::Vec3;
+
+// offset applied along the normal to start secondary rays off the surface,
+// relative to the magnitude of the coordinates of the hit point
+pub const EPSILON:f64 = 1e-6;
+
+// half line origin + direction*t restricted to ]t_min,t_max[. Camera rays
+// keep the length of the camera front, secondary rays are unit length.
+#[derive(Debug, Clone)]
+pub struct Ray
+{
+	pub origin:Vec3,
+	pub direction:Vec3,
+	pub t_min:f64,
+	pub t_max:f64
+}
+
+// distance from a surface under which a hit is taken for the surface itself,
+// the rounding error of a point grows with its coordinates
+pub fn offset(point:&Vec3) -> f64
+{
+	EPSILON*(1.0 + point.x.abs().max(point.y.abs()).max(point.z.abs()))
+}
+
+impl Ray {
+	// everything in front of the origin
+	pub fn new(origin:&Vec3,direction:&Vec3) -> Ray
+	{
+		Ray::segment(origin,direction,0.0,f64::INFINITY)
+	}
+
+	pub fn segment(origin:&Vec3,direction:&Vec3,t_min:f64,t_max:f64) -> Ray
+	{
+		Ray{origin:origin.clone(),direction:direction.clone(),t_min,t_max}
+	}
+
+	// secondary ray leaving the surface of normal at point, started off the
+	// surface on the side direction goes to so that it does not hit it again
+	pub fn spawn(point:&Vec3,normal:&Vec3,direction:&Vec3) -> Ray
+	{
+		let side = if direction.dot(normal) >= 0.0 { offset(point) } else { -offset(point) };
+		Ray::new(&point.add(&normal.mul(side)),direction)
+	}
+
+	// ray from a surface to a point at distance along the unit direction,
+	// stopping short of it so that the target itself is not hit. It aims at
+	// the target from the offset origin, which would otherwise pass it by
+	// and meet its surface early when grazing it.
+	pub fn shadow(point:&Vec3,normal:&Vec3,direction:&Vec3,distance:f64) -> Ray
+	{
+		let mut ray = Ray::spawn(point,normal,direction);
+		if distance.is_infinite() { return ray; }
+		let target  = point.add(&direction.mul(distance));
+		let to      = target.sub(&ray.origin);
+		let length  = to.norm();
+		ray.direction = to.div(length);
+		ray.t_max     = length - 2.0*offset(&target);
+		ray
+	}
+
+	pub fn at(&self,t:f64) -> Vec3
+	{
+		self.origin.add(&self.direction.mul(t))
+	}
+
+	pub fn contains(&self,t:f64) -> bool
+	{
+		t > self.t_min && t < self.t_max
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_spawn_side() {
+		let point  = Vec3::new(0.0,2.0,0.0);
+		let normal = Vec3::new(0.0,1.0,0.0);
+
+		let above = Ray::spawn(&point,&normal,&Vec3::new(1.0,1.0,0.0).normalize());
+		let below = Ray::spawn(&point,&normal,&Vec3::new(1.0,-1.0,0.0).normalize());
+		assert!(above.origin.y > 2.0);
+		assert!(below.origin.y < 2.0);
+		assert_eq!(f64::INFINITY,above.t_max);
+	}
+
+	#[test]
+	fn test_shadow_reaches_target() {
+		// a grazing target on a plane at y=2 : the ray ends just before it
+		let point  = Vec3::new(0.0,0.0,0.0);
+		let target = Vec3::new(10.0,2.0,0.0);
+		let to     = target.sub(&point);
+		let ray    = Ray::shadow(&point,&Vec3::new(0.0,1.0,0.0),&to.normalize(),to.norm());
+		assert!(ray.at(ray.t_max).y < 2.0);
+		assert!(ray.at(ray.t_max).sub(&target).norm() < 1e-4);
+		assert_eq!(f64::INFINITY,Ray::shadow(&point,&Vec3::new(0.0,1.0,0.0),&to.normalize(),f64::INFINITY).t_max);
+	}
+
+	#[test]
+	fn test_offset_grows_with_coordinates() {
+		assert_eq!(EPSILON,offset(&Vec3::null()));
+		assert!(offset(&Vec3::new(0.0,-1e6,0.0)) > 1e5*EPSILON);
+	}
+
+	#[test]
+	fn test_interval() {
+		let ray = Ray::segment(&Vec3::null(),&Vec3::new(0.0,0.0,2.0),1.0,3.0);
+
+		assert!(!ray.contains(1.0));
+		assert!(ray.contains(2.0));
+		assert!(!ray.contains(3.0));
+		assert_eq!(4.0,ray.at(2.0).z);
+	}
+}
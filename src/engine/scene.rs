@@ -0,0 +1,441 @@
+use crate::math::vector3::Vec3;
+use crate::math::aabb::Aabb;
+use crate::engine::bvh::Bvh;
+use crate::engine::shape::Shape;
+use crate::engine::material::Material;
+use crate::engine::light::Light;
+use crate::engine::environment::Environment;
+use crate::engine::ray::Ray;
+use crate::engine::triangle;
+use crate::engine::triangle::TriangleTest;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Primitive
+{
+	Triangle(usize),
+	Object(usize)
+}
+
+// a ray meeting a primitive at the ray parameter t, uv being the barycentric
+// coordinates of the point on a triangle as in triangle::Hit, (0,0) on the
+// analytic objects
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Hit
+{
+	pub primitive:Primitive,
+	pub t:f64,
+	pub uv:(f64,f64)
+}
+
+// everything a ray can hit, with the acceleration structures built once.
+// Made by a SceneBuilder, which keeps the per primitive lists in step.
+pub struct Scene
+{
+	mesh:Vec<[Vec3;3]>,
+	// shading normals of the corners of every triangle, flat when None
+	normals:Vec<Option<[Vec3;3]>>,
+	// texture coordinates of the corners of every triangle
+	uvs:Vec<Option<[(f64,f64);3]>>,
+	objects:Vec<Box<dyn Shape>>,
+	// index in materials of every triangle and every object
+	face_materials:Vec<usize>,
+	object_materials:Vec<usize>,
+	materials:Vec<Material>,
+	// id written in the object pass for every triangle and every object
+	face_objects:Vec<usize>,
+	object_ids:Vec<usize>,
+	lights:Vec<Light>,
+	triangle_test:TriangleTest,
+	mesh_bvh:Bvh,
+	object_bvh:Bvh,
+	// bvh leaves reference this list, which maps to indices of objects
+	bounded:Vec<usize>,
+	unbounded:Vec<usize>
+}
+
+// the lists a scene is made of, checked and indexed by build
+pub struct SceneBuilder
+{
+	pub mesh:Vec<[Vec3;3]>,
+	pub normals:Vec<Option<[Vec3;3]>>,
+	pub uvs:Vec<Option<[(f64,f64);3]>>,
+	pub objects:Vec<Box<dyn Shape>>,
+	pub face_materials:Vec<usize>,
+	pub object_materials:Vec<usize>,
+	pub materials:Vec<Material>,
+	pub face_objects:Vec<usize>,
+	pub object_ids:Vec<usize>,
+	// lights other than the area ones, which build finds from the emissive
+	// materials
+	pub lights:Vec<Light>,
+	// watertight unless speed matters more than holes along shared edges
+	pub triangle_test:TriangleTest
+}
+
+// what keeps a builder from making a scene
+#[derive(Debug, Clone, PartialEq)]
+pub enum SceneError
+{
+	// a per primitive list that does not have one entry per primitive
+	Length{list:&'static str,length:usize,expected:usize},
+	// a primitive using a material that does not exist
+	Material{primitive:Primitive,material:usize}
+}
+
+impl fmt::Display for SceneError
+{
+	fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result
+	{
+		match self {
+			SceneError::Length{list,length,expected} => write!(f,"{} has {} entries for {} primitives",list,length,expected),
+			SceneError::Material{primitive,material} => write!(f,"{:?} uses material {} which does not exist",primitive,material)
+		}
+	}
+}
+
+impl Error for SceneError {}
+
+impl SceneBuilder {
+	// every primitive flat, white and part of the same object, without lights,
+	// triangles being intersected with the watertight test
+	pub fn new(mesh:Vec<[Vec3;3]>,objects:Vec<Box<dyn Shape>>) -> SceneBuilder
+	{
+		SceneBuilder{
+			normals:vec![None;mesh.len()],
+			uvs:vec![None;mesh.len()],
+			face_materials:vec![0;mesh.len()],
+			object_materials:vec![0;objects.len()],
+			materials:vec![Material::default()],
+			// the whole mesh is object 0, analytic objects follow
+			face_objects:vec![0;mesh.len()],
+			object_ids:(1..=objects.len()).collect(),
+			lights:Vec::new(),
+			triangle_test:TriangleTest::default(),
+			mesh,
+			objects
+		}
+	}
+
+	// checks the lists, builds the bvhs and adds an area light for every
+	// emissive material
+	pub fn build(self) -> Result<Scene,SceneError>
+	{
+		let faces   = self.mesh.len();
+		let objects = self.objects.len();
+		for (list,length,expected) in [
+			("normals",self.normals.len(),faces),
+			("uvs",self.uvs.len(),faces),
+			("face_materials",self.face_materials.len(),faces),
+			("face_objects",self.face_objects.len(),faces),
+			("object_materials",self.object_materials.len(),objects),
+			("object_ids",self.object_ids.len(),objects)
+		]
+		{
+			if length != expected { return Err(SceneError::Length{list,length,expected}); }
+		}
+		let primitives = self.face_materials.iter().enumerate().map(|(i,m)| (Primitive::Triangle(i),*m))
+			.chain(self.object_materials.iter().enumerate().map(|(i,m)| (Primitive::Object(i),*m)));
+		for (primitive,material) in primitives
+		{
+			if material >= self.materials.len() { return Err(SceneError::Material{primitive,material}); }
+		}
+
+		let mut boxes     = Vec::<Aabb>::new();
+		let mut bounded   = Vec::<usize>::new();
+		let mut unbounded = Vec::<usize>::new();
+
+		for (index,object) in self.objects.iter().enumerate()
+		{
+			match object.bounds() {
+				Some(b) => { boxes.push(b); bounded.push(index); },
+				None    => unbounded.push(index)
+			}
+		}
+
+		let mut scene = Scene{
+			mesh_bvh:Bvh::from_mesh(&self.mesh),
+			object_bvh:Bvh::new(&boxes),
+			mesh:self.mesh,
+			normals:self.normals,
+			uvs:self.uvs,
+			objects:self.objects,
+			face_materials:self.face_materials,
+			object_materials:self.object_materials,
+			materials:self.materials,
+			face_objects:self.face_objects,
+			object_ids:self.object_ids,
+			lights:self.lights,
+			triangle_test:self.triangle_test,
+			bounded,
+			unbounded
+		};
+		scene.add_area_lights();
+		Ok(scene)
+	}
+}
+
+impl Scene {
+	pub fn mesh(&self) -> &[[Vec3;3]]
+	{
+		&self.mesh
+	}
+
+	pub fn objects(&self) -> &[Box<dyn Shape>]
+	{
+		&self.objects
+	}
+
+	pub fn lights(&self) -> &[Light]
+	{
+		&self.lights
+	}
+
+	// shading normals of the corners of a triangle, None when it is flat
+	pub fn normals(&self,triangle:usize) -> Option<&[Vec3;3]>
+	{
+		self.normals[triangle].as_ref()
+	}
+
+	// texture coordinates of the corners of a triangle
+	pub fn uvs(&self,triangle:usize) -> Option<&[(f64,f64);3]>
+	{
+		self.uvs[triangle].as_ref()
+	}
+
+	pub fn material(&self,primitive:Primitive) -> &Material
+	{
+		&self.materials[self.material_index(primitive)]
+	}
+
+	pub fn material_index(&self,primitive:Primitive) -> usize
+	{
+		match primitive {
+			Primitive::Triangle(i) => self.face_materials[i],
+			Primitive::Object(i)   => self.object_materials[i]
+		}
+	}
+
+	// id written in the primitive pass, the objects following the triangles
+	pub fn primitive_id(&self,primitive:Primitive) -> usize
+	{
+		match primitive {
+			Primitive::Triangle(i) => i,
+			Primitive::Object(i)   => self.mesh.len() + i
+		}
+	}
+
+	pub fn object_id(&self,primitive:Primitive) -> usize
+	{
+		match primitive {
+			Primitive::Triangle(i) => self.face_objects[i],
+			Primitive::Object(i)   => self.object_ids[i]
+		}
+	}
+
+	// area of a primitive, zero for the objects that can not be sampled
+	pub fn area(&self,primitive:Primitive) -> f64
+	{
+		match primitive {
+			Primitive::Triangle(i) => {
+				let t = &self.mesh[i];
+				0.5*t[1].sub(&t[0]).cross(&t[2].sub(&t[0])).norm()
+			},
+			Primitive::Object(i)   => self.objects[i].area()
+		}
+	}
+
+	// point uniformly distributed over a primitive and its outward normal
+	pub fn sample(&self,primitive:Primitive,u1:f64,u2:f64) -> Option<(Vec3,Vec3)>
+	{
+		match primitive {
+			Primitive::Triangle(i) => {
+				let t = &self.mesh[i];
+				let s = u1.sqrt();
+				let point = t[0].mul(1.0 - s).add(&t[1].mul(s*(1.0 - u2))).add(&t[2].mul(s*u2));
+				Some((point,t[1].sub(&t[0]).cross(&t[2].sub(&t[0])).normalize()))
+			},
+			Primitive::Object(i)   => self.objects[i].sample(u1,u2)
+		}
+	}
+
+	// point of the part of a primitive seen from origin and its normal, none
+	// for the primitives sampled by area
+	pub fn sample_visible(&self,primitive:Primitive,origin:&Vec3,u1:f64,u2:f64) -> Option<(Vec3,Vec3)>
+	{
+		match primitive {
+			Primitive::Triangle(_) => None,
+			Primitive::Object(i)   => self.objects[i].sample_visible(origin,u1,u2)
+		}
+	}
+
+	// solid angle density of the directions of sample_visible
+	pub fn visible_pdf(&self,primitive:Primitive,origin:&Vec3) -> Option<f64>
+	{
+		match primitive {
+			Primitive::Triangle(_) => None,
+			Primitive::Object(i)   => self.objects[i].visible_pdf(origin)
+		}
+	}
+
+	// the environment light, if any
+	pub fn environment(&self) -> Option<&Environment>
+	{
+		self.lights.iter().find_map(|light| match light {
+			Light::Environment(environment) => Some(environment),
+			_                               => None
+		})
+	}
+
+	// adds an area light for every emissive material, sampling the surfaces
+	// that use it
+	fn add_area_lights(&mut self)
+	{
+		let primitives = (0..self.mesh.len()).map(Primitive::Triangle).chain((0..self.objects.len()).map(Primitive::Object));
+		let mut lights = BTreeMap::<usize,Light>::new();
+		for primitive in primitives
+		{
+			let material = self.material_index(primitive);
+			let area = self.area(primitive);
+			if self.materials[material].emission.is_black() || area <= 0.0 { continue; }
+
+			let light = lights.entry(material).or_insert_with(|| Light::Area{material,primitives:Vec::new(),cdf:Vec::new(),area:0.0});
+			if let Light::Area{primitives,cdf,area:total,..} = light
+			{
+				*total += area;
+				primitives.push(primitive);
+				cdf.push(*total);
+			}
+		}
+		self.lights.extend(lights.into_values());
+	}
+
+	// closest triangle inside the interval of the ray
+	pub fn closest_triangle(&self,ray:&Ray) -> Option<(usize,triangle::Hit)>
+	{
+		self.mesh_bvh.closest(&ray.origin,&ray.direction,ray.t_max,|index| {
+			self.triangle_test.intersect(&self.mesh[index],&ray.origin,&ray.direction)
+				.filter(|hit| ray.contains(hit.t))
+		})
+	}
+
+	// closest analytic object inside the interval of the ray
+	pub fn closest_object(&self,ray:&Ray) -> Option<(usize,f64)>
+	{
+		// shapes give the closest hit in front of their origin, starting the
+		// ray at t_min keeps the hits behind the near end out
+		let start     = ray.at(ray.t_min);
+		let intersect = |index:usize| {
+			self.objects[index].intersect(&start,&ray.direction)
+				.map(|t| t + ray.t_min)
+				.filter(|t| ray.contains(*t))
+		};
+
+		let mut best = self.object_bvh
+			.closest(&ray.origin,&ray.direction,ray.t_max,|i| intersect(self.bounded[i]))
+			.map(|(i,t)| (self.bounded[i],t));
+
+		for index in &self.unbounded
+		{
+			let min = match best { Some((_,t)) => t, None => ray.t_max };
+			if let Some(t) = intersect(*index) {
+				if t < min { best = Some((*index,t)); }
+			}
+		}
+		best
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::engine::shape::{Sphere,Plane,Rectangle};
+	use crate::engine::color::Color;
+
+	#[test]
+	fn test_closest_object() {
+		let objects:Vec<Box<dyn Shape>> = vec![
+			Box::new(Plane{point:Vec3::new(0.0,0.0,10.0),normal:Vec3::new(0.0,0.0,-1.0)}),
+			Box::new(Sphere{center:Vec3::new(0.0,0.0,5.0),radius:1.0}),
+			Box::new(Sphere{center:Vec3::new(0.0,3.0,5.0),radius:1.0}),
+		];
+		let scene = SceneBuilder::new(Vec::new(),objects).build().unwrap();
+
+		let z = Vec3::new(0.0,0.0,1.0);
+		assert_eq!(Some((1,4.0)),scene.closest_object(&Ray::segment(&Vec3::null(),&z,0.0,100.0)));
+		assert_eq!(Some((2,4.0)),scene.closest_object(&Ray::segment(&Vec3::new(0.0,3.0,0.0),&z,0.0,100.0)));
+		assert_eq!(Some((0,10.0)),scene.closest_object(&Ray::segment(&Vec3::new(0.0,-3.0,0.0),&z,0.0,100.0)));
+		assert_eq!(None,scene.closest_object(&Ray::segment(&Vec3::new(0.0,-3.0,0.0),&z,0.0,5.0)));
+		// the near end of the ray skips the front of the sphere
+		assert_eq!(Some((1,6.0)),scene.closest_object(&Ray::segment(&Vec3::null(),&z,5.0,100.0)));
+	}
+
+	#[test]
+	fn test_triangle_test() {
+		let mesh = vec![[Vec3::new(-1.0,-1.0,2.0),Vec3::new(1.0,-1.0,2.0),Vec3::new(0.0,1.0,2.0)]];
+		let ray  = Ray::new(&Vec3::new(0.25,0.0,0.0),&Vec3::new(0.0,0.0,1.0));
+
+		let mut hits = Vec::new();
+		for test in [TriangleTest::Watertight,TriangleTest::MollerTrumbore]
+		{
+			let mut builder = SceneBuilder::new(mesh.clone(),Vec::new());
+			builder.triangle_test = test;
+			let (index,hit) = builder.build().unwrap().closest_triangle(&ray).unwrap();
+			assert_eq!(0,index);
+			assert!((hit.t-2.0).abs() < 1e-12);
+			hits.push(hit);
+		}
+		assert!((hits[0].u-hits[1].u).abs() < 1e-12 && (hits[0].v-hits[1].v).abs() < 1e-12);
+	}
+
+	#[test]
+	fn test_area_lights() {
+		let objects:Vec<Box<dyn Shape>> = vec![
+			Box::new(Plane{point:Vec3::null(),normal:Vec3::new(0.0,1.0,0.0)}),
+			Box::new(Rectangle{corner:Vec3::new(0.0,2.0,0.0),edge1:Vec3::new(1.0,0.0,0.0),edge2:Vec3::new(0.0,0.0,2.0)}),
+			Box::new(Plane{point:Vec3::new(0.0,5.0,0.0),normal:Vec3::new(0.0,1.0,0.0)})
+		];
+		let mut builder = SceneBuilder::new(vec![[Vec3::null(),Vec3::new(2.0,0.0,0.0),Vec3::new(0.0,2.0,0.0)]],objects);
+		builder.materials.push(Material{emission:Color::white(),..Material::default()});
+		builder.face_materials   = vec![1];
+		builder.object_materials = vec![0,1,1];
+		let scene = builder.build().unwrap();
+
+		// the triangle and the rectangle, the infinite plane can not be sampled
+		assert_eq!(1,scene.lights.len());
+		match &scene.lights[0] {
+			Light::Area{material,primitives,cdf,area} => {
+				assert_eq!(1,*material);
+				assert_eq!(&vec![Primitive::Triangle(0),Primitive::Object(1)],primitives);
+				assert_eq!(&vec![2.0,4.0],cdf);
+				assert_eq!(4.0,*area);
+			},
+			_ => panic!("area light expected")
+		}
+
+		let (point,normal) = scene.sample(Primitive::Triangle(0),0.25,0.5).unwrap();
+		assert_eq!(0.0,point.z);
+		assert!(point.x + point.y <= 2.0);
+		assert_eq!(1.0,normal.z);
+	}
+
+	#[test]
+	fn test_build_errors() {
+		let builder = || SceneBuilder::new(vec![[Vec3::null(),Vec3::new(1.0,0.0,0.0),Vec3::new(0.0,1.0,0.0)]],vec![Box::new(Sphere{center:Vec3::null(),radius:1.0}) as Box<dyn Shape>]);
+
+		let mut long = builder();
+		long.object_materials = vec![0,1];
+		assert_eq!(Some(SceneError::Length{list:"object_materials",length:2,expected:1}),long.build().err());
+
+		let mut short = builder();
+		short.normals.clear();
+		assert_eq!(Some(SceneError::Length{list:"normals",length:0,expected:1}),short.build().err());
+
+		let mut undefined = builder();
+		undefined.face_materials = vec![1];
+		assert_eq!(Some(SceneError::Material{primitive:Primitive::Triangle(0),material:1}),undefined.build().err());
+	}
+}
@@ -0,0 +1,822 @@
+use crate::math::vector3::Vec3;
+use crate::math::aabb::Aabb;
+
+// Analytic surfaces living alongside the triangle mesh. Distances are given
+// as ray parameters : the hit point is origin + ray*t, whatever the length of ray.
+pub trait Shape: Send + Sync
+{
+	// closest intersection strictly in front of the origin
+	fn intersect(&self, origin:&Vec3, ray:&Vec3) -> Option<f64>;
+	// unit normal pointing out of the surface at a point lying on it
+	fn normal(&self, point:&Vec3) -> Vec3;
+	// None for unbounded surfaces that cannot be stored in a bvh
+	fn bounds(&self) -> Option<Aabb>;
+	// texture coordinates of a point lying on the surface
+	fn uv(&self, _point:&Vec3) -> (f64,f64)
+	{
+		(0.0,0.0)
+	}
+	// how a point of the surface moves with its texture coordinates, none
+	// where they are not defined or degenerate
+	fn uv_derivatives(&self, _point:&Vec3) -> Option<(Vec3,Vec3)>
+	{
+		None
+	}
+	// coordinates of a point in the frame of the object, for the textures
+	// that move with it
+	fn local(&self, point:&Vec3) -> Vec3
+	{
+		point.clone()
+	}
+	// area of the surface, zero for the shapes that can not be sampled
+	fn area(&self) -> f64
+	{
+		0.0
+	}
+	// point uniformly distributed over the surface and its normal, u1 and
+	// u2 being uniform in [0,1)
+	fn sample(&self, _u1:f64, _u2:f64) -> Option<(Vec3,Vec3)>
+	{
+		None
+	}
+	// point of the part of the surface seen from origin and its normal, none
+	// for the shapes sampled by area only
+	fn sample_visible(&self, _origin:&Vec3, _u1:f64, _u2:f64) -> Option<(Vec3,Vec3)>
+	{
+		None
+	}
+	// density of the directions of sample_visible from origin
+	fn visible_pdf(&self, _origin:&Vec3) -> Option<f64>
+	{
+		None
+	}
+}
+
+// coordinates of point in a frame centered on origin with the unit axis as z
+fn axis_frame(origin:&Vec3, axis:&Vec3, point:&Vec3) -> Vec3
+{
+	let (tangent,bitangent) = axis.basis();
+	let p = point.sub(origin);
+	Vec3::new(p.dot(&tangent),p.dot(&bitangent),p.dot(axis))
+}
+
+// coordinates of point in a plane of the given unit normal, in world units
+fn planar_uv(origin:&Vec3, normal:&Vec3, point:&Vec3) -> (f64,f64)
+{
+	let (tangent,bitangent) = normal.basis();
+	let p = point.sub(origin);
+	(p.dot(&tangent),p.dot(&bitangent))
+}
+
+// smallest positive value amongst candidates
+fn nearest(candidates:&[Option<f64>]) -> Option<f64>
+{
+	let mut best:Option<f64> = None;
+	for t in candidates.iter().flatten()
+	{
+		if *t <= 0.0 { continue; }
+		best = match best {
+			Some(b) if b <= *t => Some(b),
+			_                  => Some(*t)
+		};
+	}
+	best
+}
+
+// roots of a*t^2 + b*t + c in increasing order
+fn solve_quadratic(a:f64, b:f64, c:f64) -> Option<(f64,f64)>
+{
+	if a == 0.0 { return None; }
+	let delta = b*b - 4.0*a*c;
+	if delta < 0.0 { return None; }
+
+	// avoid the cancellation of -b + sqrt(delta) when b is large
+	let q  = if b < 0.0 { -0.5*(b - delta.sqrt()) } else { -0.5*(b + delta.sqrt()) };
+	let t0 = q/a;
+	let t1 = if q != 0.0 { c/q } else { t0 };
+	if t0 < t1 { Some((t0,t1)) } else { Some((t1,t0)) }
+}
+
+// box enclosing a disk of the given center, unit normal and radius
+fn disk_bounds(center:&Vec3, normal:&Vec3, radius:f64) -> Aabb
+{
+	let ex = radius*(1.0 - normal.x*normal.x).max(0.0).sqrt();
+	let ey = radius*(1.0 - normal.y*normal.y).max(0.0).sqrt();
+	let ez = radius*(1.0 - normal.z*normal.z).max(0.0).sqrt();
+	let e  = Vec3::new(ex,ey,ez);
+	Aabb::new(&center.sub(&e),&center.add(&e))
+}
+
+// ray parameter of the hit with the plane going through point, if any
+fn plane_hit(point:&Vec3, normal:&Vec3, origin:&Vec3, ray:&Vec3) -> Option<f64>
+{
+	let denom = ray.dot(normal);
+	if denom == 0.0 { return None; }
+	let t = point.sub(origin).dot(normal)/denom;
+	if t > 0.0 { Some(t) } else { None }
+}
+
+fn disk_hit(center:&Vec3, normal:&Vec3, radius:f64, origin:&Vec3, ray:&Vec3) -> Option<f64>
+{
+	let t = plane_hit(center,normal,origin,ray)?;
+	let p = origin.add(&ray.mul(t));
+	if p.sub(center).dot(&p.sub(center)) <= radius*radius { Some(t) } else { None }
+}
+
+pub struct Sphere
+{
+	pub center:Vec3,
+	pub radius:f64
+}
+
+impl Shape for Sphere
+{
+	fn intersect(&self, origin:&Vec3, ray:&Vec3) -> Option<f64>
+	{
+		let oc = origin.sub(&self.center);
+		let (t0,t1) = solve_quadratic(
+			ray.dot(ray),
+			2.0*oc.dot(ray),
+			oc.dot(&oc) - self.radius*self.radius
+		)?;
+		nearest(&[Some(t0),Some(t1)])
+	}
+
+	fn normal(&self, point:&Vec3) -> Vec3
+	{
+		point.sub(&self.center).normalize()
+	}
+
+	fn bounds(&self) -> Option<Aabb>
+	{
+		let r = Vec3::new(self.radius,self.radius,self.radius);
+		Some(Aabb::new(&self.center.sub(&r),&self.center.add(&r)))
+	}
+
+	// longitude and latitude, the poles on the y axis
+	fn uv(&self, point:&Vec3) -> (f64,f64)
+	{
+		let d = self.normal(point);
+		(0.5 + d.z.atan2(d.x)/(2.0*std::f64::consts::PI),0.5 + d.y.clamp(-1.0,1.0).asin()/std::f64::consts::PI)
+	}
+
+	fn uv_derivatives(&self, point:&Vec3) -> Option<(Vec3,Vec3)>
+	{
+		let p   = point.sub(&self.center);
+		let rho = (p.x*p.x + p.z*p.z).sqrt();
+		if rho == 0.0 { return None; }
+		let pi  = std::f64::consts::PI;
+		Some((
+			Vec3::new(-p.z,0.0,p.x).mul(2.0*pi),
+			Vec3::new(-p.y*p.x/rho,rho,-p.y*p.z/rho).mul(pi)
+		))
+	}
+
+	fn local(&self, point:&Vec3) -> Vec3
+	{
+		point.sub(&self.center)
+	}
+
+	fn area(&self) -> f64
+	{
+		4.0*std::f64::consts::PI*self.radius*self.radius
+	}
+
+	fn sample(&self, u1:f64, u2:f64) -> Option<(Vec3,Vec3)>
+	{
+		let z   = 1.0 - 2.0*u1;
+		let r   = (1.0 - z*z).max(0.0).sqrt();
+		let phi = 2.0*std::f64::consts::PI*u2;
+		let n   = Vec3::new(r*phi.cos(),r*phi.sin(),z);
+		Some((self.center.add(&n.mul(self.radius)),n))
+	}
+
+	// uniform over the cone of directions the sphere fills seen from
+	// outside, most of its area being hidden or far away
+	fn sample_visible(&self, origin:&Vec3, u1:f64, u2:f64) -> Option<(Vec3,Vec3)>
+	{
+		let (cos_max,_) = self.cone(origin)?;
+		let to_center = self.center.sub(origin);
+		let distance  = to_center.norm();
+		let axis      = to_center.div(distance);
+		let (tangent,bitangent) = axis.basis();
+
+		let cos = 1.0 - u1*(1.0 - cos_max);
+		let sin = (1.0 - cos*cos).max(0.0).sqrt();
+		let phi = 2.0*std::f64::consts::PI*u2;
+		let direction = tangent.mul(sin*phi.cos()).add(&bitangent.mul(sin*phi.sin())).add(&axis.mul(cos));
+		// closest of the two hits along direction
+		let t     = distance*cos - (self.radius*self.radius - distance*distance*sin*sin).max(0.0).sqrt();
+		let point = origin.add(&direction.mul(t));
+		Some((point.clone(),point.sub(&self.center).div(self.radius)))
+	}
+
+	fn visible_pdf(&self, origin:&Vec3) -> Option<f64>
+	{
+		self.cone(origin).map(|(_,solid_angle)| 1.0/solid_angle)
+	}
+}
+
+impl Sphere {
+	// cosine of the half angle of the cone the sphere fills seen from
+	// origin and its solid angle, none from inside
+	fn cone(&self, origin:&Vec3) -> Option<(f64,f64)>
+	{
+		let distance2 = self.center.sub(origin).dot(&self.center.sub(origin));
+		let sin2      = self.radius*self.radius/distance2;
+		if sin2 >= 1.0 { return None; }
+		let cos_max   = (1.0 - sin2).sqrt();
+		// 1 - cos_max without cancellation for small spheres
+		Some((cos_max,2.0*std::f64::consts::PI*sin2/(1.0 + cos_max)))
+	}
+}
+
+pub struct Plane
+{
+	pub point:Vec3,
+	pub normal:Vec3
+}
+
+impl Shape for Plane
+{
+	fn intersect(&self, origin:&Vec3, ray:&Vec3) -> Option<f64>
+	{
+		plane_hit(&self.point,&self.normal,origin,ray)
+	}
+
+	fn normal(&self, _point:&Vec3) -> Vec3
+	{
+		self.normal.normalize()
+	}
+
+	fn bounds(&self) -> Option<Aabb>
+	{
+		None
+	}
+
+	// one texture repeat per world unit
+	fn uv(&self, point:&Vec3) -> (f64,f64)
+	{
+		planar_uv(&self.point,&self.normal.normalize(),point)
+	}
+
+	fn uv_derivatives(&self, _point:&Vec3) -> Option<(Vec3,Vec3)>
+	{
+		Some(self.normal.normalize().basis())
+	}
+
+	fn local(&self, point:&Vec3) -> Vec3
+	{
+		axis_frame(&self.point,&self.normal.normalize(),point)
+	}
+}
+
+pub struct Disk
+{
+	pub center:Vec3,
+	pub normal:Vec3,
+	pub radius:f64
+}
+
+impl Shape for Disk
+{
+	fn intersect(&self, origin:&Vec3, ray:&Vec3) -> Option<f64>
+	{
+		disk_hit(&self.center,&self.normal,self.radius,origin,ray)
+	}
+
+	fn normal(&self, _point:&Vec3) -> Vec3
+	{
+		self.normal.normalize()
+	}
+
+	fn bounds(&self) -> Option<Aabb>
+	{
+		Some(disk_bounds(&self.center,&self.normal.normalize(),self.radius))
+	}
+
+	// the texture covers the square around the disk
+	fn uv(&self, point:&Vec3) -> (f64,f64)
+	{
+		let (u,v) = planar_uv(&self.center,&self.normal.normalize(),point);
+		(0.5 + u/(2.0*self.radius),0.5 + v/(2.0*self.radius))
+	}
+
+	fn uv_derivatives(&self, _point:&Vec3) -> Option<(Vec3,Vec3)>
+	{
+		let (tangent,bitangent) = self.normal.normalize().basis();
+		Some((tangent.mul(2.0*self.radius),bitangent.mul(2.0*self.radius)))
+	}
+
+	fn local(&self, point:&Vec3) -> Vec3
+	{
+		axis_frame(&self.center,&self.normal.normalize(),point)
+	}
+
+	fn area(&self) -> f64
+	{
+		std::f64::consts::PI*self.radius*self.radius
+	}
+
+	fn sample(&self, u1:f64, u2:f64) -> Option<(Vec3,Vec3)>
+	{
+		let normal = self.normal.normalize();
+		let (tangent,bitangent) = normal.basis();
+		let r   = self.radius*u1.sqrt();
+		let phi = 2.0*std::f64::consts::PI*u2;
+		Some((self.center.add(&tangent.mul(r*phi.cos())).add(&bitangent.mul(r*phi.sin())),normal))
+	}
+}
+
+// parallelogram spanned by two edges from a corner, a rectangle when they
+// are perpendicular
+pub struct Rectangle
+{
+	pub corner:Vec3,
+	pub edge1:Vec3,
+	pub edge2:Vec3
+}
+
+impl Rectangle
+{
+	// position of a point of the plane along each edge, in [0,1] inside
+	fn coordinates(&self, point:&Vec3) -> (f64,f64)
+	{
+		let n  = self.edge1.cross(&self.edge2);
+		let n2 = n.dot(&n);
+		let d  = point.sub(&self.corner);
+		(d.cross(&self.edge2).dot(&n)/n2,self.edge1.cross(&d).dot(&n)/n2)
+	}
+}
+
+impl Shape for Rectangle
+{
+	fn intersect(&self, origin:&Vec3, ray:&Vec3) -> Option<f64>
+	{
+		let t     = plane_hit(&self.corner,&self.edge1.cross(&self.edge2),origin,ray)?;
+		let (a,b) = self.coordinates(&origin.add(&ray.mul(t)));
+		if (0.0..=1.0).contains(&a) && (0.0..=1.0).contains(&b) { Some(t) } else { None }
+	}
+
+	fn normal(&self, _point:&Vec3) -> Vec3
+	{
+		self.edge1.cross(&self.edge2).normalize()
+	}
+
+	fn bounds(&self) -> Option<Aabb>
+	{
+		let far = self.corner.add(&self.edge1).add(&self.edge2);
+		Some(Aabb::new(&self.corner,&self.corner)
+			.grow(&self.corner.add(&self.edge1))
+			.grow(&self.corner.add(&self.edge2))
+			.grow(&far))
+	}
+
+	// the texture is stretched over the edges
+	fn uv(&self, point:&Vec3) -> (f64,f64)
+	{
+		self.coordinates(point)
+	}
+
+	fn uv_derivatives(&self, _point:&Vec3) -> Option<(Vec3,Vec3)>
+	{
+		Some((self.edge1.clone(),self.edge2.clone()))
+	}
+
+	fn local(&self, point:&Vec3) -> Vec3
+	{
+		point.sub(&self.corner)
+	}
+
+	fn area(&self) -> f64
+	{
+		self.edge1.cross(&self.edge2).norm()
+	}
+
+	fn sample(&self, u1:f64, u2:f64) -> Option<(Vec3,Vec3)>
+	{
+		Some((self.corner.add(&self.edge1.mul(u1)).add(&self.edge2.mul(u2)),self.normal(&self.corner)))
+	}
+}
+
+// axis aligned box
+pub struct Cuboid
+{
+	pub min:Vec3,
+	pub max:Vec3
+}
+
+impl Shape for Cuboid
+{
+	fn intersect(&self, origin:&Vec3, ray:&Vec3) -> Option<f64>
+	{
+		let mut t_near = -f64::INFINITY;
+		let mut t_far  =  f64::INFINITY;
+
+		let axes = [
+			(self.min.x,self.max.x,origin.x,ray.x),
+			(self.min.y,self.max.y,origin.y,ray.y),
+			(self.min.z,self.max.z,origin.z,ray.z),
+		];
+		for (min,max,o,d) in axes.iter()
+		{
+			if *d == 0.0
+			{
+				if o < min || o > max { return None; }
+				continue;
+			}
+			let t0 = (min - o)/d;
+			let t1 = (max - o)/d;
+			let (t0,t1) = if t0 > t1 { (t1,t0) } else { (t0,t1) };
+			t_near = t_near.max(t0);
+			t_far  = t_far.min(t1);
+			if t_near > t_far { return None; }
+		}
+		nearest(&[Some(t_near),Some(t_far)])
+	}
+
+	fn normal(&self, point:&Vec3) -> Vec3
+	{
+		// the face the point lies on is the one it is the closest to
+		let faces = [
+			(point.x - self.min.x, Vec3::new(-1.0, 0.0, 0.0)),
+			(self.max.x - point.x, Vec3::new( 1.0, 0.0, 0.0)),
+			(point.y - self.min.y, Vec3::new( 0.0,-1.0, 0.0)),
+			(self.max.y - point.y, Vec3::new( 0.0, 1.0, 0.0)),
+			(point.z - self.min.z, Vec3::new( 0.0, 0.0,-1.0)),
+			(self.max.z - point.z, Vec3::new( 0.0, 0.0, 1.0)),
+		];
+		let mut best = &faces[0];
+		for face in faces.iter()
+		{
+			if face.0.abs() < best.0.abs() { best = face; }
+		}
+		best.1.clone()
+	}
+
+	fn bounds(&self) -> Option<Aabb>
+	{
+		Some(Aabb::new(&self.min,&self.max))
+	}
+
+	fn local(&self, point:&Vec3) -> Vec3
+	{
+		point.sub(&self.min)
+	}
+}
+
+// capped cylinder going from base to top
+pub struct Cylinder
+{
+	pub base:Vec3,
+	pub top:Vec3,
+	pub radius:f64
+}
+
+impl Cylinder
+{
+	fn axis(&self) -> (Vec3,f64)
+	{
+		let axis = self.top.sub(&self.base);
+		let height = axis.norm();
+		(axis.div(height),height)
+	}
+}
+
+impl Shape for Cylinder
+{
+	fn intersect(&self, origin:&Vec3, ray:&Vec3) -> Option<f64>
+	{
+		let (axis,height) = self.axis();
+		let co = origin.sub(&self.base);
+
+		// remove the components along the axis to work in the section plane
+		let d  = ray.sub(&axis.mul(ray.dot(&axis)));
+		let c  = co.sub(&axis.mul(co.dot(&axis)));
+
+		let side = |t:f64| -> Option<f64> {
+			let h = origin.add(&ray.mul(t)).sub(&self.base).dot(&axis);
+			if h >= 0.0 && h <= height { Some(t) } else { None }
+		};
+		let (t0,t1) = match solve_quadratic(d.dot(&d),2.0*d.dot(&c),c.dot(&c) - self.radius*self.radius) {
+			Some((t0,t1)) => (side(t0),side(t1)),
+			None          => (None,None)
+		};
+
+		nearest(&[
+			t0,
+			t1,
+			disk_hit(&self.base,&axis,self.radius,origin,ray),
+			disk_hit(&self.top ,&axis,self.radius,origin,ray)
+		])
+	}
+
+	fn normal(&self, point:&Vec3) -> Vec3
+	{
+		let (axis,height) = self.axis();
+		let p = point.sub(&self.base);
+		let h = p.dot(&axis);
+		let eps = 1e-6*height.max(self.radius);
+
+		if h <= eps          { return axis.mul(-1.0); }
+		if h >= height - eps { return axis; }
+		p.sub(&axis.mul(h)).normalize()
+	}
+
+	fn bounds(&self) -> Option<Aabb>
+	{
+		let (axis,_) = self.axis();
+		Some(
+			disk_bounds(&self.base,&axis,self.radius)
+				.union(&disk_bounds(&self.top,&axis,self.radius))
+		)
+	}
+
+	// angle around the axis and height from the base
+	fn uv(&self, point:&Vec3) -> (f64,f64)
+	{
+		let (axis,height)       = self.axis();
+		let (tangent,bitangent) = axis.basis();
+		let p = point.sub(&self.base);
+		(0.5 + p.dot(&bitangent).atan2(p.dot(&tangent))/(2.0*std::f64::consts::PI),(p.dot(&axis)/height).clamp(0.0,1.0))
+	}
+
+	// around the axis on the side, the caps have no texture coordinates
+	fn uv_derivatives(&self, point:&Vec3) -> Option<(Vec3,Vec3)>
+	{
+		let (axis,height)       = self.axis();
+		let (tangent,bitangent) = axis.basis();
+		let p = point.sub(&self.base);
+		let around = bitangent.mul(p.dot(&tangent)).sub(&tangent.mul(p.dot(&bitangent)));
+		if around.norm() == 0.0 { return None; }
+		Some((around.mul(2.0*std::f64::consts::PI),axis.mul(height)))
+	}
+
+	fn local(&self, point:&Vec3) -> Vec3
+	{
+		axis_frame(&self.base,&self.axis().0,point)
+	}
+}
+
+// capped cone with a disk of the given radius at its base
+pub struct Cone
+{
+	pub base:Vec3,
+	pub apex:Vec3,
+	pub radius:f64
+}
+
+impl Cone
+{
+	// unit axis from the apex to the base, height and squared cosine of the half angle
+	fn axis(&self) -> (Vec3,f64,f64)
+	{
+		let axis   = self.base.sub(&self.apex);
+		let height = axis.norm();
+		let cos2   = height*height/(height*height + self.radius*self.radius);
+		(axis.div(height),height,cos2)
+	}
+}
+
+impl Shape for Cone
+{
+	fn intersect(&self, origin:&Vec3, ray:&Vec3) -> Option<f64>
+	{
+		let (axis,height,cos2) = self.axis();
+		let co = origin.sub(&self.apex);
+		let dv = ray.dot(&axis);
+		let cv = co.dot(&axis);
+
+		// keep the nappe between the apex and the base only
+		let side = |t:f64| -> Option<f64> {
+			let h = origin.add(&ray.mul(t)).sub(&self.apex).dot(&axis);
+			if h >= 0.0 && h <= height { Some(t) } else { None }
+		};
+		let (t0,t1) = match solve_quadratic(
+			dv*dv - cos2*ray.dot(ray),
+			2.0*(dv*cv - cos2*ray.dot(&co)),
+			cv*cv - cos2*co.dot(&co)
+		) {
+			Some((t0,t1)) => (side(t0),side(t1)),
+			None          => (None,None)
+		};
+
+		nearest(&[
+			t0,
+			t1,
+			disk_hit(&self.base,&axis,self.radius,origin,ray)
+		])
+	}
+
+	fn normal(&self, point:&Vec3) -> Vec3
+	{
+		let (axis,height,cos2) = self.axis();
+		let p = point.sub(&self.apex);
+		let h = p.dot(&axis);
+
+		if h >= height*(1.0 - 1e-6) { return axis; }
+		if p.dot(&p) == 0.0         { return axis.mul(-1.0); }
+		p.mul(cos2).sub(&axis.mul(h)).normalize()
+	}
+
+	fn bounds(&self) -> Option<Aabb>
+	{
+		let (axis,_,_) = self.axis();
+		Some(disk_bounds(&self.base,&axis,self.radius).grow(&self.apex))
+	}
+
+	// from the center of the base, z going to the apex
+	fn local(&self, point:&Vec3) -> Vec3
+	{
+		axis_frame(&self.base,&self.axis().0.mul(-1.0),point)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn assert_vec(expected:&Vec3, v:&Vec3)
+	{
+		assert!((expected.x-v.x).abs() < 1e-9, "{:?} != {:?}", expected, v);
+		assert!((expected.y-v.y).abs() < 1e-9, "{:?} != {:?}", expected, v);
+		assert!((expected.z-v.z).abs() < 1e-9, "{:?} != {:?}", expected, v);
+	}
+
+	#[test]
+	fn test_solve_quadratic() {
+		assert_eq!(Some((-3.0,2.0)),solve_quadratic(1.0,1.0,-6.0));
+		assert_eq!(None,solve_quadratic(1.0,0.0,1.0));
+	}
+
+	#[test]
+	fn test_sphere() {
+		let s = Sphere{center:Vec3::new(0.0,0.0,5.0),radius:1.0};
+
+		assert_eq!(Some(2.0),s.intersect(&Vec3::null(),&Vec3::new(0.0,0.0,2.0)));
+		assert_eq!(None,s.intersect(&Vec3::null(),&Vec3::new(0.0,1.0,0.0)));
+		// from inside the far side is hit
+		assert_eq!(Some(1.0),s.intersect(&Vec3::new(0.0,0.0,5.0),&Vec3::new(0.0,0.0,1.0)));
+		assert_vec(&Vec3::new(0.0,0.0,-1.0),&s.normal(&Vec3::new(0.0,0.0,4.0)));
+	}
+
+	#[test]
+	fn test_uv() {
+		let s = Sphere{center:Vec3::new(0.0,0.0,5.0),radius:2.0};
+		assert_eq!((0.5,0.5),s.uv(&Vec3::new(2.0,0.0,5.0)));
+		assert_eq!(1.0,s.uv(&Vec3::new(0.0,2.0,5.0)).1);
+
+		let d = Disk{center:Vec3::new(0.0,0.0,3.0),normal:Vec3::new(0.0,0.0,-1.0),radius:2.0};
+		assert_eq!((0.5,0.5),d.uv(&Vec3::new(0.0,0.0,3.0)));
+
+		// world units along the plane
+		let p = Plane{point:Vec3::null(),normal:Vec3::new(0.0,1.0,0.0)};
+		let (u0,v0) = p.uv(&Vec3::new(0.0,0.0,0.0));
+		let (u1,v1) = p.uv(&Vec3::new(3.0,0.0,4.0));
+		assert!(((u1-u0).powi(2)+(v1-v0).powi(2)-25.0).abs() < 1e-9);
+
+		let c = Cylinder{base:Vec3::null(),top:Vec3::new(0.0,4.0,0.0),radius:1.0};
+		assert_eq!(0.25,c.uv(&Vec3::new(1.0,1.0,0.0)).1);
+	}
+
+	// derivatives against finite differences of the texture coordinates
+	fn check_uv_derivatives(shape:&dyn Shape, point:&Vec3)
+	{
+		let (dpdu,dpdv) = shape.uv_derivatives(point).unwrap();
+		let (u,v)   = shape.uv(point);
+		let h       = 1e-6;
+		let (u1,v1) = shape.uv(&point.add(&dpdu.mul(h)));
+		let (u2,v2) = shape.uv(&point.add(&dpdv.mul(h)));
+		assert!(((u1-u)/h-1.0).abs() < 1e-3 && ((v1-v)/h).abs() < 1e-3,"dpdu of {:?}",point);
+		assert!(((u2-u)/h).abs() < 1e-3 && ((v2-v)/h-1.0).abs() < 1e-3,"dpdv of {:?}",point);
+	}
+
+	#[test]
+	fn test_uv_derivatives() {
+		check_uv_derivatives(&Sphere{center:Vec3::new(0.0,0.0,5.0),radius:2.0},&Vec3::new(1.2,-1.0,5.0+1.56f64.sqrt()));
+		check_uv_derivatives(&Plane{point:Vec3::null(),normal:Vec3::new(0.0,1.0,1.0)},&Vec3::new(3.0,1.0,-1.0));
+		check_uv_derivatives(&Disk{center:Vec3::null(),normal:Vec3::new(0.0,0.0,1.0),radius:3.0},&Vec3::new(1.0,1.0,0.0));
+		check_uv_derivatives(&Cylinder{base:Vec3::null(),top:Vec3::new(0.0,4.0,0.0),radius:1.0},&Vec3::new(0.6,1.0,0.8));
+		assert!(Sphere{center:Vec3::null(),radius:1.0}.uv_derivatives(&Vec3::new(0.0,1.0,0.0)).is_none());
+	}
+
+	#[test]
+	fn test_local() {
+		let s = Sphere{center:Vec3::new(0.0,0.0,5.0),radius:2.0};
+		assert_vec(&Vec3::new(2.0,0.0,0.0),&s.local(&Vec3::new(2.0,0.0,5.0)));
+
+		// z follows the axis, from the base
+		let c = Cylinder{base:Vec3::new(1.0,0.0,0.0),top:Vec3::new(1.0,4.0,0.0),radius:1.0};
+		let p = c.local(&Vec3::new(2.0,3.0,0.0));
+		assert!((p.z-3.0).abs() < 1e-12);
+		assert!((p.x*p.x+p.y*p.y-1.0).abs() < 1e-12);
+
+		let cone = Cone{base:Vec3::null(),apex:Vec3::new(0.0,0.0,-2.0),radius:1.0};
+		assert!((cone.local(&Vec3::new(0.0,0.0,-1.0)).z-1.0).abs() < 1e-12);
+	}
+
+	#[test]
+	fn test_plane() {
+		let p = Plane{point:Vec3::new(0.0,-1.0,0.0),normal:Vec3::new(0.0,2.0,0.0)};
+
+		assert_eq!(Some(1.0),p.intersect(&Vec3::null(),&Vec3::new(0.0,-1.0,1.0)));
+		assert_eq!(None,p.intersect(&Vec3::null(),&Vec3::new(0.0,1.0,1.0)));
+		assert_eq!(None,p.intersect(&Vec3::null(),&Vec3::new(1.0,0.0,0.0)));
+		assert_vec(&Vec3::new(0.0,1.0,0.0),&p.normal(&Vec3::null()));
+		assert!(p.bounds().is_none());
+	}
+
+	#[test]
+	fn test_disk() {
+		let d = Disk{center:Vec3::new(0.0,0.0,3.0),normal:Vec3::new(0.0,0.0,-1.0),radius:1.0};
+
+		assert_eq!(Some(3.0),d.intersect(&Vec3::null(),&Vec3::new(0.0,0.0,1.0)));
+		assert_eq!(None,d.intersect(&Vec3::new(1.5,0.0,0.0),&Vec3::new(0.0,0.0,1.0)));
+
+		let b = d.bounds().unwrap();
+		assert_vec(&Vec3::new(-1.0,-1.0,3.0),&b.min);
+		assert_vec(&Vec3::new( 1.0, 1.0,3.0),&b.max);
+	}
+
+	#[test]
+	fn test_rectangle() {
+		let r = Rectangle{corner:Vec3::new(-1.0,-1.0,3.0),edge1:Vec3::new(2.0,0.0,0.0),edge2:Vec3::new(0.0,4.0,0.0)};
+
+		assert_eq!(Some(3.0),r.intersect(&Vec3::null(),&Vec3::new(0.0,0.0,1.0)));
+		assert_eq!(None,r.intersect(&Vec3::new(1.5,0.0,0.0),&Vec3::new(0.0,0.0,1.0)));
+		assert_vec(&Vec3::new(0.0,0.0,1.0),&r.normal(&Vec3::null()));
+		assert_eq!((0.5,0.25),r.uv(&Vec3::new(0.0,0.0,3.0)));
+		assert_eq!(8.0,r.area());
+		check_uv_derivatives(&r,&Vec3::new(0.3,0.2,3.0));
+
+		let b = r.bounds().unwrap();
+		assert_vec(&Vec3::new(-1.0,-1.0,3.0),&b.min);
+		assert_vec(&Vec3::new( 1.0, 3.0,3.0),&b.max);
+	}
+
+	#[test]
+	fn test_sample() {
+		let shapes:Vec<Box<dyn Shape>> = vec![
+			Box::new(Sphere{center:Vec3::new(1.0,2.0,3.0),radius:2.0}),
+			Box::new(Disk{center:Vec3::new(1.0,2.0,3.0),normal:Vec3::new(1.0,1.0,0.0),radius:2.0}),
+			Box::new(Rectangle{corner:Vec3::new(0.0,1.0,2.0),edge1:Vec3::new(2.0,0.0,0.0),edge2:Vec3::new(0.0,2.0,2.0)})
+		];
+		let mut rng = crate::math::random::Rng::new(1,1);
+		for shape in &shapes
+		{
+			// on the surface, with its normal, centered on the shape
+			let mut mean = Vec3::null();
+			for _ in 0..20000
+			{
+				let (point,normal) = shape.sample(rng.next_f64(),rng.next_f64()).unwrap();
+				assert_vec(&shape.normal(&point),&normal);
+				let back = point.add(&normal.mul(0.01));
+				assert!(shape.intersect(&back,&normal.mul(-1.0)).is_some());
+				mean = mean.add(&point.div(20000.0));
+			}
+			assert!(mean.sub(&Vec3::new(1.0,2.0,3.0)).norm() < 0.05,"{:?}",mean);
+		}
+		assert_eq!(4.0*std::f64::consts::PI,Disk{center:Vec3::null(),normal:Vec3::new(0.0,1.0,0.0),radius:2.0}.area());
+		assert!(Plane{point:Vec3::null(),normal:Vec3::new(0.0,1.0,0.0)}.sample(0.5,0.5).is_none());
+	}
+
+	#[test]
+	fn test_cuboid() {
+		let c = Cuboid{min:Vec3::new(-1.0,-1.0,2.0),max:Vec3::new(1.0,1.0,4.0)};
+
+		assert_eq!(Some(2.0),c.intersect(&Vec3::null(),&Vec3::new(0.0,0.0,1.0)));
+		assert_eq!(None,c.intersect(&Vec3::new(2.0,0.0,0.0),&Vec3::new(0.0,0.0,1.0)));
+		assert_eq!(Some(1.0),c.intersect(&Vec3::new(0.0,0.0,3.0),&Vec3::new(0.0,0.0,1.0)));
+		assert_vec(&Vec3::new(0.0,0.0,-1.0),&c.normal(&Vec3::new(0.2,0.3,2.0)));
+		assert_vec(&Vec3::new(1.0,0.0, 0.0),&c.normal(&Vec3::new(1.0,0.3,3.5)));
+	}
+
+	#[test]
+	fn test_cylinder() {
+		let c = Cylinder{base:Vec3::new(0.0,-1.0,5.0),top:Vec3::new(0.0,1.0,5.0),radius:1.0};
+
+		// side
+		assert_eq!(Some(4.0),c.intersect(&Vec3::null(),&Vec3::new(0.0,0.0,1.0)));
+		assert_vec(&Vec3::new(0.0,0.0,-1.0),&c.normal(&Vec3::new(0.0,0.0,4.0)));
+		// above the top cap
+		assert_eq!(None,c.intersect(&Vec3::new(0.0,2.0,0.0),&Vec3::new(0.0,0.0,1.0)));
+		// cap seen from above
+		assert_eq!(Some(1.0),c.intersect(&Vec3::new(0.0,2.0,5.0),&Vec3::new(0.0,-1.0,0.0)));
+		assert_vec(&Vec3::new(0.0,1.0,0.0),&c.normal(&Vec3::new(0.5,1.0,5.0)));
+	}
+
+	#[test]
+	fn test_cone() {
+		let c = Cone{base:Vec3::new(0.0,-1.0,5.0),apex:Vec3::new(0.0,1.0,5.0),radius:1.0};
+
+		// halfway up the radius is 0.5
+		let t = c.intersect(&Vec3::null(),&Vec3::new(0.0,0.0,1.0)).unwrap();
+		assert!((4.5-t).abs() < 1e-9);
+		assert_vec(&Vec3::new(0.0,1.0,-2.0).normalize(),&c.normal(&Vec3::new(0.0,0.0,4.5)));
+		// the other nappe above the apex is ignored
+		assert_eq!(None,c.intersect(&Vec3::new(0.0,2.0,0.0),&Vec3::new(0.0,0.0,1.0)));
+		// base cap seen from below
+		assert_eq!(Some(1.0),c.intersect(&Vec3::new(0.0,-2.0,5.0),&Vec3::new(0.0,1.0,0.0)));
+		assert_vec(&Vec3::new(0.0,-1.0,0.0),&c.normal(&Vec3::new(0.3,-1.0,5.0)));
+	}
+}
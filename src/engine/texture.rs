@@ -0,0 +1,351 @@
+use crate::math::vector3::Vec3;
+use crate::engine::color::Color;
+use crate::engine::tonemap::srgb_to_linear;
+use crate::math::noise::{self,Octaves};
+
+// what a texture is looked up with at a point of a surface
+pub struct Lookup
+{
+	// texture coordinates, v going up the image
+	pub uv:(f64,f64),
+	// world space position
+	pub point:Vec3,
+	// position in the frame of the object, the world for the mesh
+	pub local:Vec3,
+	// how point and local move along the surface with the texture
+	// coordinates, any frame of the surface when it has none
+	pub dpdu:Vec3,
+	pub dpdv:Vec3,
+	pub local_dpdu:Vec3,
+	pub local_dpdv:Vec3
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Filtering
+{
+	// the texel the point falls in
+	Nearest,
+	// blend of the four texels around the point
+	Bilinear
+}
+
+// how coordinates outside of [0,1] are brought back on the image
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Wrap
+{
+	Repeat,
+	Clamp,
+	Mirror
+}
+
+// picture in linear rgb, row after row from the top left corner
+pub struct Image
+{
+	pub width:usize,
+	pub height:usize,
+	pub texels:Vec<Color>
+}
+
+// where procedural textures are evaluated
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Space
+{
+	World,
+	// moves with the object
+	Object
+}
+
+// scalar patterns computed from the point, mostly in [0,1]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Pattern
+{
+	// squares of the texture coordinates
+	Checker,
+	// cubes of space
+	Checker3d,
+	Perlin,
+	Simplex,
+	Fbm(Octaves),
+	Turbulence(Octaves),
+	// veins along x bent by turbulence
+	Marble{ octaves:Octaves, strength:f64 },
+	// rings around the z axis, rings per unit, distorted by fbm
+	Wood{ octaves:Octaves, rings:f64, strength:f64 },
+	// distance to the closest cell center
+	Worley
+}
+
+pub enum Texture
+{
+	Image{ image:Image, filtering:Filtering, wrap:Wrap },
+	// blend of two colors by a pattern, the point or the uv being multiplied
+	// by scale first
+	Pattern{ pattern:Pattern, space:Space, scale:f64, colors:[Color;2] }
+}
+
+impl Lookup {
+	// the lookup of the point moved by (du,dv) along the surface
+	pub fn shift(&self,du:f64,dv:f64) -> Lookup
+	{
+		Lookup{
+			uv:(self.uv.0 + du,self.uv.1 + dv),
+			point:self.point.add(&self.dpdu.mul(du)).add(&self.dpdv.mul(dv)),
+			local:self.local.add(&self.local_dpdu.mul(du)).add(&self.local_dpdv.mul(dv)),
+			dpdu:self.dpdu.clone(),
+			dpdv:self.dpdv.clone(),
+			local_dpdu:self.local_dpdu.clone(),
+			local_dpdv:self.local_dpdv.clone()
+		}
+	}
+}
+
+impl Wrap {
+	// texel index along an axis of size texels
+	fn apply(&self,i:i64,size:usize) -> usize
+	{
+		let n = size as i64;
+		let i = match self {
+			Wrap::Repeat => i.rem_euclid(n),
+			Wrap::Clamp  => i.clamp(0,n-1),
+			Wrap::Mirror => {
+				let i = i.rem_euclid(2*n);
+				if i < n { i } else { 2*n-1-i }
+			}
+		};
+		i as usize
+	}
+}
+
+impl Image {
+	// 8 bits rgba texels, decoded from srgb for color images but kept as
+	// they are for data like heights or normals
+	pub fn from_rgba(width:usize,height:usize,rgba:&[[u8;4]],srgb:bool) -> Image
+	{
+		assert_eq!(width*height,rgba.len(),"image size does not match its texels");
+		let channel = |c:u8| {
+			let c = (c as f64)/255.0;
+			if srgb { srgb_to_linear(c) } else { c }
+		};
+		Image{
+			width,
+			height,
+			texels:rgba.iter().map(|t| Color::new(channel(t[0]),channel(t[1]),channel(t[2]))).collect()
+		}
+	}
+
+	fn texel(&self,x:i64,y:i64,wrap:Wrap) -> Color
+	{
+		self.texels[wrap.apply(y,self.height)*self.width + wrap.apply(x,self.width)]
+	}
+
+	pub fn sample(&self,u:f64,v:f64,filtering:Filtering,wrap:Wrap) -> Color
+	{
+		// the image is seen from its bottom left corner, texel centers at half
+		// integers
+		let x = u*(self.width as f64);
+		let y = (1.0 - v)*(self.height as f64);
+		match filtering {
+			Filtering::Nearest  => self.texel(x.floor() as i64,y.floor() as i64,wrap),
+			Filtering::Bilinear => {
+				let (x,y)   = (x - 0.5,y - 0.5);
+				let (x0,y0) = (x.floor(),y.floor());
+				let (fx,fy) = (x - x0,y - y0);
+				let (x0,y0) = (x0 as i64,y0 as i64);
+				let top     = self.texel(x0,y0,wrap).mul(1.0 - fx).add(&self.texel(x0+1,y0,wrap).mul(fx));
+				let bottom  = self.texel(x0,y0+1,wrap).mul(1.0 - fx).add(&self.texel(x0+1,y0+1,wrap).mul(fx));
+				top.mul(1.0 - fy).add(&bottom.mul(fy))
+			}
+		}
+	}
+}
+
+impl Pattern {
+	pub fn value(&self,uv:(f64,f64),p:&Vec3) -> f64
+	{
+		let parity = |n:f64| n.rem_euclid(2.0);
+		match self {
+			Pattern::Checker    => parity(uv.0.floor() + uv.1.floor()),
+			Pattern::Checker3d  => parity(p.x.floor() + p.y.floor() + p.z.floor()),
+			Pattern::Perlin     => 0.5 + 0.5*noise::perlin(p),
+			Pattern::Simplex    => 0.5 + 0.5*noise::simplex(p),
+			Pattern::Fbm(octaves)        => 0.5 + 0.5*noise::fbm(p,octaves),
+			Pattern::Turbulence(octaves) => noise::turbulence(p,octaves),
+			Pattern::Marble{octaves,strength} => {
+				0.5 + 0.5*(std::f64::consts::PI*(p.x + strength*noise::turbulence(p,octaves))).sin()
+			},
+			Pattern::Wood{octaves,rings,strength} => {
+				let r = (p.x*p.x + p.y*p.y).sqrt() + strength*noise::fbm(p,octaves);
+				(r*rings).rem_euclid(1.0)
+			},
+			Pattern::Worley => noise::worley(p)
+		}
+	}
+}
+
+impl Texture {
+	pub fn color(&self,lookup:&Lookup) -> Color
+	{
+		match self {
+			Texture::Image{image,filtering,wrap} => image.sample(lookup.uv.0,lookup.uv.1,*filtering,*wrap),
+			Texture::Pattern{pattern,space,scale,colors} => {
+				let point = match space {
+					Space::World  => &lookup.point,
+					Space::Object => &lookup.local
+				};
+				let t = pattern.value((lookup.uv.0*scale,lookup.uv.1*scale),&point.mul(*scale)).clamp(0.0,1.0);
+				colors[0].mul(1.0 - t).add(&colors[1].mul(t))
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// 2x2 image : black white on the top row, red green below
+	fn image() -> Image
+	{
+		Image::from_rgba(2,2,&[[0,0,0,255],[255,255,255,255],[255,0,0,255],[0,255,0,255]],false)
+	}
+
+	#[test]
+	fn test_wrap() {
+		assert_eq!(1,Wrap::Repeat.apply(-1,2));
+		assert_eq!(0,Wrap::Repeat.apply(4,2));
+		assert_eq!(0,Wrap::Clamp.apply(-3,2));
+		assert_eq!(1,Wrap::Clamp.apply(5,2));
+		assert_eq!(0,Wrap::Mirror.apply(-1,2));
+		assert_eq!(1,Wrap::Mirror.apply(2,2));
+		assert_eq!(0,Wrap::Mirror.apply(3,2));
+	}
+
+	#[test]
+	fn test_nearest() {
+		let image = image();
+
+		// v goes up : the bottom left texel is red
+		assert_eq!(Color::new(1.0,0.0,0.0),image.sample(0.25,0.25,Filtering::Nearest,Wrap::Repeat));
+		assert_eq!(Color::white(),image.sample(0.75,0.75,Filtering::Nearest,Wrap::Repeat));
+		assert_eq!(Color::white(),image.sample(-0.25,1.75,Filtering::Nearest,Wrap::Repeat));
+	}
+
+	#[test]
+	fn test_bilinear() {
+		let image = image();
+
+		// texel centers give the texels, the center of the image their average
+		assert_eq!(Color::new(0.0,1.0,0.0),image.sample(0.75,0.25,Filtering::Bilinear,Wrap::Clamp));
+		assert_eq!(Color::new(0.5,0.5,0.25),image.sample(0.5,0.5,Filtering::Bilinear,Wrap::Clamp));
+		// clamped edges do not blend with the other side
+		assert_eq!(Color::black(),image.sample(0.0,1.0,Filtering::Bilinear,Wrap::Clamp));
+		assert_eq!(Color::new(0.5,0.5,0.25),image.sample(0.0,1.0,Filtering::Bilinear,Wrap::Repeat));
+	}
+
+	fn lookup(uv:(f64,f64),point:Vec3,local:Vec3) -> Lookup
+	{
+		Lookup{
+			uv,
+			point,
+			local,
+			dpdu:Vec3::new(1.0,0.0,0.0),
+			dpdv:Vec3::new(0.0,1.0,0.0),
+			local_dpdu:Vec3::new(0.0,0.0,1.0),
+			local_dpdv:Vec3::new(0.0,1.0,0.0)
+		}
+	}
+
+	#[test]
+	fn test_shift() {
+		let shifted = lookup((0.5,0.5),Vec3::null(),Vec3::new(1.0,0.0,0.0)).shift(0.25,-0.5);
+
+		assert_eq!((0.75,0.0),shifted.uv);
+		assert!(shifted.point.sub(&Vec3::new(0.25,-0.5,0.0)).norm() < 1e-12);
+		assert!(shifted.local.sub(&Vec3::new(1.0,-0.5,0.25)).norm() < 1e-12);
+	}
+
+	#[test]
+	fn test_checkers() {
+		let origin = Vec3::null();
+		assert_eq!(0.0,Pattern::Checker.value((0.5,0.5),&origin));
+		assert_eq!(1.0,Pattern::Checker.value((1.5,0.5),&origin));
+		assert_eq!(1.0,Pattern::Checker.value((-0.5,0.5),&origin));
+		assert_eq!(1.0,Pattern::Checker3d.value((0.0,0.0),&Vec3::new(0.5,0.5,-0.5)));
+		assert_eq!(0.0,Pattern::Checker3d.value((0.0,0.0),&Vec3::new(1.5,0.5,-0.5)));
+	}
+
+	#[test]
+	fn test_pattern_values() {
+		let octaves = Octaves::default();
+		let lattice = Vec3::new(3.0,-2.0,5.0);
+		let value   = |pattern:Pattern,x:f64,y:f64| pattern.value((0.0,0.0),&Vec3::new(x,y,0.7));
+
+		// the gradient noises are 0 on the lattice, at every octave when
+		// the lacunarity is an integer
+		assert_eq!(0.5,Pattern::Perlin.value((0.0,0.0),&lattice));
+		assert_eq!(0.5,Pattern::Fbm(octaves).value((0.0,0.0),&lattice));
+		assert_eq!(0.0,Pattern::Turbulence(octaves).value((0.0,0.0),&lattice));
+		// without noise, marble is a sine along x and wood rings around z
+		let marble = || Pattern::Marble{octaves,strength:0.0};
+		let wood   = || Pattern::Wood{octaves,rings:8.0,strength:0.0};
+		assert!((value(marble(),0.5,0.3) - 1.0).abs() < 1e-12);
+		assert!(value(marble(),1.5,-2.0).abs() < 1e-12);
+		assert!((value(marble(),0.25,0.0) - value(marble(),2.25,4.0)).abs() < 1e-12);
+		assert_eq!(0.5,value(wood(),0.0625,0.0));
+		assert_eq!(0.0,value(wood(),3.0,4.0));
+		assert_eq!(0.5,value(wood(),0.0,-0.0625));
+	}
+
+	#[test]
+	fn test_patterns_in_range() {
+		let octaves = Octaves::default();
+		// bound of the sum of the octaves of a noise in [-1,1]
+		let sum     = (1.0 - octaves.gain.powi(octaves.count as i32))/(1.0 - octaves.gain);
+		let ranges  = [
+			(Pattern::Perlin,0.0,1.0),
+			(Pattern::Simplex,0.0,1.0),
+			(Pattern::Fbm(octaves),0.5 - 0.5*sum,0.5 + 0.5*sum),
+			(Pattern::Turbulence(octaves),0.0,sum),
+			(Pattern::Marble{octaves,strength:4.0},0.0,1.0),
+			(Pattern::Wood{octaves,rings:8.0,strength:0.2},0.0,1.0),
+			(Pattern::Worley,0.0,3.0_f64.sqrt())
+		];
+		for (pattern,low,high) in &ranges
+		{
+			let mut values = Vec::new();
+			for n in 0..500
+			{
+				let p = Vec3::new((n as f64)*0.37,(n as f64)*-0.11,1.3 + (n as f64)*0.05);
+				let v = pattern.value((0.0,0.0),&p);
+				assert!((*low..=*high).contains(&v),"{:?} gives {}",pattern,v);
+				values.push(v);
+			}
+			// and they do vary over it
+			let spread = values.iter().copied().fold(f64::MIN,f64::max) - values.iter().copied().fold(f64::MAX,f64::min);
+			assert!(spread > 0.2*(high - low),"{:?} spreads over {}",pattern,spread);
+		}
+		// wood never reaches the next ring
+		assert!(Pattern::Wood{octaves,rings:8.0,strength:0.0}.value((0.0,0.0),&Vec3::new(0.124,0.0,0.0)) < 1.0);
+	}
+
+	#[test]
+	fn test_pattern_space() {
+		let checker = |space:Space| Texture::Pattern{
+			pattern:Pattern::Checker3d,
+			space,
+			scale:2.0,
+			colors:[Color::black(),Color::white()]
+		};
+
+		// cubes of half a unit, in the frame of the object or of the world
+		let at = lookup((0.0,0.0),Vec3::new(0.25,0.0,0.0),Vec3::new(0.75,0.0,0.0));
+		assert_eq!(Color::white(),checker(Space::Object).color(&at));
+		assert_eq!(Color::black(),checker(Space::World).color(&at));
+	}
+
+	#[test]
+	fn test_srgb() {
+		let image = Image::from_rgba(1,1,&[[188,188,188,255]],true);
+		assert!((image.texels[0].r - 0.5).abs() < 0.01);
+	}
+}
@@ -0,0 +1,101 @@
+use crate::engine::color::Color;
+
+// curve bringing linear radiance to the displayable [0,1] range
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ToneMap
+{
+	// everything above 1 saturates
+	Clamp,
+	// x/(1+x), never saturates but washes out the highlights
+	Reinhard,
+	// Narkowicz fit of the ACES filmic curve, contrasted with a soft shoulder
+	Aces
+}
+
+// what happens to the rendered radiance before it is quantized to 8 bits
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct OutputTransform
+{
+	// in stops, every unit doubles the brightness
+	pub exposure:f64,
+	pub tone_map:ToneMap,
+	// sRGB transfer function, linear values are written as is otherwise
+	pub srgb:bool
+}
+
+impl Default for OutputTransform
+{
+	fn default() -> OutputTransform
+	{
+		OutputTransform{exposure:0.0,tone_map:ToneMap::Clamp,srgb:true}
+	}
+}
+
+pub fn linear_to_srgb(x:f64) -> f64
+{
+	if x <= 0.0031308 { 12.92*x } else { 1.055*x.powf(1.0/2.4) - 0.055 }
+}
+
+pub fn srgb_to_linear(x:f64) -> f64
+{
+	if x <= 0.04045 { x/12.92 } else { ((x + 0.055)/1.055).powf(2.4) }
+}
+
+impl ToneMap {
+	pub fn apply(&self,x:f64) -> f64
+	{
+		let x = x.max(0.0);
+		match self {
+			ToneMap::Clamp    => x.min(1.0),
+			ToneMap::Reinhard => x/(1.0 + x),
+			ToneMap::Aces     => ((x*(2.51*x + 0.03))/(x*(2.43*x + 0.59) + 0.14)).clamp(0.0,1.0)
+		}
+	}
+}
+
+impl OutputTransform {
+	// display value in [0,1] of a linear color
+	pub fn apply(&self,c:&Color) -> Color
+	{
+		let scale   = 2.0_f64.powf(self.exposure);
+		let channel = |x:f64| {
+			let mapped = self.tone_map.apply(x*scale);
+			if self.srgb { linear_to_srgb(mapped) } else { mapped }
+		};
+		Color::new(channel(c.r),channel(c.g),channel(c.b))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_srgb_round_trip() {
+		for i in 0..=255
+		{
+			let x = (i as f64)/255.0;
+			assert!((linear_to_srgb(srgb_to_linear(x))-x).abs() < 1e-12);
+		}
+		assert!((linear_to_srgb(0.5)-0.735356983).abs() < 1e-6);
+	}
+
+	#[test]
+	fn test_tone_maps() {
+		assert_eq!(1.0,ToneMap::Clamp.apply(4.0));
+		assert_eq!(0.8,ToneMap::Reinhard.apply(4.0));
+		assert!(ToneMap::Aces.apply(100.0) > 0.99);
+		for tone_map in &[ToneMap::Clamp,ToneMap::Reinhard,ToneMap::Aces]
+		{
+			assert_eq!(0.0,tone_map.apply(-1.0));
+			assert!(tone_map.apply(0.5) < tone_map.apply(0.6));
+		}
+	}
+
+	#[test]
+	fn test_exposure() {
+		let transform = OutputTransform{exposure:1.0,tone_map:ToneMap::Clamp,srgb:false};
+
+		assert_eq!(Color::new(0.5,1.0,0.0),transform.apply(&Color::new(0.25,0.75,0.0)));
+	}
+}
@@ -1,130 +1,523 @@
 use crate::engine::camera::Camera;
+use crate::engine::scene::{Scene,Primitive,Hit};
+use crate::engine::color::Color;
+use crate::engine::bsdf::{Bsdf,Frame,Scattering};
+use crate::engine::light::power_heuristic;
+use crate::engine::integrator::Integrator;
+use crate::engine::framebuffer::Framebuffer;
+use crate::engine::filter::Filter;
+use crate::engine::tonemap::OutputTransform;
+use crate::engine::aov::Surface;
+use crate::engine::triangle;
+use crate::engine::ray::{Ray,offset};
+use crate::engine::texture::Lookup;
+use crate::math::random::Rng;
 use crate::math::vector3::Vec3;
-use crate::math::matrix3::Mat3;
+
+use std::error::Error;
+use std::f64::consts::PI;
+use std::fmt;
 
 use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
 
-#[derive(Copy, Clone)]
+// 8 bits rgba, the layout lodepng expects
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub struct Pixel(pub u8,pub u8,pub u8,pub u8);
 
 impl Pixel {
     pub fn white() -> Pixel { Pixel(255,255,255,255)}
     pub fn black() -> Pixel { Pixel(  0,  0,  0,255)}
-    pub fn gray(t:f64)  -> Pixel { 
-    	let mut color = 255.0*t  ;
-    	if color < 0.0   { color = 0.0; }
-    	if color > 255.0 { color = 255.0; }
-    	let color:u8 = color as u8;
-    	Pixel(color,color,color,255)
-    }
     pub fn blue() -> Pixel { Pixel(0,0,255,255)}
+    pub fn from_color(c:&Color) -> Pixel {
+    	Pixel::from_color_alpha(c,1.0)
+    }
+    pub fn from_color_alpha(c:&Color,alpha:f64) -> Pixel {
+    	let channel = |t:f64| (255.0*t).round().clamp(0.0,255.0) as u8;
+    	Pixel(channel(c.r),channel(c.g),channel(c.b),channel(alpha))
+    }
 }
 pub struct Tracer{
 	pub cam:Camera,
 	pub screen:(usize,usize),
-	pub light: Vec3,
-	pub background: Pixel
+	// linear radiance of the rays leaving a scene without environment and
+	// coverage of the pixels where camera rays miss everything there
+	pub background: Color,
+	pub background_alpha: f64,
+	pub threads: usize,
+	pub tile_size: usize,
+	// number of reflection or refraction bounces allowed after the camera ray
+	pub max_depth: usize,
+	pub integrator: Integrator,
+	// camera rays per pixel
+	pub samples: usize,
+	pub filter: Filter,
+	// from the rendered radiance to the written image
+	pub output: OutputTransform,
+	// hides the progress bar
+	pub quiet: bool,
+	// also finds the surface seen through every pixel for the auxiliary passes
+	pub aovs: bool
+}
+
+// what keeps the tracer from rendering
+#[derive(Debug, Clone, PartialEq)]
+pub enum RenderError
+{
+	// the pool of render threads could not be started
+	Threads(String)
+}
+
+impl fmt::Display for RenderError
+{
+	fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result
+	{
+		match self {
+			RenderError::Threads(error) => write!(f,"unable to start render threads : {}",error)
+		}
+	}
+}
+
+impl Error for RenderError {}
+
+// a camera ray through the point (x,y) of the image and what it brought back
+struct Sample
+{
+	x:f64,
+	y:f64,
+	color:Color,
+	alpha:f64,
+	// first surface met, for the first sample of a pixel when the aovs are on
+	surface:Option<Surface>
+}
+
+// a rectangular bucket of the image : (x,y) of its top left corner, width, height
+struct Tile(usize,usize,usize,usize);
+
+// filter weighted sums of the samples of a tile over the pixels they reach,
+// which spill over the tile by the radius of the filter. Adding up the
+// splats of every tile gives each pixel all the samples around it.
+struct Splats
+{
+	region:Tile,
+	colors:Vec<Color>,
+	alpha:Vec<f64>,
+	weights:Vec<f64>
+}
+
+// a point being shaded : the geometric normal of the side it is seen from,
+// the shading frame and wo in it, and the bsdf there
+pub(crate) struct Shading<'a>
+{
+	pub point:Vec3,
+	pub facing:Vec3,
+	pub frame:Frame,
+	pub wo:Vec3,
+	pub bsdf:Scattering<'a>
 }
 
 impl Tracer{
 
-	fn triangle_intersect(triangle:&[Vec3;3],origin:&Vec3,ray:&Vec3) -> bool
+	// black background, one thread per core, whitted integrator, 1 sample
+	// per pixel with a box filter, no progress bar and no aovs, every field
+	// can be changed afterwards
+	pub fn new(cam:Camera,width:usize,height:usize) -> Tracer
+	{
+		Tracer{
+			cam,
+			screen:(width,height),
+			background:Color::black(),
+			background_alpha:1.0,
+			threads:0,
+			tile_size:32,
+			max_depth:5,
+			integrator:Integrator::Whitted,
+			samples:1,
+			filter:Filter::Box,
+			output:OutputTransform::default(),
+			quiet:true,
+			aovs:false
+		}
+	}
+
+	pub(crate) fn normal(scene:&Scene,primitive:Primitive,point:&Vec3) -> Vec3
+	{
+		match primitive {
+			Primitive::Triangle(index) => {
+				let triangle = &scene.mesh()[index];
+				let axe1     = triangle[1].sub(&triangle[0]);
+				let axe2     = triangle[2].sub(&triangle[0]);
+				axe1.cross(&axe2).normalize()
+			},
+			Primitive::Object(index) => scene.objects()[index].normal(point)
+		}
+	}
+
+	// normal used for lighting, interpolated from the vertex normals of
+	// smooth triangles and turned to the side of facing, the geometric
+	// normal of the face seen by the ray
+	pub(crate) fn shading_normal(scene:&Scene,hit:&Hit,facing:&Vec3) -> Vec3
+	{
+		let index   = match hit.primitive {
+			Primitive::Triangle(index) => index,
+			Primitive::Object(_)       => return facing.clone()
+		};
+		let normals = match scene.normals(index) {
+			Some(normals) => normals,
+			None          => return facing.clone()
+		};
+		let normal = triangle::interpolate(normals,facing,hit.uv.0,hit.uv.1);
+		if normal.dot(facing) < 0.0 { normal.mul(-1.0) } else { normal }
+	}
+
+	// where the textures of the primitive hit are looked up at point
+	pub(crate) fn lookup(scene:&Scene,hit:&Hit,point:&Vec3) -> Lookup
+	{
+		let primitive = hit.primitive;
+		let (uv,derivatives) = match primitive {
+			Primitive::Triangle(index) => match scene.uvs(index) {
+				Some(uvs) => {
+					let (u,v) = hit.uv;
+					let w     = 1.0 - u - v;
+					(
+						(w*uvs[0].0 + u*uvs[1].0 + v*uvs[2].0,w*uvs[0].1 + u*uvs[1].1 + v*uvs[2].1),
+						triangle::uv_derivatives(&scene.mesh()[index],uvs)
+					)
+				},
+				None => ((0.0,0.0),None)
+			},
+			Primitive::Object(index) => (scene.objects()[index].uv(point),scene.objects()[index].uv_derivatives(point))
+		};
+		let (dpdu,dpdv) = derivatives.unwrap_or_else(|| Tracer::normal(scene,primitive,point).basis());
+		let (local,local_dpdu,local_dpdv) = match primitive {
+			Primitive::Triangle(_)   => (point.clone(),dpdu.clone(),dpdv.clone()),
+			Primitive::Object(index) => {
+				// object frames only move and turn the world
+				let object = &scene.objects()[index];
+				let local  = object.local(point);
+				let du     = object.local(&point.add(&dpdu)).sub(&local);
+				let dv     = object.local(&point.add(&dpdv)).sub(&local);
+				(local,du,dv)
+			}
+		};
+		Lookup{uv,point:point.clone(),local,dpdu,dpdv,local_dpdu,local_dpdv}
+	}
+
+	// light the bsdf sends to wo from every light that sees the point, wo
+	// being in the shading frame. A light is as bright as a white lambertian
+	// surface facing it. The path integrator also finds area lights along
+	// the directions sampled from the bsdf, both are weighted against each
+	// other (multiple importance sampling).
+	pub(crate) fn compute_color(&self,scene:&Scene,shading:&Shading,rng:&mut Rng) -> Color
+	{
+		let mut color = Color::black();
+		let Shading{point,facing,frame,wo,bsdf} = shading;
+
+		for light in scene.lights()
+		{
+			let sample = match light.illuminate(scene,point,&[rng.next_f64(),rng.next_f64(),rng.next_f64()]) {
+				Some(sample) => sample,
+				None         => continue
+			};
+
+			let wi = frame.to_local(&sample.direction);
+			if wi.z == 0.0 { continue; }
+			let f  = bsdf.evaluate(wo,&wi);
+			if f.is_black() { continue; }
+			// off the actual surface, which the shading normal may lean away from
+			let visible = self.transmittance(scene,&Ray::shadow(point,facing,&sample.direction,sample.distance));
+			if visible == 0.0 { continue; }
+
+			let weight = if self.integrator == Integrator::Path { power_heuristic(sample.pdf,bsdf.pdf(wo,&wi)) } else { 1.0 };
+			color = color.add(&f.mul(PI*wi.z.abs()*weight*visible).mul_color(&sample.radiance));
+		}
+		color
+	}
+
+	// share of the light going along a shadow ray, through the surfaces that
+	// only partly cover it
+	fn transmittance(&self,scene:&Scene,ray:&Ray) -> f64
 	{
-		let axe1 = triangle[0].sub(origin);
-		let axe2 = triangle[1].sub(origin);
-		let axe3 = triangle[2].sub(origin);
+		let mut ray   = ray.clone();
+		let mut share = 1.0;
+		while let Some(hit) = self.closest(scene,&ray)
+		{
+			share *= 1.0 - scene.material(hit.primitive).opacity;
+			if share <= 0.0 { return 0.0; }
+			ray.t_min = hit.t + offset(&ray.at(hit.t));
+		}
+		share
+	}
+
+	// light coming along a ray that leaves the scene : the environment, or
+	// the background color without one
+	pub(crate) fn miss(&self,scene:&Scene,direction:&Vec3) -> Color
+	{
+		match scene.environment() {
+			Some(environment) => environment.radiance(&direction.normalize()),
+			None              => self.background
+		}
+	}
+
+	// color seen along a secondary ray, what it misses when it escapes
+	fn trace(&self,scene:&Scene,ray:&Ray,depth:usize,rng:&mut Rng) -> Color
+	{
+		match self.closest(scene,ray) {
+			None      => self.miss(scene,&ray.direction),
+			Some(hit) => self.shade(scene,&hit,ray,depth,rng)
+		}
+	}
 
-		let basis = Mat3::from_basis(&axe1,&axe2,&axe3).inv();
-		return match basis {
-			None => false,
-			Some(basis) => {
-				let ray = basis.mul_vec3(ray);
-				(ray.x >= 0.0 && ray.y >= 0.0 && ray.z >= 0.0)
+	// color of the point where ray hit a primitive, spawning reflected and
+	// refracted rays until max_depth is reached
+	fn shade(&self,scene:&Scene,hit:&Hit,ray:&Ray,depth:usize,rng:&mut Rng) -> Color
+	{
+		let primitive = hit.primitive;
+		let material  = scene.material(primitive);
+		let point     = ray.at(hit.t);
+		let direction = ray.direction.normalize();
+		let view      = direction.mul(-1.0);
+		let outward   = Tracer::normal(scene,primitive,&point);
+		let entering  = outward.dot(&view) >= 0.0;
+		// surfaces are two sided : shade the face seen by the ray
+		let facing    = if entering { outward } else { outward.mul(-1.0) };
+		let lookup    = Tracer::lookup(scene,hit,&point);
+		let normal    = material.perturb(&lookup,&Tracer::shading_normal(scene,hit,&facing));
+
+		let frame     = Frame::new(&normal);
+		let wo        = frame.to_local(&view);
+		let shading   = Shading{point:point.clone(),facing:facing.clone(),frame,wo,bsdf:material.bsdf(&lookup,entering)};
+
+		// emitters are seen directly and through mirrors
+		let mut color = material.emission.add(&self.compute_color(scene,&shading,rng));
+		if depth < self.max_depth
+		{
+			for (wi,weight) in shading.bsdf.specular(&shading.wo)
+			{
+				let ray = Ray::spawn(&point,&facing,&shading.frame.to_world(&wi));
+				color = color.add(&self.trace(scene,&ray,depth+1,rng).mul_color(&weight));
 			}
 		}
+		if material.opacity < 1.0
+		{
+			// the uncovered part shows what is behind, which is not a bounce
+			let behind = Ray::spawn(&point,&facing,&direction);
+			color = color.mul(material.opacity).add(&self.trace(scene,&behind,depth,rng).mul(1.0 - material.opacity));
+		}
+		color
 	}
 
-	fn triangle_distance(triangle:&[Vec3;3],origin:&Vec3,ray:&Vec3) -> Option<Vec3>
+	// closest primitive inside the interval of the ray
+	pub(crate) fn closest(&self,scene:&Scene,ray:&Ray) -> Option<Hit>
 	{
-		let axe1 = ray.mul(-1.0);
-		let axe2 = triangle[1].sub(&triangle[0]);
-		let axe3 = triangle[2].sub(&triangle[0]);
+		let hit = scene.closest_triangle(ray).map(|(index,hit)| Hit{primitive:Primitive::Triangle(index),t:hit.t,uv:(hit.u,hit.v)});
 
-		let from_basis = Mat3::from_basis(&axe1,&axe2,&axe3);
+		let mut rest = ray.clone();
+		if let Some(hit) = hit { rest.t_max = hit.t; }
+		match scene.closest_object(&rest) {
+			Some((index,t)) => Some(Hit{primitive:Primitive::Object(index),t,uv:(0.0,0.0)}),
+			None            => hit
+		}
+	}
 
-		let basis = from_basis.inv();
-		return match basis {
-			None => None,
-			Some(basis) => {
-				let vec = origin.sub(&triangle[0]);
-				let sol = basis.mul_vec3(&vec);
-				Some(sol)
+	fn tiles(&self) -> Vec<Tile>
+	{
+		let size  = self.tile_size.max(1);
+		let mut tiles = Vec::<Tile>::new();
+		for y in (0..self.screen.1).step_by(size)
+		{
+			for x in (0..self.screen.0).step_by(size)
+			{
+				tiles.push(Tile(x,y,size.min(self.screen.0-x),size.min(self.screen.1-y)));
 			}
 		}
+		tiles
 	}
 
-	fn compute_color(&self,triangle:&[Vec3;3]) -> Pixel
+	// color and coverage of the camera ray through the point (x,y) of the
+	// image, and the surface it meets when aov is set
+	fn sample(&self,scene:&Scene,x:f64,y:f64,aov:bool,rng:&mut Rng) -> Sample
 	{
-		let axe1   = triangle[1].sub(&triangle[0]);
-		let axe2   = triangle[2].sub(&triangle[0]);
-		let normal = axe1.cross(&axe2).normalize();
+		let ray           = self.cam.primary(x,y,self.screen.0,self.screen.1);
+		let hit           = match self.closest(scene,&ray) {
+			Some(hit) => hit,
+			None      => {
+				// an environment covers the whole image
+				let alpha = if scene.environment().is_some() { 1.0 } else { self.background_alpha };
+				return Sample{x,y,color:self.miss(scene,&ray.direction),alpha,surface:None}
+			}
+		};
+		let color = match self.integrator {
+			Integrator::Whitted => self.shade(scene,&hit,&ray,0,rng),
+			Integrator::Path    => self.path_radiance(scene,&hit,&ray,rng)
+		};
+		let surface = if aov { Some(self.surface(scene,&hit,&ray)) } else { None };
+		Sample{x,y,color,alpha:1.0,surface}
+	}
 
-		let ligh_direction = self.light.normalize();
+	// samples jittered inside the cells of a grid covering the pixel, a
+	// single sample stays at the center
+	fn pixel_samples(&self,scene:&Scene,i:usize,j:usize) -> Vec<Sample>
+	{
+		// seeded by pixel so that the noise does not depend on the tiling
+		let mut rng = Rng::new((j*self.screen.0+i) as u64,0);
+		let count   = self.samples.max(1);
+		if count == 1
+		{
+			return vec![self.sample(scene,(i as f64)+0.5,(j as f64)+0.5,self.aovs,&mut rng)];
+		}
 
-		Pixel::gray(ligh_direction.dot(&normal))
+		// exactly one sample per cell, the grid as square as the divisors
+		// of count allow
+		let (columns,rows) = Tracer::strata(count);
+		(0..count).map(|k| {
+			let x = (i as f64) + (((k%columns) as f64) + rng.next_f64())/(columns as f64);
+			let y = (j as f64) + (((k/columns) as f64) + rng.next_f64())/(rows as f64);
+			self.sample(scene,x,y,self.aovs && k == 0,&mut rng)
+		}).collect()
 	}
 
-	pub fn render(&self,mesh:&Vec<[Vec3;3]>) -> Vec<Pixel>
+	// surface a camera ray hits, the normal facing the camera
+	fn surface(&self,scene:&Scene,hit:&Hit,ray:&Ray) -> Surface
 	{
-		let mut rendered_ray = 0;
-    	let total_size = self.screen.0*self.screen.1;
+		let primitive      = hit.primitive;
+		let point          = ray.at(hit.t);
+		let outward        = Tracer::normal(scene,primitive,&point);
+		let facing         = if outward.dot(&ray.direction) > 0.0 { outward.mul(-1.0) } else { outward };
+		let lookup         = Tracer::lookup(scene,hit,&point);
+		let material       = scene.material(primitive);
+		let normal         = material.perturb(&lookup,&Tracer::shading_normal(scene,hit,&facing));
+		Surface{
+			depth:self.cam.depth(&ray.direction,hit.t),
+			normal,
+			albedo:material.albedo(&lookup),
+			primitive:scene.primitive_id(primitive),
+			object:scene.object_id(primitive)
+		}
+	}
 
-		let pb = ProgressBar::new(total_size as u64);
-		pb.set_style(ProgressStyle::default_bar()
-			.template("[{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} ({eta})")
-			.progress_chars("#>-"));
+	// columns and rows of a grid of count cells, rows being the largest
+	// divisor of count not above its square root
+	fn strata(count:usize) -> (usize,usize)
+	{
+		let rows = (1..=count).take_while(|r| r*r <= count).filter(|r| count % *r == 0).last().unwrap_or(1);
+		(count/rows,rows)
+	}
 
-		let rays = self.cam.get_rays(self.screen.0,self.screen.1);
-		let mut out:Vec<Pixel> = Vec::<Pixel>::with_capacity(total_size);
+	// splats of the samples of the pixels of the tile and their surfaces
+	// when the aovs are on
+	fn render_tile(&self,scene:&Scene,tile:&Tile) -> (Splats,Vec<Option<Surface>>)
+	{
+		let (columns,rows) = self.screen;
+		// samples may fall under the filter of the pixels around the tile
+		let apron  = (self.filter.radius() - 0.5).ceil().max(0.0) as usize;
+		let left   = tile.0.saturating_sub(apron);
+		let top    = tile.1.saturating_sub(apron);
+		let right  = (tile.0+tile.2+apron).min(columns);
+		let bottom = (tile.1+tile.3+apron).min(rows);
+		let width  = right-left;
 
-		for j in 0..self.screen.1
+		let mut splats = Splats{
+			region:Tile(left,top,width,bottom-top),
+			colors:vec![Color::black();width*(bottom-top)],
+			alpha:vec![0.0;width*(bottom-top)],
+			weights:vec![0.0;width*(bottom-top)]
+		};
+		let mut surfaces = Vec::<Option<Surface>>::with_capacity(tile.2*tile.3);
+		for j in tile.1..tile.1+tile.3
 		{
-			for i in 0..self.screen.0
+			for i in tile.0..tile.0+tile.2
 			{
-				let mut min = 10.0;
-				let mut closest:Option<&[Vec3;3]> = None;
-
-				for triangle in mesh
+				let mut samples = self.pixel_samples(scene,i,j);
+				surfaces.push(samples[0].surface.take());
+				for q in j.saturating_sub(apron)..(j+apron+1).min(rows)
 				{
-					if Tracer::triangle_intersect(triangle,&self.cam.position,&rays[j*self.screen.0+i])
+					for p in i.saturating_sub(apron)..(i+apron+1).min(columns)
 					{
-						let distance = Tracer::triangle_distance(triangle,&self.cam.position,&rays[j*self.screen.0+i]);
-						if let Some(v)= distance {
-							if min > v.x 
-							{ 
-								min = v.x;
-								closest = Some(&triangle);
-							}
+						let center = ((p as f64)+0.5,(q as f64)+0.5);
+						let index  = (q-top)*width+(p-left);
+						for sample in &samples
+						{
+							let w = self.filter.weight(sample.x-center.0,sample.y-center.1);
+							if w == 0.0 { continue; }
+							splats.colors[index]   = splats.colors[index].add(&sample.color.mul(w));
+							splats.alpha[index]   += sample.alpha*w;
+							splats.weights[index] += w;
 						}
 					}
 				}
-				match closest{
-					None           => out.push(self.background),
-					Some(triangle) => out.push(self.compute_color(triangle))
-				}
-				
-				rendered_ray += 1;
-		        pb.set_position(rendered_ray);
-			}		
+			}
+		}
+		(splats,surfaces)
+	}
+
+	// with 0 threads the tiles go to the global rayon pool, one thread per
+	// logical core, else to a pool of that many threads
+	pub fn render(&self,scene:&Scene) -> Result<Framebuffer,RenderError>
+	{
+		let tiles = self.tiles();
+
+		let pb = if self.quiet { ProgressBar::hidden() } else { ProgressBar::new(tiles.len() as u64) };
+		pb.set_style(ProgressStyle::default_bar()
+			.template("[{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} tiles ({eta})")
+			.progress_chars("#>-"));
+
+		let render_tiles = || -> Vec<(Splats,Vec<Option<Surface>>)> {
+			tiles.par_iter().map(|tile| {
+				let rendered = self.render_tile(scene,tile);
+				pb.inc(1);
+				rendered
+			}).collect()
+		};
+		let rendered = if self.threads == 0 { render_tiles() } else {
+			rayon::ThreadPoolBuilder::new()
+				.num_threads(self.threads)
+				.build()
+				.map_err(|e| RenderError::Threads(e.to_string()))?
+				.install(render_tiles)
+		};
+
+		// tiles come back in submission order whatever thread rendered them,
+		// so the sums and the image do not depend on the scheduling
+		let mut colors  = vec![Color::black();self.screen.0*self.screen.1];
+		let mut alpha   = vec![0.0;self.screen.0*self.screen.1];
+		let mut weights = vec![0.0;self.screen.0*self.screen.1];
+		let mut surfaces:Vec<Option<Surface>> = vec![None;self.screen.0*self.screen.1];
+		for (tile,(splats,tile_surfaces)) in tiles.iter().zip(rendered)
+		{
+			let region = &splats.region;
+			for k in 0..region.2*region.3
+			{
+				let index       = (region.1+k/region.2)*self.screen.0+region.0+k%region.2;
+				colors[index]   = colors[index].add(&splats.colors[k]);
+				alpha[index]   += splats.alpha[k];
+				weights[index] += splats.weights[k];
+			}
+			for (k,surface) in tile_surfaces.into_iter().enumerate()
+			{
+				surfaces[(tile.1+k/tile.2)*self.screen.0+tile.0+k%tile.2] = surface;
+			}
+		}
+		for ((color,coverage),weight) in colors.iter_mut().zip(alpha.iter_mut()).zip(weights)
+		{
+			let weight = if weight == 0.0 { 1.0 } else { weight };
+			*color     = color.mul(1.0/weight);
+			*coverage /= weight;
 		}
 	    pb.finish_with_message("done");
-		out
+		let mut image = Framebuffer::new(self.screen.0,self.screen.1,colors,alpha);
+		if self.aovs { image.surfaces = Some(surfaces); }
+		Ok(image)
 	}
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
 	use super::*;
+	use crate::engine::light::Light;
+	use crate::engine::scene::SceneBuilder;
+	use crate::engine::tonemap::{ToneMap,srgb_to_linear};
+	use crate::engine::shape::{Shape,Sphere};
 
 	// computation can easily be done by hand on a drawing 
 
@@ -138,18 +531,272 @@ mod tests {
 		let triangle   = [p0,p1,p2];
 
 		let expected   = Vec3::new(1.0,3.0/5.0,1.0/3.0);
-		let out        = Tracer::triangle_distance(&triangle,&line_point,&line_dir);
+		for out in &[triangle::moller_trumbore(&triangle,&line_point,&line_dir),triangle::watertight(&triangle,&line_point,&line_dir)]
+		{
+			match out {
+			    None      => panic!("no intersection found"),
+			    Some(out) => {
+			    	assert_eq!(expected.x,out.t);
+			    	assert_eq!(expected.y,out.u);
+			    	assert_eq!(expected.z,out.v);
+			    }
+			}
+		}
+	}
+
+
+	// also used by the integrator tests
+	pub(crate) fn test_tracer(threads:usize,tile_size:usize) -> Tracer
+	{
+		let cam        = Camera::new(&Vec3::new(0.0,0.0,-10.0),&Vec3::new(0.0,0.0,10.0),&Vec3::new(0.0,1.0,0.0),70.0);
+		let mut tracer = Tracer::new(cam,37,23);
+		tracer.background = Color::new(0.0,0.0,1.0);
+		tracer.threads    = threads;
+		tracer.tile_size  = tile_size;
+		tracer.samples    = 1;
+		tracer
+	}
+
+	#[test]
+	fn test_tiles_cover_screen() {
+		let tracer = test_tracer(1,8);
+		let tiles  = tracer.tiles();
 
-		match out {
-		    None      => assert!(false),
-		    Some(out) => {
-		    	assert_eq!(expected.x,out.x);
-		    	assert_eq!(expected.y,out.y);
-		    	assert_eq!(expected.z,out.z);
-		    }
+		let covered:usize = tiles.iter().map(|t| t.2*t.3).sum();
+		assert_eq!(37*23,covered);
+		assert_eq!(5*3,tiles.len());
+		for t in &tiles
+		{
+			assert!(t.0+t.2 <= 37);
+			assert!(t.1+t.3 <= 23);
 		}
 	}
 
+	#[test]
+	fn test_render_independent_of_thread_count() {
+		let mesh = vec![
+			[Vec3::new(-2.0,-2.0,0.0),Vec3::new(2.0,-2.0,0.0),Vec3::new(0.0,2.0,-1.0)],
+			[Vec3::new(-3.0,-1.0,1.0),Vec3::new(1.0,3.0,1.0),Vec3::new(3.0,-3.0,2.0)],
+		];
+		let mut scene = SceneBuilder::new(mesh,Vec::new());
+		scene.lights.push(Light::Directional{direction:Vec3::new(-5.0,-5.0,5.0),color:Color::white(),intensity:1.0});
+		let scene = scene.build().unwrap();
+
+		let single = test_tracer(1,7).render(&scene).unwrap();
+		let multi  = test_tracer(4,7).render(&scene).unwrap();
+		let global = test_tracer(0,7).render(&scene).unwrap();
+
+		assert_eq!(single.colors.len(),37*23);
+		assert_eq!(single.colors,multi.colors);
+		assert_eq!(single.colors,global.colors);
+	}
+
+	#[test]
+	fn test_filtered_render_independent_of_tiling() {
+		let mesh = vec![
+			[Vec3::new(-2.0,-2.0,0.0),Vec3::new(2.0,-2.0,0.0),Vec3::new(0.0,2.0,-1.0)],
+		];
+		let mut scene = SceneBuilder::new(mesh,Vec::new());
+		scene.lights.push(Light::Directional{direction:Vec3::new(-5.0,-5.0,5.0),color:Color::white(),intensity:1.0});
+		let scene = scene.build().unwrap();
+
+		let filtered = |threads:usize,tile_size:usize| {
+			let mut tracer = test_tracer(threads,tile_size);
+			tracer.samples = 4;
+			tracer.filter  = Filter::Mitchell;
+			tracer.render(&scene).unwrap()
+		};
+		// the splats of the tiles add up in another order
+		for (a,b) in filtered(1,7).colors.iter().zip(filtered(3,5).colors)
+		{
+			assert!((a.r-b.r).abs() < 1e-12 && (a.g-b.g).abs() < 1e-12 && (a.b-b.b).abs() < 1e-12,"{:?} {:?}",a,b);
+		}
+		assert_eq!(filtered(2,7).colors,filtered(3,7).colors);
+	}
+
+	#[test]
+	fn test_strata() {
+		assert_eq!((1,1),Tracer::strata(1));
+		assert_eq!((4,4),Tracer::strata(16));
+		assert_eq!((3,2),Tracer::strata(6));
+		assert_eq!((7,1),Tracer::strata(7));
+		assert_eq!((4,3),Tracer::strata(12));
+		// every cell of the grid gets a sample
+		for count in 1..50
+		{
+			let (columns,rows) = Tracer::strata(count);
+			assert_eq!(count,columns*rows);
+			assert!(rows <= columns);
+		}
+	}
+
+	#[test]
+	fn test_empty_scene_is_background() {
+		let scene = SceneBuilder::new(Vec::new(),Vec::new()).build().unwrap();
+
+		for filter in &[Filter::Box,Filter::Tent,Filter::Gaussian,Filter::Mitchell]
+		{
+			let mut tracer = test_tracer(2,8);
+			tracer.samples    = 5;
+			tracer.filter     = *filter;
+			tracer.background       = Color::new(srgb_to_linear(10.0/255.0),srgb_to_linear(20.0/255.0),srgb_to_linear(30.0/255.0));
+			tracer.background_alpha = 40.0/255.0;
+			for pixel in &tracer.render(&scene).unwrap().to_pixels(&tracer.output)
+			{
+				assert_eq!(Pixel(10,20,30,40),*pixel);
+			}
+		}
+	}
+
+	#[test]
+	fn test_environment_is_opaque() {
+		use crate::engine::environment::Environment;
+		use crate::engine::texture::Image;
+
+		let mut scene  = SceneBuilder::new(Vec::new(),Vec::new());
+		let sky        = Image{width:2,height:1,texels:vec![Color::new(0.5,0.5,0.5);2]};
+		scene.lights   = vec![Light::Environment(Environment::new(sky,0.0,1.0))];
+		let scene      = scene.build().unwrap();
+		let mut tracer = test_tracer(1,8);
+		tracer.background_alpha = 0.0;
+
+		// the environment is seen where the background would be transparent
+		let image = tracer.render(&scene).unwrap();
+		assert!(image.alpha.iter().all(|a| *a == 1.0));
+		assert!(image.colors.iter().all(|c| *c == Color::new(0.5,0.5,0.5)));
+	}
+
+	#[test]
+	fn test_background_ignores_output() {
+		let scene = SceneBuilder::new(Vec::new(),Vec::new()).build().unwrap();
+		let mut tracer = test_tracer(1,8);
+		tracer.background = Color::new(0.25,0.5,2.0);
+
+		// the output transform only acts on the rendered image
+		tracer.output = OutputTransform{exposure:2.0,tone_map:ToneMap::Aces,srgb:false};
+		let image = tracer.render(&scene).unwrap();
+		assert!(image.colors.iter().all(|c| *c == Color::new(0.25,0.5,2.0)));
+	}
+
+	#[test]
+	fn test_path_render_independent_of_thread_count() {
+		let mesh = vec![
+			[Vec3::new(-2.0,-2.0,0.0),Vec3::new(2.0,-2.0,0.0),Vec3::new(0.0,2.0,-1.0)],
+			[Vec3::new(-3.0,-1.0,1.0),Vec3::new(1.0,3.0,1.0),Vec3::new(3.0,-3.0,2.0)],
+		];
+		let mut scene = SceneBuilder::new(mesh,Vec::new());
+		scene.lights.push(Light::Directional{direction:Vec3::new(-5.0,-5.0,5.0),color:Color::white(),intensity:1.0});
+		let scene = scene.build().unwrap();
+
+		let path_tracer = |threads:usize,tile_size:usize| {
+			let mut tracer = test_tracer(threads,tile_size);
+			tracer.integrator = Integrator::Path;
+			tracer.samples    = 4;
+			tracer
+		};
+		let single = path_tracer(1,7).render(&scene).unwrap();
+		let multi  = path_tracer(4,5).render(&scene).unwrap();
+
+		assert_eq!(single.colors,multi.colors);
+	}
+
+	#[test]
+	fn test_far_surfaces_are_visible() {
+		// the triangle is 100 times the length of the camera front away
+		let mesh  = vec![[Vec3::new(-100.0,-100.0,100.0),Vec3::new(0.0,100.0,100.0),Vec3::new(100.0,-100.0,100.0)]];
+		let scene = SceneBuilder::new(mesh,Vec::new()).build().unwrap();
+		let cam   = Camera::new(&Vec3::null(),&Vec3::new(0.0,0.0,1.0),&Vec3::new(0.0,1.0,0.0),70.0);
+
+		let mut tracer = Tracer::new(cam,3,3);
+		tracer.background = Color::new(0.0,0.0,1.0);
+		tracer.samples    = 1;
+		assert_eq!(Pixel::black(),tracer.render(&scene).unwrap().to_pixels(&tracer.output)[4]);
+
+		// beyond the far plane it is gone
+		tracer.cam.far = 50.0;
+		assert_eq!(Pixel::blue(),tracer.render(&scene).unwrap().to_pixels(&tracer.output)[4]);
+	}
+
+	#[test]
+	fn test_shading_normal() {
+		let mesh       = vec![[Vec3::new(0.0,0.0,0.0),Vec3::new(1.0,0.0,0.0),Vec3::new(0.0,1.0,0.0)]];
+		let mut smooth = SceneBuilder::new(mesh.clone(),Vec::new());
+		let scene      = SceneBuilder::new(mesh,Vec::new()).build().unwrap();
+		let facing     = Vec3::new(0.0,0.0,-1.0);
+		let tracer     = test_tracer(1,8);
+		// the hits carry the barycentric coordinates of the point
+		let hit        = |scene:&Scene,x:f64,y:f64| tracer.closest(scene,&Ray::new(&Vec3::new(x,y,-1.0),&Vec3::new(0.0,0.0,1.0))).unwrap();
+
+		// flat triangles use the geometric normal
+		assert!(Tracer::shading_normal(&scene,&hit(&scene,0.5,0.5),&facing).sub(&facing).norm() == 0.0);
+
+		// halfway between the last two corners, turned to the facing side
+		smooth.normals[0] = Some([Vec3::new(0.0,0.0,1.0),Vec3::new(1.0,0.0,1.0),Vec3::new(-1.0,0.0,1.0)]);
+		let scene  = smooth.build().unwrap();
+		let center = hit(&scene,0.5,0.5);
+		assert!((center.uv.0-0.5).abs() < 1e-12 && (center.uv.1-0.5).abs() < 1e-12);
+		let normal = Tracer::shading_normal(&scene,&center,&facing);
+		assert!(normal.sub(&facing).norm() < 1e-12);
+		let normal = Tracer::shading_normal(&scene,&hit(&scene,0.5,0.0),&facing);
+		assert!(normal.sub(&Vec3::new(-1.0,0.0,-2.0).normalize()).norm() < 1e-12);
+	}
+
+	#[test]
+	fn test_shadow_rays_leave_the_surface() {
+		use crate::engine::bsdf::Dielectric;
+		use crate::engine::microfacet::Ggx;
+
+		let mesh        = vec![[Vec3::new(-20.0,-20.0,0.0),Vec3::new(20.0,-20.0,0.0),Vec3::new(0.0,20.0,0.0)]];
+		let mut builder = SceneBuilder::new(mesh,Vec::new());
+		builder.lights.push(Light::Point{position:Vec3::new(-10.0,0.0,0.5),color:Color::white(),intensity:100.0});
+		let scene       = builder.build().unwrap();
+
+		// a rough glass whose shading normal leans away from the light, which
+		// is still above the triangle : the shadow ray must not start below it
+		let frame   = Frame::new(&Vec3::new(1.0,0.0,1.0).normalize());
+		let wo      = frame.to_local(&Vec3::new(1.0,0.0,0.1).normalize());
+		let shading = Shading{
+			point:Vec3::null(),
+			facing:Vec3::new(0.0,0.0,1.0),
+			frame,
+			wo,
+			bsdf:Scattering::Dielectric(Dielectric{ggx:Ggx::from_roughness(0.5),eta:1.5})
+		};
+		let tracer  = test_tracer(1,8);
+		assert!(!tracer.compute_color(&scene,&shading,&mut Rng::new(0,0)).is_black());
+	}
+
+	#[test]
+	fn test_surfaces() {
+		let mesh  = vec![[Vec3::new(-1.0,-1.0,0.0),Vec3::new(0.0,1.0,0.0),Vec3::new(1.0,-1.0,0.0)]];
+		let sphere:Box<dyn Shape> = Box::new(Sphere{center:Vec3::new(4.0,0.0,0.0),radius:1.0});
+		let scene = SceneBuilder::new(mesh,vec![sphere]).build().unwrap();
+
+		let mut tracer = test_tracer(2,8);
+		tracer.samples = 4;
+		let plain      = tracer.render(&scene).unwrap();
+		assert!(plain.surfaces.is_none());
+
+		// the surfaces come from the first samples, the image is unchanged
+		tracer.aovs  = true;
+		let image    = tracer.render(&scene).unwrap();
+		assert_eq!(plain.colors,image.colors);
+		let surfaces = image.surfaces.unwrap();
+		assert!(surfaces[0].is_none());
+
+		// the center of the image sees the triangle on the sensor plane
+		let center = surfaces[11*37+18].as_ref().unwrap();
+		assert!((center.depth-10.0).abs() < 1e-9);
+		assert!(center.normal.sub(&Vec3::new(0.0,0.0,-1.0)).norm() < 1e-9);
+		assert_eq!(0,center.primitive);
+		assert_eq!(0,center.object);
+
+		// the sphere is counted after the triangle
+		let spheres = surfaces.iter().flatten().filter(|s| s.object == 1).collect::<Vec<_>>();
+		assert!(!spheres.is_empty());
+		assert!(spheres.iter().all(|s| s.primitive == 1));
+	}
+
 }
 
 
@@ -0,0 +1,302 @@
+use crate::math::vector3::Vec3;
+use crate::engine::bvh::Distance;
+
+use std::collections::HashMap;
+
+// Ray-triangle intersections giving the ray parameter and the barycentric
+// coordinates of the hit in one pass : the point is
+// triangle[0] + u*(triangle[1]-triangle[0]) + v*(triangle[2]-triangle[0]).
+// Both faces are hit and t is strictly positive.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Hit
+{
+	pub t:f64,
+	pub u:f64,
+	pub v:f64
+}
+
+impl Distance for Hit
+{
+	fn t(&self) -> f64 { self.t }
+}
+
+// which of the two tests below a scene intersects its triangles with
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum TriangleTest
+{
+	#[default]
+	Watertight,
+	MollerTrumbore
+}
+
+impl TriangleTest {
+	pub fn intersect(&self,triangle:&[Vec3;3],origin:&Vec3,ray:&Vec3) -> Option<Hit>
+	{
+		match self {
+			TriangleTest::Watertight     => watertight(triangle,origin,ray),
+			TriangleTest::MollerTrumbore => moller_trumbore(triangle,origin,ray)
+		}
+	}
+}
+
+// Möller–Trumbore : Cramer's rule on origin + t*ray = p0 + u*e1 + v*e2.
+// Fast, but not watertight : rays through an edge shared by two triangles
+// may miss both and leave holes in a mesh.
+pub fn moller_trumbore(triangle:&[Vec3;3],origin:&Vec3,ray:&Vec3) -> Option<Hit>
+{
+	let e1  = triangle[1].sub(&triangle[0]);
+	let e2  = triangle[2].sub(&triangle[0]);
+	let p   = ray.cross(&e2);
+	let det = e1.dot(&p);
+	if det == 0.0 { return None; }
+
+	let s = origin.sub(&triangle[0]);
+	let u = s.dot(&p)/det;
+	if !(0.0..=1.0).contains(&u) { return None; }
+
+	let q = s.cross(&e1);
+	let v = ray.dot(&q)/det;
+	if v < 0.0 || u + v > 1.0 { return None; }
+
+	let t = e2.dot(&q)/det;
+	if t > 0.0 { Some(Hit{t,u,v}) } else { None }
+}
+
+fn axis(v:&Vec3,k:usize) -> f64
+{
+	match k {
+		0 => v.x,
+		1 => v.y,
+		_ => v.z
+	}
+}
+
+// Woop, Benthin and Wald's watertight test : the triangle is moved in a
+// space where the ray goes along +z from the origin, and the edge functions
+// are evaluated the same way for both triangles sharing an edge, so that a
+// ray can not go through the gap between them.
+pub fn watertight(triangle:&[Vec3;3],origin:&Vec3,ray:&Vec3) -> Option<Hit>
+{
+	// z is the largest dimension of the ray, x and y keep the winding
+	let kz = if ray.x.abs() > ray.y.abs() {
+		if ray.x.abs() > ray.z.abs() { 0 } else { 2 }
+	} else if ray.y.abs() > ray.z.abs() { 1 } else { 2 };
+	let mut kx = (kz + 1)%3;
+	let mut ky = (kx + 1)%3;
+	if axis(ray,kz) < 0.0 { std::mem::swap(&mut kx,&mut ky); }
+	if axis(ray,kz) == 0.0 { return None; }
+
+	// shear taking the ray to +z
+	let sx = axis(ray,kx)/axis(ray,kz);
+	let sy = axis(ray,ky)/axis(ray,kz);
+	let sz = 1.0/axis(ray,kz);
+
+	let a = triangle[0].sub(origin);
+	let b = triangle[1].sub(origin);
+	let c = triangle[2].sub(origin);
+	let (ax,ay) = (axis(&a,kx) - sx*axis(&a,kz),axis(&a,ky) - sy*axis(&a,kz));
+	let (bx,by) = (axis(&b,kx) - sx*axis(&b,kz),axis(&b,ky) - sy*axis(&b,kz));
+	let (cx,cy) = (axis(&c,kx) - sx*axis(&c,kz),axis(&c,ky) - sy*axis(&c,kz));
+
+	// scaled barycentric coordinates, an edge gives exactly 0 on both sides
+	let e0 = cx*by - cy*bx;
+	let e1 = ax*cy - ay*cx;
+	let e2 = bx*ay - by*ax;
+	if (e0 < 0.0 || e1 < 0.0 || e2 < 0.0) && (e0 > 0.0 || e1 > 0.0 || e2 > 0.0) { return None; }
+
+	let det = e0 + e1 + e2;
+	if det == 0.0 { return None; }
+
+	let t = (e0*axis(&a,kz) + e1*axis(&b,kz) + e2*axis(&c,kz))*sz/det;
+	if t > 0.0 { Some(Hit{t,u:e1/det,v:e2/det}) } else { None }
+}
+
+// how the point moves on the triangle with the texture coordinates given at
+// its corners, none when the coordinates do not span a plane
+pub fn uv_derivatives(triangle:&[Vec3;3],uvs:&[(f64,f64);3]) -> Option<(Vec3,Vec3)>
+{
+	let e1  = triangle[1].sub(&triangle[0]);
+	let e2  = triangle[2].sub(&triangle[0]);
+	let (du1,dv1) = (uvs[1].0 - uvs[0].0,uvs[1].1 - uvs[0].1);
+	let (du2,dv2) = (uvs[2].0 - uvs[0].0,uvs[2].1 - uvs[0].1);
+	let det = du1*dv2 - dv1*du2;
+	if det == 0.0 { return None; }
+
+	Some((e1.mul(dv2).sub(&e2.mul(dv1)).div(det),e2.mul(du1).sub(&e1.mul(du2)).div(det)))
+}
+
+// normal at (u,v) blended from the normals of the corners, the face
+// normal when opposing corner normals cancel out
+pub fn interpolate(normals:&[Vec3;3],face:&Vec3,u:f64,v:f64) -> Vec3
+{
+	let normal = normals[0].mul(1.0 - u - v)
+		.add(&normals[1].mul(u))
+		.add(&normals[2].mul(v));
+	if normal.norm() < 1e-12 { face.clone() } else { normal.normalize() }
+}
+
+// angle between the edges of a triangle at one of its corners
+fn corner_angle(triangle:&[Vec3;3],corner:usize) -> f64
+{
+	let p = &triangle[corner];
+	let a = triangle[(corner+1)%3].sub(p);
+	let b = triangle[(corner+2)%3].sub(p);
+	let (na,nb) = (a.norm(),b.norm());
+	if na == 0.0 || nb == 0.0 { return 0.0; }
+	(a.dot(&b)/(na*nb)).clamp(-1.0,1.0).acos()
+}
+
+// smooth normals of every corner of the mesh. Corners at the same position
+// share the normals of the faces around them weighted by the angle of each
+// face at that corner, faces bending more than crease_angle (in degrees) from
+// the one of the corner are left out so that sharp edges stay sharp.
+pub fn vertex_normals(mesh:&[[Vec3;3]],crease_angle:f64) -> Vec<[Vec3;3]>
+{
+	let face_normals:Vec<Vec3> = mesh.iter().map(|t| {
+		let n = t[1].sub(&t[0]).cross(&t[2].sub(&t[0]));
+		if n.norm() > 0.0 { n.normalize() } else { n }
+	}).collect();
+
+	// corners sharing a position, found through the bits of the coordinates
+	let key = |p:&Vec3| (p.x.to_bits(),p.y.to_bits(),p.z.to_bits());
+	let mut shared = HashMap::<(u64,u64,u64),Vec<(usize,usize)>>::new();
+	for (face,triangle) in mesh.iter().enumerate()
+	{
+		for (corner,p) in triangle.iter().enumerate()
+		{
+			shared.entry(key(p)).or_default().push((face,corner));
+		}
+	}
+
+	let cos_crease = crease_angle.to_radians().cos();
+	mesh.iter().enumerate().map(|(face,triangle)| {
+		let own = &face_normals[face];
+		let mut normals = [own.clone(),own.clone(),own.clone()];
+		for (corner,p) in triangle.iter().enumerate()
+		{
+			let mut sum = Vec3::null();
+			for (other,other_corner) in &shared[&key(p)]
+			{
+				let n = &face_normals[*other];
+				if n.dot(own) < cos_crease { continue; }
+				sum = sum.add(&n.mul(corner_angle(&mesh[*other],*other_corner)));
+			}
+			if sum.norm() > 0.0 { normals[corner] = sum.normalize(); }
+		}
+		normals
+	}).collect()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn assert_near(expected:f64,value:f64)
+	{
+		assert!((expected-value).abs() < 1e-12,"{} != {}",expected,value);
+	}
+
+	#[test]
+	fn test_miss() {
+		let triangle = [Vec3::new(-1.0,-1.0,2.0),Vec3::new(1.0,-1.0,2.0),Vec3::new(0.0,1.0,2.0)];
+		let origin   = Vec3::null();
+		let rays     = [
+			// outside, behind the origin and parallel to the triangle
+			Vec3::new(2.0,0.0,1.0),
+			Vec3::new(0.0,0.0,-1.0),
+			Vec3::new(1.0,0.0,0.0)
+		];
+		for ray in &rays
+		{
+			assert_eq!(None,moller_trumbore(&triangle,&origin,ray));
+			assert_eq!(None,watertight(&triangle,&origin,ray));
+		}
+	}
+
+	#[test]
+	fn test_both_faces() {
+		let triangle = [Vec3::new(-1.0,-1.0,2.0),Vec3::new(0.0,1.0,2.0),Vec3::new(1.0,-1.0,2.0)];
+		let front    = watertight(&triangle,&Vec3::null(),&Vec3::new(0.0,0.0,1.0)).unwrap();
+		let back     = watertight(&triangle,&Vec3::new(0.0,0.0,4.0),&Vec3::new(0.0,0.0,-1.0)).unwrap();
+
+		assert_near(2.0,front.t);
+		assert_near(2.0,back.t);
+		assert_near(front.u,back.u);
+		assert_near(front.v,back.v);
+	}
+
+	#[test]
+	fn test_shared_edge_is_watertight() {
+		// a quad split along its diagonal, rays crossing the diagonal must
+		// hit at least one of the two triangles
+		let p    = [Vec3::new(-1.0,-1.0,3.0),Vec3::new(1.0,-1.0,3.0),Vec3::new(1.0,1.0,3.0),Vec3::new(-1.0,1.0,3.0)];
+		let left = [p[0].clone(),p[2].clone(),p[3].clone()];
+		let right= [p[0].clone(),p[1].clone(),p[2].clone()];
+		let origin = Vec3::new(0.1,0.3,-0.7);
+
+		for k in 0..1000
+		{
+			let s   = -0.9 + 1.8*(k as f64)/1000.0;
+			let ray = Vec3::new(s,s,3.0).sub(&origin);
+			assert!(watertight(&left,&origin,&ray).is_some() || watertight(&right,&origin,&ray).is_some(),"leak at {}",s);
+		}
+	}
+
+	#[test]
+	fn test_barycentric() {
+		let triangle = [Vec3::new(7.0,0.0,0.0),Vec3::new(7.0,5.0,0.0),Vec3::new(7.0,0.0,3.0)];
+		let hit      = watertight(&triangle,&Vec3::new(0.0,3.0,1.0),&Vec3::new(1.0,0.0,0.0)).unwrap();
+
+		// the point of the hit is (7,3,1)
+		assert_near(7.0,hit.t);
+		assert_near(3.0/5.0,hit.u);
+		assert_near(1.0/3.0,hit.v);
+	}
+
+	#[test]
+	fn test_uv_derivatives() {
+		// a triangle of the plane z=0 with a texture turned by 90 degrees and
+		// stretched twice along v
+		let triangle = [Vec3::new(1.0,1.0,0.0),Vec3::new(1.0,2.0,0.0),Vec3::new(0.0,1.0,0.0)];
+		let (dpdu,dpdv) = uv_derivatives(&triangle,&[(0.0,0.0),(1.0,0.0),(0.0,0.5)]).unwrap();
+
+		assert!(dpdu.sub(&Vec3::new(0.0,1.0,0.0)).norm() < 1e-12);
+		assert!(dpdv.sub(&Vec3::new(-2.0,0.0,0.0)).norm() < 1e-12);
+		assert!(uv_derivatives(&triangle,&[(0.0,0.0),(1.0,1.0),(2.0,2.0)]).is_none());
+	}
+
+	#[test]
+	fn test_vertex_normals() {
+		// two faces of a roof meeting at 90 degrees along the x axis
+		let mesh = vec![
+			[Vec3::new(0.0,0.0,0.0),Vec3::new(1.0,0.0,0.0),Vec3::new(0.0,1.0,-1.0)],
+			[Vec3::new(0.0,0.0,0.0),Vec3::new(0.0,1.0,1.0),Vec3::new(1.0,0.0,0.0)]
+		];
+
+		// shared corners get the average, the others keep the face normal
+		let smooth = vertex_normals(&mesh,100.0);
+		let up     = Vec3::new(0.0,1.0,0.0);
+		assert!(smooth[0][0].sub(&up).norm() < 1e-12);
+		assert!(smooth[1][2].sub(&up).norm() < 1e-12);
+		assert!(smooth[0][2].sub(&Vec3::new(0.0,1.0,1.0).normalize()).norm() < 1e-12);
+
+		// a crease below the angle between the faces keeps them flat
+		let sharp = vertex_normals(&mesh,60.0);
+		for corner in &sharp[0]
+		{
+			assert!(corner.sub(&Vec3::new(0.0,1.0,1.0).normalize()).norm() < 1e-12);
+		}
+		assert!(interpolate(&smooth[0],&up,0.25,0.25).sub(&smooth[0][0]).norm() > 0.0);
+	}
+
+	#[test]
+	fn test_interpolate_opposing_normals() {
+		let up      = Vec3::new(0.0,0.0,1.0);
+		let normals = [Vec3::new(1.0,0.0,0.0),Vec3::new(-1.0,0.0,0.0),Vec3::new(0.0,1.0,0.0)];
+
+		// halfway between the first two corners the normals cancel out
+		assert!(interpolate(&normals,&up,0.5,0.0).sub(&up).norm() < 1e-12);
+		let normal = interpolate(&normals,&up,0.25,0.0);
+		assert!(normal.sub(&Vec3::new(1.0,0.0,0.0)).norm() < 1e-12);
+	}
+}
@@ -0,0 +1,50 @@
+//! Ray tracer rendering triangle meshes and analytic shapes.
+//!
+//! A scene can be read from a YAML file with [`loader`] or built directly :
+//!
+//! ```
+//! use ray_tracer::{Vec3,Camera,SceneBuilder,Shape,Sphere,Material,Color,Light,Tracer};
+//!
+//! let triangle = [Vec3::new(-1.0,0.0,0.0),Vec3::new(1.0,0.0,0.0),Vec3::new(0.0,1.0,0.0)];
+//! let sphere:Box<dyn Shape> = Box::new(Sphere{center:Vec3::new(0.0,0.0,1.0),radius:0.5});
+//!
+//! let mut builder = SceneBuilder::new(vec![triangle],vec![sphere]);
+//! builder.materials.push(Material{diffuse:Color::new(0.9,0.1,0.1),..Default::default()});
+//! builder.object_materials[0] = 1;
+//! builder.lights.push(Light::Point{position:Vec3::new(0.0,3.0,-3.0),color:Color::white(),intensity:10.0});
+//! let scene = builder.build().unwrap();
+//!
+//! let cam    = Camera::new(&Vec3::new(0.0,0.5,-3.0),&Vec3::new(0.0,0.0,3.0),&Vec3::new(0.0,1.0,0.0),60.0);
+//! let tracer = Tracer::new(cam,64,48);
+//! let image  = tracer.render(&scene).unwrap();
+//!
+//! let pixels = image.to_pixels(&tracer.output);
+//!
+//! assert_eq!(64*48,pixels.len());
+//! ```
+
+mod math;
+mod engine;
+pub mod loader;
+pub mod writer;
+
+pub use math::vector3::Vec3;
+pub use math::matrix3::Mat3;
+pub use math::matrix4::Mat4;
+pub use math::aabb::Aabb;
+pub use engine::camera::{Camera,Projection,FovAxis};
+pub use engine::scene::{Scene,SceneBuilder,SceneError,Primitive};
+pub use engine::triangle::TriangleTest;
+pub use engine::shape::{Shape,Sphere,Plane,Disk,Rectangle,Cuboid,Cylinder,Cone};
+pub use engine::material::{Material,Model};
+pub use engine::bsdf::Metal;
+pub use engine::texture::{Texture,Image,Pattern,Space,Filtering,Wrap};
+pub use engine::color::Color;
+pub use engine::light::Light;
+pub use engine::environment::Environment;
+pub use engine::tracer::{Tracer,Pixel,RenderError};
+pub use engine::integrator::Integrator;
+pub use engine::filter::Filter;
+pub use engine::tonemap::{OutputTransform,ToneMap};
+pub use engine::framebuffer::Framebuffer;
+pub use engine::aov::{Pass,Surface};
@@ -1,23 +1,737 @@
+mod error;
+mod obj;
+mod hdr;
+mod pfm;
+
+pub use error::LoadError;
+
 use super::engine::tracer::Tracer;
 use super::engine::camera::Camera as EngineCam;
-use super::engine::tracer::Pixel;
+use super::engine::camera::{Projection as EngineProjection, FovAxis as EngineFovAxis};
+use super::engine::scene::Scene as EngineScene;
+use super::engine::scene::SceneBuilder;
+use super::engine::shape::{Shape,Sphere,Plane,Disk,Rectangle,Cuboid,Cylinder,Cone};
+use super::engine::color::Color;
+use super::engine::material::{Material as EngineMaterial, Model as EngineModel};
+use super::engine::bsdf::Metal as EngineMetal;
+use super::engine::light::Light as EngineLight;
+use super::engine::integrator::Integrator as EngineIntegrator;
+use super::engine::environment::Environment as EngineEnvironment;
+use super::engine::filter::Filter as EngineFilter;
+use super::engine::tonemap::{OutputTransform, ToneMap as EngineToneMap, srgb_to_linear};
+use super::engine::aov::Pass as EnginePass;
+use super::engine::triangle;
+use super::engine::texture::{Texture as EngineTexture, Image, Filtering as EngineFiltering, Wrap as EngineWrap};
+use super::engine::texture::{Pattern as EnginePattern, Space as EngineSpace};
+use super::math::noise::Octaves as EngineOctaves;
+use super::writer::Precision as WriterPrecision;
 use super::math::vector3::Vec3;
 
+use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::Read;
+use std::path::{Path,PathBuf};
 use serde::{Serialize, Deserialize};
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Vertex (f64,f64,f64);
 
+impl Vertex
+{
+    fn to_vec3(&self) -> Vec3 { Vec3::new(self.0,self.1,self.2) }
+}
+
+// a face is three 1-based vertex indices, optionally followed by a material name
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Face
+{
+    Plain(u32,u32,u32),
+    WithMaterial(u32,u32,u32,String)
+}
+
+impl Face
+{
+    fn indices(&self) -> (u32,u32,u32)
+    {
+        match self {
+            Face::Plain(a,b,c)          => (*a,*b,*c),
+            Face::WithMaterial(a,b,c,_) => (*a,*b,*c)
+        }
+    }
+
+    fn material(&self) -> Option<&String>
+    {
+        match self {
+            Face::Plain(..)             => None,
+            Face::WithMaterial(_,_,_,m) => Some(m)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Uv (f64,f64);
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rgb (f64,f64,f64);
+
+impl Rgb
+{
+    fn to_color(&self) -> Color { Color::new(self.0,self.1,self.2) }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filtering
+{
+    Nearest,
+    #[default]
+    Bilinear
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Wrap
+{
+    #[default]
+    Repeat,
+    Clamp,
+    Mirror
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Space
+{
+    #[default]
+    World,
+    Object
+}
+
+fn default_scale() -> f64 { 1.0 }
+fn default_colors() -> [Rgb;2] { [Rgb(0.0,0.0,0.0),Rgb(1.0,1.0,1.0)] }
+
+// placement and colors of the procedural textures, unknown keys being
+// rejected rather than ignored
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pattern
+{
+    #[serde(default)]
+    space:Space,
+    #[serde(default = "default_scale")]
+    scale:f64,
+    #[serde(default = "default_colors")]
+    colors:[Rgb;2]
+}
+
+fn default_octaves() -> u32 { 6 }
+fn default_lacunarity() -> f64 { 2.0 }
+fn default_gain() -> f64 { 0.5 }
+
+// a pattern summing octaves of noise
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Fractal
+{
+    #[serde(default)]
+    space:Space,
+    #[serde(default = "default_scale")]
+    scale:f64,
+    #[serde(default = "default_colors")]
+    colors:[Rgb;2],
+    #[serde(default = "default_octaves")]
+    octaves:u32,
+    #[serde(default = "default_lacunarity")]
+    lacunarity:f64,
+    #[serde(default = "default_gain")]
+    gain:f64
+}
+
+fn default_marble_strength() -> f64 { 4.0 }
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Marble
+{
+    #[serde(default)]
+    space:Space,
+    #[serde(default = "default_scale")]
+    scale:f64,
+    #[serde(default = "default_colors")]
+    colors:[Rgb;2],
+    #[serde(default = "default_octaves")]
+    octaves:u32,
+    #[serde(default = "default_lacunarity")]
+    lacunarity:f64,
+    #[serde(default = "default_gain")]
+    gain:f64,
+    #[serde(default = "default_marble_strength")]
+    strength:f64
+}
+
+fn default_rings() -> f64 { 8.0 }
+fn default_wood_strength() -> f64 { 0.2 }
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Wood
+{
+    #[serde(default)]
+    space:Space,
+    #[serde(default = "default_scale")]
+    scale:f64,
+    #[serde(default = "default_colors")]
+    colors:[Rgb;2],
+    #[serde(default = "default_octaves")]
+    octaves:u32,
+    #[serde(default = "default_lacunarity")]
+    lacunarity:f64,
+    #[serde(default = "default_gain")]
+    gain:f64,
+    #[serde(default = "default_rings")]
+    rings:f64,
+    #[serde(default = "default_wood_strength")]
+    strength:f64
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Texture
+{
+    // png file relative to the scene file
+    Image {
+        path:String,
+        #[serde(default)]
+        filter:Filtering,
+        #[serde(default)]
+        wrap:Wrap
+    },
+    Checker(Pattern),
+    Checker3d(Pattern),
+    Perlin(Pattern),
+    Simplex(Pattern),
+    Fbm(Fractal),
+    Turbulence(Fractal),
+    Marble(Marble),
+    Wood(Wood),
+    Worley(Pattern)
+}
+
+fn placement(space:&Space,scale:f64,colors:&[Rgb;2],pattern:EnginePattern) -> EngineTexture
+{
+    EngineTexture::Pattern{
+        pattern,
+        space:match space {
+            Space::World  => EngineSpace::World,
+            Space::Object => EngineSpace::Object
+        },
+        scale,
+        colors:[colors[0].to_color(),colors[1].to_color()]
+    }
+}
+
+impl Texture
+{
+    // paths are relative to directory, images are decoded from srgb when
+    // they hold colors
+    fn to_engine(&self,directory:&Path,srgb:bool) -> Result<EngineTexture,LoadError>
+    {
+        let pattern = |p:&Pattern,pattern| placement(&p.space,p.scale,&p.colors,pattern);
+        let fractal = |f:&Fractal,pattern:fn(EngineOctaves) -> EnginePattern| placement(&f.space,f.scale,&f.colors,
+            pattern(EngineOctaves{count:f.octaves,lacunarity:f.lacunarity,gain:f.gain}));
+        match self {
+            Texture::Image{path,filter,wrap} => Ok(EngineTexture::Image{
+                image:read_image(&directory.join(path),srgb)?,
+                filtering:match filter {
+                    Filtering::Nearest  => EngineFiltering::Nearest,
+                    Filtering::Bilinear => EngineFiltering::Bilinear
+                },
+                wrap:match wrap {
+                    Wrap::Repeat => EngineWrap::Repeat,
+                    Wrap::Clamp  => EngineWrap::Clamp,
+                    Wrap::Mirror => EngineWrap::Mirror
+                }
+            }),
+            Texture::Checker(p)    => Ok(pattern(p,EnginePattern::Checker)),
+            Texture::Checker3d(p)  => Ok(pattern(p,EnginePattern::Checker3d)),
+            Texture::Perlin(p)     => Ok(pattern(p,EnginePattern::Perlin)),
+            Texture::Simplex(p)    => Ok(pattern(p,EnginePattern::Simplex)),
+            Texture::Fbm(f)        => Ok(fractal(f,EnginePattern::Fbm)),
+            Texture::Turbulence(f) => Ok(fractal(f,EnginePattern::Turbulence)),
+            Texture::Marble(m)     => Ok(placement(&m.space,m.scale,&m.colors,EnginePattern::Marble{
+                octaves:EngineOctaves{count:m.octaves,lacunarity:m.lacunarity,gain:m.gain},
+                strength:m.strength
+            })),
+            Texture::Wood(w)       => Ok(placement(&w.space,w.scale,&w.colors,EnginePattern::Wood{
+                octaves:EngineOctaves{count:w.octaves,lacunarity:w.lacunarity,gain:w.gain},
+                rings:w.rings,
+                strength:w.strength
+            })),
+            Texture::Worley(p)     => Ok(pattern(p,EnginePattern::Worley))
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Metal
+{
+    Gold,
+    Copper,
+    Aluminium
+}
+
+fn default_principled_roughness() -> f64 { 0.5 }
+fn default_principled_specular() -> f64 { 0.5 }
+fn default_clearcoat_gloss() -> f64 { 1.0 }
+
+// scattering model of a material, the diffuse color being its base color
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Bsdf
+{
+    Lambert,
+    // a measured metal, or its complex index of refraction eta + i*k
+    Conductor {
+        metal:Option<Metal>,
+        eta:Option<Rgb>,
+        k:Option<Rgb>,
+        #[serde(default)]
+        roughness:f64
+    },
+    // glass of the index of refraction of the material
+    Dielectric {
+        #[serde(default)]
+        roughness:f64
+    },
+    Principled {
+        #[serde(default)]
+        metallic:f64,
+        #[serde(default = "default_principled_roughness")]
+        roughness:f64,
+        #[serde(default = "default_principled_specular")]
+        specular:f64,
+        #[serde(default)]
+        sheen:f64,
+        #[serde(default)]
+        clearcoat:f64,
+        #[serde(default = "default_clearcoat_gloss")]
+        clearcoat_gloss:f64
+    }
+}
+
+impl Bsdf
+{
+    fn to_engine(&self) -> Result<EngineModel,LoadError>
+    {
+        match self {
+            Bsdf::Lambert => Ok(EngineModel::Lambert),
+            Bsdf::Conductor{metal,eta,k,roughness} => {
+                let (eta,k) = match (metal,eta,k) {
+                    (None,Some(eta),Some(k)) => (eta.to_color(),k.to_color()),
+                    (Some(metal),None,None) => match metal {
+                        Metal::Gold      => EngineMetal::Gold.ior(),
+                        Metal::Copper    => EngineMetal::Copper.ior(),
+                        Metal::Aluminium => EngineMetal::Aluminium.ior()
+                    },
+                    _ => return Err(LoadError::Semantic(String::from("conductor needs either a metal or both eta and k")))
+                };
+                Ok(EngineModel::Conductor{eta,k,roughness:*roughness})
+            },
+            Bsdf::Dielectric{roughness} => Ok(EngineModel::Dielectric{roughness:*roughness}),
+            Bsdf::Principled{metallic,roughness,specular,sheen,clearcoat,clearcoat_gloss} => Ok(EngineModel::Principled{
+                metallic:*metallic,
+                roughness:*roughness,
+                specular:*specular,
+                sheen:*sheen,
+                clearcoat:*clearcoat,
+                clearcoat_gloss:*clearcoat_gloss
+            })
+        }
+    }
+}
+
+fn default_diffuse() -> Rgb { Rgb(1.0,1.0,1.0) }
+fn default_specular() -> Rgb { Rgb(0.0,0.0,0.0) }
+fn default_shininess() -> f64 { 32.0 }
+fn default_ior() -> f64 { 1.5 }
+fn default_opacity() -> f64 { 1.0 }
+fn default_emission() -> Rgb { Rgb(0.0,0.0,0.0) }
+fn default_normal_strength() -> f64 { 1.0 }
+fn default_bump_height() -> f64 { 0.01 }
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Material
+{
+    #[serde(default = "default_diffuse")]
+    diffuse: Rgb,
+    #[serde(default = "default_specular")]
+    specular: Rgb,
+    #[serde(default = "default_shininess")]
+    shininess: f64,
+    #[serde(default)]
+    reflectivity: f64,
+    #[serde(default)]
+    transparency: f64,
+    #[serde(default = "default_ior")]
+    ior: f64,
+    // coverage, MTL dissolve : the rest of the light goes through unchanged
+    #[serde(default = "default_opacity")]
+    opacity: f64,
+    // diffuse color read from a texture
+    texture: Option<Texture>,
+    // images of these two are data, not srgb colors
+    normal_map: Option<Texture>,
+    #[serde(default = "default_normal_strength")]
+    normal_strength: f64,
+    bump_map: Option<Texture>,
+    #[serde(default = "default_bump_height")]
+    bump_height: f64,
+    // radiance of glowing surfaces, which become area lights
+    #[serde(default = "default_emission")]
+    emission: Rgb,
+    // phong shading with the fields above when missing
+    bsdf: Option<Bsdf>
+}
+
+impl Default for Material
+{
+    fn default() -> Material
+    {
+        Material{
+            diffuse:default_diffuse(),
+            specular:default_specular(),
+            shininess:default_shininess(),
+            reflectivity:0.0,
+            transparency:0.0,
+            ior:default_ior(),
+            opacity:default_opacity(),
+            texture:None,
+            normal_map:None,
+            normal_strength:default_normal_strength(),
+            bump_map:None,
+            bump_height:default_bump_height(),
+            emission:default_emission(),
+            bsdf:None
+        }
+    }
+}
+
+impl Material
+{
+    // textures are looked up from directory
+    fn to_engine(&self,directory:&Path) -> Result<EngineMaterial,LoadError>
+    {
+        between("opacity",self.opacity,0.0,1.0)?;
+        between("reflectivity",self.reflectivity,0.0,1.0)?;
+        between("transparency",self.transparency,0.0,1.0)?;
+        between("shininess",self.shininess,0.0,f64::INFINITY)?;
+        // refraction divides by the index
+        if !(self.ior > 0.0 && self.ior.is_finite())
+        {
+            return Err(LoadError::Semantic(format!("ior {} is not between 0 and inf",self.ior)));
+        }
+        // what is reflected and transmitted is taken from the same light
+        if self.reflectivity + self.transparency > 1.0
+        {
+            return Err(LoadError::Semantic(format!("reflectivity {} and transparency {} sum above 1",self.reflectivity,self.transparency)));
+        }
+        Ok(EngineMaterial{
+            diffuse:self.diffuse.to_color(),
+            specular:self.specular.to_color(),
+            shininess:self.shininess,
+            reflectivity:self.reflectivity,
+            transparency:self.transparency,
+            ior:self.ior,
+            opacity:self.opacity,
+            texture:match &self.texture {
+                Some(texture) => Some(texture.to_engine(directory,true)?),
+                None          => None
+            },
+            normal_map:match &self.normal_map {
+                Some(texture) => Some(texture.to_engine(directory,false)?),
+                None          => None
+            },
+            normal_strength:self.normal_strength,
+            bump_map:match &self.bump_map {
+                Some(texture) => Some(texture.to_engine(directory,false)?),
+                None          => None
+            },
+            bump_height:self.bump_height,
+            emission:self.emission.to_color(),
+            model:match &self.bsdf {
+                Some(bsdf) => bsdf.to_engine()?,
+                None       => EngineModel::Phong
+            }
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Geometry
+{
+    Sphere   { center:Vertex, radius:f64 },
+    Plane    { point:Vertex, normal:Vertex },
+    Disk     { center:Vertex, normal:Vertex, radius:f64 },
+    Rectangle { corner:Vertex, edge1:Vertex, edge2:Vertex },
+    #[serde(rename = "box")]
+    Cuboid   { min:Vertex, max:Vertex },
+    Cylinder { base:Vertex, top:Vertex, radius:f64 },
+    Cone     { base:Vertex, apex:Vertex, radius:f64 }
+}
+
+// radius of a shape, which has to be positive for it to be seen
+fn radius(shape:&str,radius:f64) -> Result<f64,LoadError>
+{
+    if radius > 0.0 { Ok(radius) }
+    else { Err(LoadError::Semantic(format!("{} radius {} is not positive",shape,radius))) }
+}
+
+// finite value of a parameter inside [min,max]
+fn between(name:&str,value:f64,min:f64,max:f64) -> Result<f64,LoadError>
+{
+    if value.is_finite() && (min..=max).contains(&value) { Ok(value) }
+    else { Err(LoadError::Semantic(format!("{} {} is not between {} and {}",name,value,min,max))) }
+}
+
+// normal or direction, which has to be non null for it to be normalized
+fn non_null(name:&str,vertex:&Vertex) -> Result<Vec3,LoadError>
+{
+    let direction = vertex.to_vec3();
+    if direction.norm() > 0.0 { Ok(direction) }
+    else { Err(LoadError::Semantic(format!("{} is null",name))) }
+}
+
+// axis of a cylinder or cone, from a base to a distinct end
+fn axis(shape:&str,base:&Vertex,end:&Vertex,name:&str) -> Result<(Vec3,Vec3),LoadError>
+{
+    let (base,end) = (base.to_vec3(),end.to_vec3());
+    if end.sub(&base).norm() > 0.0 { Ok((base,end)) }
+    else { Err(LoadError::Semantic(format!("{} base and {} are the same point",shape,name))) }
+}
+
+// box whose min corner is below its max corner on every axis
+fn cuboid(min:&Vertex,max:&Vertex) -> Result<Cuboid,LoadError>
+{
+    if min.0 <= max.0 && min.1 <= max.1 && min.2 <= max.2 { Ok(Cuboid{min:min.to_vec3(),max:max.to_vec3()}) }
+    else { Err(LoadError::Semantic(String::from("box min is above its max"))) }
+}
+
+// rectangle spanned by two edges that are not parallel
+fn rectangle(corner:&Vertex,edge1:&Vertex,edge2:&Vertex) -> Result<Rectangle,LoadError>
+{
+    let (edge1,edge2) = (edge1.to_vec3(),edge2.to_vec3());
+    if edge1.cross(&edge2).norm() > 0.0 { Ok(Rectangle{corner:corner.to_vec3(),edge1,edge2}) }
+    else { Err(LoadError::Semantic(String::from("rectangle edges are null or parallel"))) }
+}
+
+impl Geometry
+{
+    fn to_shape(&self) -> Result<Box<dyn Shape>,LoadError>
+    {
+        Ok(match self {
+            Geometry::Sphere{center,radius:r}      => Box::new(Sphere{center:center.to_vec3(),radius:radius("sphere",*r)?}),
+            Geometry::Plane{point,normal}          => Box::new(Plane{point:point.to_vec3(),normal:non_null("plane normal",normal)?}),
+            Geometry::Disk{center,normal,radius:r} => Box::new(Disk{center:center.to_vec3(),normal:non_null("disk normal",normal)?,radius:radius("disk",*r)?}),
+            Geometry::Rectangle{corner,edge1,edge2} => Box::new(rectangle(corner,edge1,edge2)?),
+            Geometry::Cuboid{min,max}              => Box::new(cuboid(min,max)?),
+            Geometry::Cylinder{base,top,radius:r}  => {
+                let (base,top) = axis("cylinder",base,top,"top")?;
+                Box::new(Cylinder{base,top,radius:radius("cylinder",*r)?})
+            },
+            Geometry::Cone{base,apex,radius:r}     => {
+                let (base,apex) = axis("cone",base,apex,"apex")?;
+                Box::new(Cone{base,apex,radius:radius("cone",*r)?})
+            }
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Object
+{
+    #[serde(flatten)]
+    geometry: Geometry,
+    material: Option<String>
+}
+
+// serde does not reject unknown keys next to a flattened field : the material
+// is taken out and the rest has to be a geometry
+impl<'de> Deserialize<'de> for Object
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer:D) -> Result<Object,D::Error>
+    {
+        use serde::de::Error;
+        let mut keys = serde_yaml::Mapping::deserialize(deserializer)?;
+        let material = match keys.remove(&serde_yaml::Value::from("material")) {
+            Some(material) => Some(String::deserialize(material).map_err(D::Error::custom)?),
+            None           => None
+        };
+        let geometry = Geometry::deserialize(serde_yaml::Value::Mapping(keys)).map_err(D::Error::custom)?;
+        Ok(Object{geometry,material})
+    }
+}
+
+fn default_light_color() -> Rgb { Rgb(1.0,1.0,1.0) }
+fn default_intensity() -> f64 { 1.0 }
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase", deny_unknown_fields)]
+pub enum Light
+{
+    Directional {
+        direction:Vertex,
+        #[serde(default = "default_light_color")]
+        color:Rgb,
+        #[serde(default = "default_intensity")]
+        intensity:f64
+    },
+    Point {
+        position:Vertex,
+        #[serde(default = "default_light_color")]
+        color:Rgb,
+        #[serde(default = "default_intensity")]
+        intensity:f64
+    },
+    Spot {
+        position:Vertex,
+        direction:Vertex,
+        inner:f64,
+        outer:f64,
+        #[serde(default = "default_light_color")]
+        color:Rgb,
+        #[serde(default = "default_intensity")]
+        intensity:f64
+    },
+    // area lights are glowing objects, color times intensity being their radiance
+    Rectangle {
+        corner:Vertex,
+        edge1:Vertex,
+        edge2:Vertex,
+        #[serde(default = "default_light_color")]
+        color:Rgb,
+        #[serde(default = "default_intensity")]
+        intensity:f64
+    },
+    Disk {
+        center:Vertex,
+        normal:Vertex,
+        radius:f64,
+        #[serde(default = "default_light_color")]
+        color:Rgb,
+        #[serde(default = "default_intensity")]
+        intensity:f64
+    },
+    Sphere {
+        center:Vertex,
+        radius:f64,
+        #[serde(default = "default_light_color")]
+        color:Rgb,
+        #[serde(default = "default_intensity")]
+        intensity:f64
+    }
+}
+
+fn light_intensity(intensity:f64) -> Result<f64,LoadError>
+{
+    between("light intensity",intensity,0.0,f64::INFINITY)
+}
+
+// surface and radiance of an area light
+type AreaLight = (Box<dyn Shape>,Color);
+
+impl Light
+{
+    fn area(&self) -> Result<Option<AreaLight>,LoadError>
+    {
+        Ok(match self {
+            Light::Rectangle{corner,edge1,edge2,color,intensity} => Some((
+                Box::new(rectangle(corner,edge1,edge2)?),
+                color.to_color().mul(light_intensity(*intensity)?)
+            )),
+            Light::Disk{center,normal,radius:r,color,intensity} => Some((
+                Box::new(Disk{center:center.to_vec3(),normal:non_null("disk light normal",normal)?,radius:radius("disk light",*r)?}),
+                color.to_color().mul(light_intensity(*intensity)?)
+            )),
+            Light::Sphere{center,radius:r,color,intensity} => Some((
+                Box::new(Sphere{center:center.to_vec3(),radius:radius("sphere light",*r)?}),
+                color.to_color().mul(light_intensity(*intensity)?)
+            )),
+            _ => None
+        })
+    }
+
+    // none for area lights, which the scene finds from its emissive materials
+    fn to_engine(&self) -> Result<Option<EngineLight>,LoadError>
+    {
+        Ok(Some(match self {
+            Light::Directional{direction,color,intensity} => EngineLight::Directional{
+                direction:non_null("directional light direction",direction)?,
+                color:color.to_color(),
+                intensity:light_intensity(*intensity)?
+            },
+            Light::Point{position,color,intensity} => EngineLight::Point{
+                position:position.to_vec3(),
+                color:color.to_color(),
+                intensity:light_intensity(*intensity)?
+            },
+            Light::Spot{position,direction,inner,outer,color,intensity} => EngineLight::Spot{
+                position:position.to_vec3(),
+                direction:non_null("spot light direction",direction)?,
+                outer:between("spot light outer angle",*outer,0.0,180.0)?,
+                inner:between("spot light inner angle",*inner,0.0,*outer)?,
+                color:color.to_color(),
+                intensity:light_intensity(*intensity)?
+            },
+            Light::Rectangle{..} | Light::Disk{..} | Light::Sphere{..} => return Ok(None)
+        }))
+    }
+}
+
+// equirectangular picture of the surroundings, lighting the scene and seen
+// behind it in place of the background color
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
-pub struct Face (u32,u32,u32);
+pub struct Environment {
+    // .hdr or .pfm file, relative to the scene file
+    path: String,
+    // turn around the vertical axis, in degrees
+    #[serde(default)]
+    rotation: f64,
+    #[serde(default="default_intensity")]
+    intensity: f64
+}
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Scene {
+    #[serde(default)]
     vertices: Vec::<Vertex>,
+    #[serde(default)]
     faces   : Vec::<Face>,
-    light_direction : Vertex,
+    // one normal per vertex for smooth faces
+    #[serde(default)]
+    normals : Vec::<Vertex>,
+    // one texture coordinate per vertex
+    #[serde(default)]
+    uvs     : Vec::<Uv>,
+    // faces without normals are smoothed with their neighbours bending less
+    // than this angle in degrees, 0 keeps them flat
+    #[serde(default)]
+    crease_angle : f64,
+    // wavefront obj file added to the mesh, relative to the scene file
+    mesh    : Option<String>,
+    #[serde(default)]
+    objects : Vec::<Object>,
+    // material of the faces that do not name one
+    material: Option<String>,
+    #[serde(default)]
+    lights : Vec::<Light>,
+    // former single white light, given as the direction pointing to the light
+    light_direction : Option<Vertex>,
+    environment : Option<Environment>,
+    // seen where rays leave the scene without environment
     background_color : (u8,u8,u8,u8)
 }
 
@@ -28,32 +742,252 @@ pub struct Screen
     pub height:usize
 }
 
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Projection
+{
+    #[default]
+    Perspective,
+    Fisheye
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FovAxis
+{
+    #[default]
+    Horizontal,
+    Vertical
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct Camera
 {
     position: Vertex,
     look_at: Vertex,
     up: Vertex,
-    fov: f64,
+    pub fov: f64,
+    #[serde(default)]
+    fov_axis: FovAxis,
+    #[serde(default)]
+    projection: Projection,
     pub screen: Screen,
+    // clip planes along the viewing axis, nothing is clipped by default
+    #[serde(default)]
+    near: f64,
+    far: Option<f64>
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Integrator
+{
+    #[default]
+    Whitted,
+    Path
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter
+{
+    #[default]
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell
+}
+
+fn default_tile_size() -> usize { 32 }
+fn default_max_depth() -> usize { 5 }
+fn default_samples() -> usize { 1 }
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Render
+{
+    #[serde(default)]
+    threads: usize,
+    #[serde(default = "default_tile_size")]
+    tile_size: usize,
+    #[serde(default = "default_max_depth")]
+    max_depth: usize,
+    #[serde(default)]
+    integrator: Integrator,
+    #[serde(default = "default_samples")]
+    pub samples: usize,
+    #[serde(default)]
+    filter: Filter
+}
+
+impl Default for Render
+{
+    fn default() -> Render
+    {
+        Render{
+            threads:0,
+            tile_size:default_tile_size(),
+            max_depth:default_max_depth(),
+            integrator:Integrator::Whitted,
+            samples:default_samples(),
+            filter:Filter::Box
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ToneMap
+{
+    #[default]
+    Clamp,
+    Reinhard,
+    Aces
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding
+{
+    #[default]
+    Srgb,
+    Linear
+}
+
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Precision
+{
+    #[default]
+    Half,
+    Float
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Pass
+{
+    Depth,
+    Normal,
+    Albedo,
+    Primitive,
+    Object,
+    Alpha
+}
+
+// how the rendered radiance is turned into the image file
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Output
+{
+    #[serde(default)]
+    exposure: f64,
+    #[serde(default)]
+    tone_map: ToneMap,
+    #[serde(default)]
+    encoding: Encoding,
+    // floats of the OpenEXR files
+    #[serde(default)]
+    exr: Precision,
+    // auxiliary passes written next to the image
+    #[serde(default)]
+    aovs: Vec<Pass>,
+    // passes go in the OpenEXR output instead of files of their own
+    #[serde(default)]
+    pub layers: bool
+}
+
+impl Output
+{
+    pub fn precision(&self) -> WriterPrecision
+    {
+        match self.exr {
+            Precision::Half  => WriterPrecision::Half,
+            Precision::Float => WriterPrecision::Float
+        }
+    }
+
+    pub fn passes(&self) -> Vec<EnginePass>
+    {
+        self.aovs.iter().map(|pass| match pass {
+            Pass::Depth     => EnginePass::Depth,
+            Pass::Normal    => EnginePass::Normal,
+            Pass::Albedo    => EnginePass::Albedo,
+            Pass::Primitive => EnginePass::Primitive,
+            Pass::Object    => EnginePass::Object,
+            Pass::Alpha     => EnginePass::Alpha
+        }).collect()
+    }
 }
 
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct ConfigData
 {
     pub camera:Camera,
-    scene:Scene
+    scene:Scene,
+    #[serde(default)]
+    materials:BTreeMap<String,Material>,
+    #[serde(default)]
+    pub render:Render,
+    #[serde(default)]
+    pub output:Output,
+    // folder of the scene file, other files are looked up from there
+    #[serde(skip)]
+    directory:PathBuf
 }
 
-pub fn from_path(path:&str) -> ConfigData
-{    
+fn read_file(path:&Path) -> Result<String,LoadError>
+{
     let mut input = String::from("");
-    let mut ifile = File::open(&path).expect("unable to open path");    
-    ifile.read_to_string(&mut input).expect("unable to read");
-    serde_yaml::from_str(&input).expect("invalid format")
+    let mut ifile = File::open(path).map_err(|e| LoadError::io(path.to_path_buf(),e))?;
+    ifile.read_to_string(&mut input).map_err(|e| LoadError::io(path.to_path_buf(),e))?;
+    Ok(input)
 }
 
-pub fn to_engine(config:&ConfigData) -> (Tracer,Vec::<[Vec3;3]>)
+// png image, srgb for colors and linear for data
+fn read_image(path:&Path,srgb:bool) -> Result<Image,LoadError>
+{
+    let data   = std::fs::read(path).map_err(|e| LoadError::io(path.to_path_buf(),e))?;
+    let bitmap = lodepng::decode32(&data)
+        .map_err(|e| LoadError::Semantic(format!("unable to read texture {} : {}",path.display(),e)))?;
+    let rgba:Vec<[u8;4]> = bitmap.buffer.iter().map(|p| [p.r,p.g,p.b,p.a]).collect();
+    Ok(Image::from_rgba(bitmap.width,bitmap.height,&rgba,srgb))
+}
+
+// high dynamic range picture, linear
+fn read_hdr_image(path:&Path) -> Result<Image,LoadError>
+{
+    let decode = match path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).as_deref() {
+        Some("hdr") => hdr::decode,
+        Some("pfm") => pfm::decode,
+        _           => return Err(LoadError::Semantic(format!("environment {} is neither a .hdr nor a .pfm file",path.display())))
+    };
+    let data = std::fs::read(path).map_err(|e| LoadError::io(path.to_path_buf(),e))?;
+    decode(&data).map_err(|e| LoadError::Semantic(format!("unable to read environment {} : {}",path.display(),e)))
+}
+
+pub fn from_str(input:&str) -> Result<ConfigData,LoadError>
+{
+    serde_yaml::from_str(input).map_err(LoadError::yaml)
+}
+
+pub fn from_path(path:&str) -> Result<ConfigData,LoadError>
+{
+    let mut config   = from_str(&read_file(Path::new(path))?)?;
+    config.directory = Path::new(path).parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+    Ok(config)
+}
+
+// 1-based index of a face in the vertex list
+fn face_vertex(vertices:&[Vertex],index:u32,face:usize) -> Result<Vec3,LoadError>
+{
+    match (index as usize).checked_sub(1).and_then(|i| vertices.get(i)) {
+        Some(v) => Ok(v.to_vec3()),
+        None    => Err(LoadError::Semantic(format!(
+            "face {} references vertex {} but vertices go from 1 to {}",face+1,index,vertices.len()
+        )))
+    }
+}
+
+pub fn to_engine(config:&ConfigData) -> Result<(Tracer,EngineScene),LoadError>
 {
 
     let origin    = Vec3::new( config.camera.position.0, config.camera.position.1 , config.camera.position.2 );
@@ -62,63 +996,706 @@ pub fn to_engine(config:&ConfigData) -> (Tracer,Vec::<[Vec3;3]>)
     let up        = Vec3::new( config.camera.up.0, config.camera.up.1 , config.camera.up.2 );
     let fov       = config.camera.fov;
 
+    if direction.norm() == 0.0
+    {
+        return Err(LoadError::Semantic(String::from("camera look_at is the same point as its position")));
+    }
+    if up.cross(&direction).norm() == 0.0
+    {
+        return Err(LoadError::Semantic(String::from("camera up is null or parallel to the viewing direction")));
+    }
+    if config.camera.screen.width == 0 || config.camera.screen.height == 0
+    {
+        return Err(LoadError::Semantic(String::from("camera screen has no pixel")));
+    }
+    if !(fov > 0.0 && fov < 180.0)
+    {
+        return Err(LoadError::Semantic(format!("camera fov {} is not between 0 and 180 degrees",fov)));
+    }
+    let far = config.camera.far.unwrap_or(f64::INFINITY);
+    if !(config.camera.near >= 0.0 && far > config.camera.near)
+    {
+        return Err(LoadError::Semantic(String::from("camera near has to be positive and less than far")));
+    }
+
+    let mut cam   = EngineCam::new(&origin,&direction,&up,fov);
+    cam.projection = match config.camera.projection {
+        Projection::Perspective => EngineProjection::Perspective,
+        Projection::Fisheye     => EngineProjection::Fisheye
+    };
+    cam.fov_axis = match config.camera.fov_axis {
+        FovAxis::Horizontal => EngineFovAxis::Horizontal,
+        FovAxis::Vertical   => EngineFovAxis::Vertical
+    };
+    cam.near = config.camera.near;
+    cam.far  = far;
+
     let tracer    = Tracer{
-                        cam:EngineCam::new(&origin,&direction,&up,fov),
+                        cam,
                         screen:(
                             config.camera.screen.width,
                             config.camera.screen.height
                         ),
-                        light:Vec3::new(
-                            config.scene.light_direction.0,
-                            config.scene.light_direction.1,
-                            config.scene.light_direction.2
+                        // 8 bits sRGB, decoded to linear radiance once
+                        background:Color::new(
+                            srgb_to_linear((config.scene.background_color.0 as f64)/255.0),
+                            srgb_to_linear((config.scene.background_color.1 as f64)/255.0),
+                            srgb_to_linear((config.scene.background_color.2 as f64)/255.0)
                         ),
-                        background:Pixel{
-                            0:config.scene.background_color.0,
-                            1:config.scene.background_color.1,
-                            2:config.scene.background_color.2,
-                            3:config.scene.background_color.3
-                        }
+                        background_alpha:(config.scene.background_color.3 as f64)/255.0,
+                        threads:config.render.threads,
+                        tile_size:config.render.tile_size,
+                        max_depth:config.render.max_depth,
+                        integrator:match config.render.integrator {
+                            Integrator::Whitted => EngineIntegrator::Whitted,
+                            Integrator::Path    => EngineIntegrator::Path
+                        },
+                        samples:config.render.samples,
+                        filter:match config.render.filter {
+                            Filter::Box      => EngineFilter::Box,
+                            Filter::Tent     => EngineFilter::Tent,
+                            Filter::Gaussian => EngineFilter::Gaussian,
+                            Filter::Mitchell => EngineFilter::Mitchell
+                        },
+                        output:OutputTransform{
+                            exposure:config.output.exposure,
+                            tone_map:match config.output.tone_map {
+                                ToneMap::Clamp    => EngineToneMap::Clamp,
+                                ToneMap::Reinhard => EngineToneMap::Reinhard,
+                                ToneMap::Aces     => EngineToneMap::Aces
+                            },
+                            srgb:config.output.encoding == Encoding::Srgb
+                        },
+                        quiet:false,
+                        // alpha is always rendered
+                        aovs:config.output.aovs.iter().any(|pass| *pass != Pass::Alpha)
                     };
 
-    let mut mesh  = Vec::<[Vec3;3]>::with_capacity(config.scene.faces.len());
+    let mut materials = vec![EngineMaterial::default()];
+    let mut names     = BTreeMap::<&String,usize>::new();
+    for (name,material) in &config.materials
+    {
+        names.insert(name,materials.len());
+        materials.push(material.to_engine(&config.directory)?);
+    }
 
-    for face  in &config.scene.faces
+    let obj = match &config.scene.mesh {
+        Some(path) => {
+            let path = config.directory.join(path);
+            Some((obj::from_path(&path)?,path.parent().unwrap_or_else(|| Path::new("")).to_path_buf()))
+        },
+        None       => None
+    };
+    // materials of the scene file win over the ones of the mtl libraries,
+    // whose texture paths are relative to the obj file
+    if let Some((obj,directory)) = &obj
     {
-        let mut v1 = Vec3::null();
-        let mut v2 = Vec3::null();
-        let mut v3 = Vec3::null();
+        for (name,material) in &obj.materials
+        {
+            if names.contains_key(name) { continue; }
+            names.insert(name,materials.len());
+            materials.push(material.to_engine(directory)?);
+        }
+    }
 
-        if let Some(v) = config.scene.vertices.get((face.0-1) as usize) {
-            v1.x = v.0;
-            v1.y = v.1;
-            v1.z = v.2;
-        } else {
-            println!("face {:?} has invalid vertice reference : ignored!",face);
-            continue;
-        };
+    let find_material = |name:Option<&String>| -> Result<usize,LoadError> {
+        match name {
+            None       => Ok(0),
+            Some(name) => match names.get(name) {
+                Some(index) => Ok(*index),
+                None        => Err(LoadError::Semantic(format!("material {} is not defined",name)))
+            }
+        }
+    };
+    let mesh_material = find_material(config.scene.material.as_ref())?;
 
-        if let Some(v) = config.scene.vertices.get((face.1-1) as usize) {
-            v2.x = v.0;
-            v2.y = v.1;
-            v2.z = v.2;
-        } else {
-            println!("face {:?} has invalid vertice reference : ignored!",face);
-            continue;
-        };
+    let mut mesh           = Vec::<[Vec3;3]>::with_capacity(config.scene.faces.len());
+    let mut face_materials = Vec::<usize>::with_capacity(config.scene.faces.len());
+    let mut normals        = Vec::<Option<[Vec3;3]>>::with_capacity(config.scene.faces.len());
+    let mut uvs            = Vec::<Option<[(f64,f64);3]>>::with_capacity(config.scene.faces.len());
+    if !config.scene.normals.is_empty() && config.scene.normals.len() != config.scene.vertices.len()
+    {
+        return Err(LoadError::Semantic(format!(
+            "{} normals are given for {} vertices",config.scene.normals.len(),config.scene.vertices.len()
+        )));
+    }
+    if !config.scene.uvs.is_empty() && config.scene.uvs.len() != config.scene.vertices.len()
+    {
+        return Err(LoadError::Semantic(format!(
+            "{} uvs are given for {} vertices",config.scene.uvs.len(),config.scene.vertices.len()
+        )));
+    }
+    // the faces of the scene file are object 0, every group of the obj file
+    // is an object of its own, analytic objects come last
+    let mut face_objects   = vec![0;config.scene.faces.len()];
 
-        if let Some(v) = config.scene.vertices.get((face.2-1) as usize) {
-            v3.x = v.0;
-            v3.y = v.1;
-            v3.z = v.2;
-        } else {
-            println!("face {:?} has invalid vertice reference : ignored!",face);
-            continue;
-        };
+    for (index,face) in config.scene.faces.iter().enumerate()
+    {
+        let (i1,i2,i3) = face.indices();
+        let vertices   = &config.scene.vertices;
+        mesh.push([
+            face_vertex(vertices,i1,index)?,
+            face_vertex(vertices,i2,index)?,
+            face_vertex(vertices,i3,index)?
+        ]);
+        face_materials.push(match face.material() {
+            None       => mesh_material,
+            Some(name) => find_material(Some(name))?
+        });
+        normals.push(match config.scene.normals.is_empty() {
+            true  => None,
+            false => Some([
+                face_vertex(&config.scene.normals,i1,index)?,
+                face_vertex(&config.scene.normals,i2,index)?,
+                face_vertex(&config.scene.normals,i3,index)?
+            ])
+        });
+        // the indices were checked against the vertices, uvs have as many
+        let uv = |i:u32| { let uv = &config.scene.uvs[i as usize-1]; (uv.0,uv.1) };
+        uvs.push(match config.scene.uvs.is_empty() {
+            true  => None,
+            false => Some([uv(i1),uv(i2),uv(i3)])
+        });
+    }
+
+    if let Some((obj,_)) = &obj
+    {
+        for triangle in &obj.triangles
+        {
+            let [c1,c2,c3] = &triangle.corners;
+            mesh.push([
+                obj.positions[c1.position].clone(),
+                obj.positions[c2.position].clone(),
+                obj.positions[c3.position].clone()
+            ]);
+            face_materials.push(match &triangle.material {
+                None       => mesh_material,
+                Some(name) => find_material(Some(name))?
+            });
+            face_objects.push(1+triangle.group);
+            normals.push(match (c1.normal,c2.normal,c3.normal) {
+                (Some(n1),Some(n2),Some(n3)) => Some([
+                    obj.normals[n1].clone(),
+                    obj.normals[n2].clone(),
+                    obj.normals[n3].clone()
+                ]),
+                _ => None
+            });
+            uvs.push(match (c1.uv,c2.uv,c3.uv) {
+                (Some(t1),Some(t2),Some(t3)) => Some([obj.uvs[t1],obj.uvs[t2],obj.uvs[t3]]),
+                _                            => None
+            });
+        }
+    }
+    let first_object = 1+obj.as_ref().map_or(0,|(obj,_)| obj.groups.len());
+
+    let mut objects          = config.scene.objects.iter().map(|o| o.geometry.to_shape()).collect::<Result<Vec<_>,_>>()?;
+    let mut object_materials = config.scene.objects.iter().map(|o| find_material(o.material.as_ref())).collect::<Result<Vec<_>,_>>()?;
+    // area lights come after the objects, each with a black material of its own
+    for area in config.scene.lights.iter().map(Light::area)
+    {
+        let (shape,emission) = match area? { Some(area) => area, None => continue };
+        objects.push(shape);
+        object_materials.push(materials.len());
+        materials.push(EngineMaterial{diffuse:Color::black(),emission,..EngineMaterial::default()});
+    }
+
+    if !(0.0..=180.0).contains(&config.scene.crease_angle)
+    {
+        return Err(LoadError::Semantic(format!("crease_angle {} is not between 0 and 180 degrees",config.scene.crease_angle)));
+    }
+    if config.scene.crease_angle > 0.0
+    {
+        let smooth = triangle::vertex_normals(&mesh,config.scene.crease_angle);
+        for (normal,smooth) in normals.iter_mut().zip(smooth)
+        {
+            if normal.is_none() { *normal = Some(smooth); }
+        }
+    }
+
+    let mut scene          = SceneBuilder::new(mesh,objects);
+    scene.normals          = normals;
+    scene.uvs              = uvs;
+    scene.materials        = materials;
+    scene.face_materials   = face_materials;
+    scene.object_materials = object_materials;
+    scene.face_objects     = face_objects;
+    scene.object_ids       = (first_object..first_object+scene.objects.len()).collect();
+    scene.lights           = config.scene.lights.iter().map(Light::to_engine).filter_map(Result::transpose).collect::<Result<Vec<_>,_>>()?;
+
+    if let Some(direction) = &config.scene.light_direction
+    {
+        scene.lights.push(EngineLight::Directional{
+            direction:non_null("light_direction",direction)?.mul(-1.0),
+            color:Color::white(),
+            intensity:1.0
+        });
+    }
+
+    if let Some(environment) = &config.scene.environment
+    {
+        scene.lights.push(EngineLight::Environment(EngineEnvironment::new(
+            read_hdr_image(&config.directory.join(&environment.path))?,
+            environment.rotation.to_radians(),
+            between("environment intensity",environment.intensity,0.0,f64::INFINITY)?
+        )));
+    }
+
+    let scene = scene.build().map_err(|e| LoadError::Semantic(e.to_string()))?;
+    Ok((tracer,scene))
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::scene::Primitive;
+
+    const CAMERA:&str = "camera:\n  position: [0, 0, -5]\n  look_at: [0, 0, 0]\n  up: [0, 1, 0]\n  fov: 60\n  screen: {width: 4, height: 3}\n";
+
+    fn load(scene:&str) -> Result<(Tracer,EngineScene),LoadError>
+    {
+        to_engine(&from_str(&format!("{}{}",CAMERA,scene))?)
+    }
+
+    // message of the error a scene section with item appended gives
+    fn scene_error(item:&str) -> String
+    {
+        semantic(load(&format!("scene:\n  background_color: [0,0,0,255]\n{}",item)))
+    }
+
+    fn semantic(result:Result<(Tracer,EngineScene),LoadError>) -> String
+    {
+        match result {
+            Err(LoadError::Semantic(message)) => message,
+            Err(e)                            => panic!("unexpected error {}",e),
+            Ok(_)                             => panic!("scene accepted")
+        }
+    }
 
-        mesh.push([v1,v2,v3]);
+    #[test]
+    fn test_valid_scene() {
+        let (_,scene) = load("scene:\n  background_color: [0,0,0,255]\n  vertices: [[0,0,0],[1,0,0],[0,1,0]]\n  faces: [[1,2,3]]\n").unwrap();
+
+        assert_eq!(1,scene.mesh().len());
+    }
+
+    #[test]
+    fn test_yaml_location() {
+        match from_str("camera:\n  position: [0, 0\n  fov: 60\n") {
+            Err(LoadError::Yaml{line,column,..}) => {
+                assert!(line.is_some());
+                assert!(column.is_some());
+            },
+            _ => panic!("invalid yaml accepted")
+        }
+    }
+
+    #[test]
+    fn test_face_out_of_range() {
+        let vertices = "scene:\n  background_color: [0,0,0,255]\n  vertices: [[0,0,0],[1,0,0],[0,1,0]]\n";
+
+        let message = semantic(load(&format!("{}  faces: [[1,2,3],[1,2,4]]\n",vertices)));
+        assert!(message.contains("face 2"),"{}",message);
+        // 1-based indices : 0 is never valid
+        semantic(load(&format!("{}  faces: [[0,1,2]]\n",vertices)));
+    }
+
+    #[test]
+    fn test_degenerate_geometry() {
+        assert!(scene_error("  objects:\n    - {type: sphere, center: [0,0,0], radius: 0}\n").contains("sphere radius"));
+        assert!(scene_error("  objects:\n    - {type: disk, center: [0,0,0], normal: [0,0,1], radius: -1}\n").contains("disk radius"));
+        assert!(scene_error("  objects:\n    - {type: disk, center: [0,0,0], normal: [0,0,0], radius: 1}\n").contains("disk normal"));
+        assert!(scene_error("  objects:\n    - {type: plane, point: [0,0,0], normal: [0,0,0]}\n").contains("plane normal"));
+        assert!(scene_error("  objects:\n    - {type: rectangle, corner: [0,0,0], edge1: [1,0,0], edge2: [2,0,0]}\n").contains("rectangle"));
+        assert!(scene_error("  objects:\n    - {type: cylinder, base: [0,1,0], top: [0,1,0], radius: 1}\n").contains("cylinder base"));
+        assert!(scene_error("  objects:\n    - {type: cone, base: [0,1,0], apex: [0,1,0], radius: 1}\n").contains("cone base"));
+        assert!(scene_error("  objects:\n    - {type: box, min: [0,0,0], max: [1,-1,1]}\n").contains("box min"));
+        assert!(scene_error("  lights:\n    - {type: sphere, center: [0,0,0], radius: 0}\n").contains("sphere light radius"));
+    }
+
+    #[test]
+    fn test_degenerate_lights() {
+        assert!(scene_error("  lights:\n    - {type: directional, direction: [0,0,0]}\n").contains("directional light"));
+        assert!(scene_error("  lights:\n    - {type: spot, position: [0,0,0], direction: [0,0,0], inner: 10, outer: 20}\n").contains("spot light"));
+        assert!(scene_error("  lights:\n    - {type: spot, position: [0,0,0], direction: [0,0,1], inner: 30, outer: 20}\n").contains("inner angle"));
+        assert!(scene_error("  light_direction: [0,0,0]\n").contains("light_direction"));
+        assert!(scene_error("  lights:\n    - {type: spot, position: [0,0,0], direction: [0,0,1], inner: 10, outer: 200}\n").contains("outer angle 200 is not between 0 and 180"));
+        assert!(scene_error("  lights:\n    - {type: point, position: [0,0,0], intensity: -1}\n").contains("intensity -1 is not between"));
+        assert!(scene_error("  lights:\n    - {type: directional, direction: [0,0,1], intensity: .inf}\n").contains("intensity inf is not between"));
+        assert!(scene_error("  lights:\n    - {type: disk, center: [0,0,0], normal: [0,0,1], radius: 1, intensity: -2}\n").contains("intensity -2 is not between"));
+    }
+
+    #[test]
+    fn test_fov_range() {
+        let camera = |fov:&str| format!("camera:\n  position: [0, 0, -5]\n  look_at: [0, 0, 0]\n  up: [0, 1, 0]\n  fov: {}\n  screen: {{width: 4, height: 3}}\nscene:\n  background_color: [0,0,0,255]\n",fov);
+
+        for fov in ["0","-10","180","270"]
+        {
+            let message = semantic(from_str(&camera(fov)).and_then(|config| to_engine(&config)));
+            assert!(message.contains("fov"),"{}",message);
+        }
+        assert!(from_str(&camera("179")).and_then(|config| to_engine(&config)).is_ok());
+    }
+
+    #[test]
+    fn test_background_is_decoded_once() {
+        let scene = "scene:\n  background_color: [188,255,0,128]\n";
+        for output in ["","output:\n  encoding: linear\n  exposure: 2\n"]
+        {
+            let (tracer,_) = load(&format!("{}{}",scene,output)).unwrap();
+            assert!((tracer.background.r-0.5).abs() < 0.005,"{:?}",tracer.background);
+            assert_eq!(1.0,tracer.background.g);
+            assert_eq!(0.0,tracer.background.b);
+            assert_eq!(128.0/255.0,tracer.background_alpha);
+        }
+    }
+
+    #[test]
+    fn test_undefined_material() {
+        semantic(load("scene:\n  background_color: [0,0,0,255]\n  material: gold\n"));
+    }
+
+    // linear color seen through a single pixel camera looking along z at
+    // the origin, with one sample
+    fn render_center(scene:&str) -> Color
+    {
+        let camera = "camera:\n  position: [0, 0, -5]\n  look_at: [0, 0, 0]\n  up: [0, 1, 0]\n  fov: 60\n  screen: {width: 1, height: 1}\n";
+        let (tracer,scene) = to_engine(&from_str(&format!("{}{}",camera,scene)).unwrap()).unwrap();
+        tracer.render(&scene).unwrap().colors[0]
+    }
+
+    fn assert_color(expected:Color,color:Color)
+    {
+        let close = (expected.r-color.r).abs() < 1e-9 && (expected.g-color.g).abs() < 1e-9 && (expected.b-color.b).abs() < 1e-9;
+        assert!(close,"{:?} != {:?}",color,expected);
+    }
+
+    #[test]
+    fn test_materials_render() {
+        // a wall facing the camera, lit from behind the camera
+        let scene = |face:&str| format!(
+            "render:\n  samples: 1\nmaterials:\n  brick: {{diffuse: [0.8, 0.2, 0.1]}}\n  tile: {{diffuse: [0.8, 0.2, 0.1], specular: [0.5, 0.5, 0.5], shininess: 10}}\nscene:\n  background_color: [0,0,0,255]\n  material: tile\n  vertices: [[-10,-10,0],[10,-10,0],[0,10,0]]\n  faces: [{}]\n  lights:\n    - {{type: directional, direction: [0, 0, 1]}}\n",
+            face
+        );
+
+        // the diffuse color under a light facing the wall
+        assert_color(Color::new(0.8,0.2,0.1),render_center(&scene("[1,2,3,brick]")));
+        // faces without a material take the one of the scene, whose
+        // highlight peaks when the light is behind the viewer
+        assert_color(Color::new(1.3,0.7,0.6),render_center(&scene("[1,2,3]")));
+    }
+
+    #[test]
+    fn test_reflection_render() {
+        // a mirror at z=0 sending the camera ray to a red wall behind the
+        // camera, lit by a point light 2 units away with an intensity of 4
+        let scene = |mirror:&str,depth:usize| format!(
+            "render:\n  samples: 1\n  max_depth: {}\nmaterials:\n  mirror: {}\n  red: {{diffuse: [1, 0, 0]}}\nscene:\n  background_color: [0,0,0,255]\n  vertices: [[-10,-10,0],[10,-10,0],[0,10,0],[-10,-10,-10],[10,-10,-10],[0,10,-10]]\n  faces: [[1,2,3,mirror],[4,5,6,red]]\n  lights:\n    - {{type: point, position: [0, 0, -8], intensity: 4}}\n",
+            depth,mirror
+        );
+
+        assert_color(Color::new(1.0,0.0,0.0),render_center(&scene("{diffuse: [0, 0, 0], reflectivity: 1}",5)));
+        assert_color(Color::new(0.5,0.0,0.0),render_center(&scene("{diffuse: [0, 0, 0], reflectivity: 0.5}",5)));
+        // no bounce allowed, only the direct light of a black surface
+        assert_color(Color::black(),render_center(&scene("{diffuse: [0, 0, 0], reflectivity: 1}",0)));
+    }
+
+    #[test]
+    fn test_refraction_render() {
+        // a glass pane at z=0 in front of a red wall at z=2, lit by a point
+        // light between them : 4% of the light is reflected at normal
+        // incidence toward the black background, the rest goes through
+        let scene = |depth:usize| format!(
+            "render:\n  samples: 1\n  max_depth: {}\nmaterials:\n  glass: {{diffuse: [0, 0, 0], transparency: 1, ior: 1.5}}\n  red: {{diffuse: [1, 0, 0]}}\nscene:\n  background_color: [0,0,0,255]\n  vertices: [[-10,-10,0],[10,-10,0],[0,10,0],[-10,-10,2],[10,-10,2],[0,10,2]]\n  faces: [[1,2,3,glass],[4,5,6,red]]\n  lights:\n    - {{type: point, position: [0, 0, 1]}}\n",
+            depth
+        );
+
+        assert_color(Color::new(0.96,0.0,0.0),render_center(&scene(5)));
+        assert_color(Color::black(),render_center(&scene(0)));
+    }
+
+    #[test]
+    fn test_opacity_render() {
+        // a half covering red wall in front of a green one, both lit from
+        // behind the camera : the green wall is half in the shadow of the red
+        let scene = "render:\n  samples: 1\nmaterials:\n  lace: {diffuse: [1, 0, 0], opacity: 0.5}\n  green: {diffuse: [0, 1, 0]}\nscene:\n  background_color: [0,0,0,255]\n  vertices: [[-10,-10,0],[10,-10,0],[0,10,0],[-10,-10,2],[10,-10,2],[0,10,2]]\n  faces: [[1,2,3,lace],[4,5,6,green]]\n  lights:\n    - {type: directional, direction: [0, 0, 1]}\n";
+        assert_color(Color::new(0.5,0.25,0.0),render_center(scene));
+
+        let message = semantic(load("materials:\n  lace: {opacity: 2}\nscene:\n  background_color: [0,0,0,255]\n  material: lace\n"));
+        assert!(message.contains("opacity"),"{}",message);
+    }
+
+    #[test]
+    fn test_energy_conservation() {
+        let material = |values:&str| load(&format!("materials:\n  m: {{{}}}\nscene:\n  background_color: [0,0,0,255]\n  material: m\n",values));
+
+        assert!(semantic(material("reflectivity: 1.5")).contains("reflectivity"));
+        assert!(semantic(material("transparency: -0.5")).contains("transparency"));
+        assert!(semantic(material("reflectivity: 0.6, transparency: 0.6")).contains("sum above 1"));
+        assert!(material("reflectivity: 0.4, transparency: 0.6").is_ok());
+        assert!(semantic(material("ior: 0")).contains("ior 0 is not between"));
+        assert!(semantic(material("ior: -1.5")).contains("ior -1.5 is not between"));
+        assert!(semantic(material("shininess: -4")).contains("shininess -4 is not between"));
+    }
+
+    #[test]
+    fn test_degenerate_camera() {
+        let scene = "scene:\n  background_color: [0,0,0,255]\n";
+        let look_at_eye = CAMERA.replace("look_at: [0, 0, 0]","look_at: [0, 0, -5]");
+        let null_up     = CAMERA.replace("up: [0, 1, 0]","up: [0, 0, 0]");
+
+        semantic(to_engine(&from_str(&format!("{}{}",look_at_eye,scene)).unwrap()));
+        semantic(to_engine(&from_str(&format!("{}{}",null_up,scene)).unwrap()));
+    }
+
+    #[test]
+    fn test_vertex_normals() {
+        let vertices = "scene:\n  background_color: [0,0,0,255]\n  vertices: [[0,0,0],[1,0,0],[0,1,0],[0,0,1]]\n  faces: [[1,2,3],[1,4,2]]\n";
+
+        let (_,flat) = load(vertices).unwrap();
+        assert!((0..2).all(|i| flat.normals(i).is_none()));
+
+        let (_,given) = load(&format!("{}  normals: [[0,0,1],[0,1,0],[1,0,0],[0,1,0]]\n",vertices)).unwrap();
+        let normals   = given.normals(1).unwrap();
+        assert_eq!(1.0,normals[0].z);
+        assert_eq!(1.0,normals[1].y);
+        assert_eq!(1.0,given.normals(0).unwrap()[2].x);
+
+        // the two faces are at 90 degrees
+        let (_,creased) = load(&format!("{}  crease_angle: 80\n",vertices)).unwrap();
+        assert_eq!(1.0,creased.normals(0).unwrap()[0].z);
+        let (_,smooth)  = load(&format!("{}  crease_angle: 100\n",vertices)).unwrap();
+        assert!(smooth.normals(0).unwrap()[0].z.abs() < 1.0);
+
+        // an angle between faces is between 0 and 180 degrees
+        for angle in ["-1","181",".nan"]
+        {
+            let message = semantic(load(&format!("{}  crease_angle: {}\n",vertices,angle)));
+            assert!(message.contains("crease_angle"),"{}",message);
+        }
+        assert!(load(&format!("{}  crease_angle: 180\n",vertices)).is_ok());
+
+        semantic(load(&format!("{}  normals: [[0,0,1]]\n",vertices)));
+    }
+
+    #[test]
+    fn test_clip_planes() {
+        let scene = "scene:\n  background_color: [0,0,0,255]\n";
+        let (tracer,_) = to_engine(&from_str(&format!("{}  near: 0.5\n  far: 100\n{}",CAMERA,scene)).unwrap()).unwrap();
+
+        assert_eq!(0.5,tracer.cam.near);
+        assert_eq!(100.0,tracer.cam.far);
+        assert_eq!(f64::INFINITY,load(scene).unwrap().0.cam.far);
+        semantic(to_engine(&from_str(&format!("{}  near: 2\n  far: 1\n{}",CAMERA,scene)).unwrap()));
+        semantic(to_engine(&from_str(&format!("{}  near: -1\n{}",CAMERA,scene)).unwrap()));
+    }
+
+    #[test]
+    fn test_aovs() {
+        let yaml   = "scene:\n  background_color: [0,0,0,255]\n  vertices: [[0,0,0],[1,0,0],[0,1,0]]\n  faces: [[1,2,3]]\n  objects:\n    - {type: sphere, center: [0,0,0], radius: 1}\n";
+        let config = from_str(&format!("{}{}output:\n  aovs: [depth, alpha]\n",CAMERA,yaml)).unwrap();
+        let (tracer,scene) = to_engine(&config).unwrap();
+
+        assert!(tracer.aovs);
+        assert_eq!(vec![EnginePass::Depth,EnginePass::Alpha],config.output.passes());
+        assert_eq!(0,scene.object_id(Primitive::Triangle(0)));
+        assert_eq!(1,scene.object_id(Primitive::Object(0)));
+
+        let alpha_only = from_str(&format!("{}{}output:\n  aovs: [alpha]\n",CAMERA,yaml)).unwrap();
+        assert!(!to_engine(&alpha_only).unwrap().0.aovs);
+    }
+
+    #[test]
+    fn test_uvs() {
+        let vertices = "scene:\n  background_color: [0,0,0,255]\n  vertices: [[0,0,0],[1,0,0],[0,1,0]]\n  faces: [[1,2,3]]\n";
+
+        assert_eq!(None,load(vertices).unwrap().1.uvs(0));
+        let (_,scene) = load(&format!("{}  uvs: [[0,0],[1,0],[0.5,1]]\n",vertices)).unwrap();
+        assert_eq!(Some(&[(0.0,0.0),(1.0,0.0),(0.5,1.0)]),scene.uvs(0));
+
+        semantic(load(&format!("{}  uvs: [[0,0]]\n",vertices)));
+    }
+
+    #[test]
+    fn test_missing_texture() {
+        match load("materials:\n  wall: {texture: {type: image, path: /does/not/exist.png}}\nscene:\n  background_color: [0,0,0,255]\n  material: wall\n") {
+            Err(LoadError::Io{path,..}) => assert_eq!(Path::new("/does/not/exist.png"),path),
+            _                           => panic!("missing texture accepted")
+        }
+    }
+
+    #[test]
+    fn test_procedural_textures() {
+        let config = from_str(&format!(
+            "{}materials:\n  a: {{texture: {{type: wood, space: object, rings: 4, octaves: 2}}}}\n  b: {{texture: {{type: checker3d, scale: 2, colors: [[1,0,0],[0,0,1]]}}}}\nscene:\n  background_color: [0,0,0,255]\n",
+            CAMERA
+        )).unwrap();
+
+        match config.materials["a"].to_engine(Path::new("")).unwrap().texture {
+            Some(EngineTexture::Pattern{pattern:EnginePattern::Wood{octaves,rings,strength},space,scale,..}) => {
+                assert_eq!(2,octaves.count);
+                assert_eq!(2.0,octaves.lacunarity);
+                assert_eq!(4.0,rings);
+                assert_eq!(0.2,strength);
+                assert_eq!(EngineSpace::Object,space);
+                assert_eq!(1.0,scale);
+            },
+            _ => panic!("wood texture expected")
+        }
+        match config.materials["b"].to_engine(Path::new("")).unwrap().texture {
+            Some(EngineTexture::Pattern{pattern:EnginePattern::Checker3d,space,scale,colors}) => {
+                assert_eq!(EngineSpace::World,space);
+                assert_eq!(2.0,scale);
+                assert_eq!(Color::new(0.0,0.0,1.0),colors[1]);
+            },
+            _ => panic!("checker texture expected")
+        }
+        assert!(from_str(&format!("{}materials:\n  c: {{texture: {{type: plaid}}}}\n",CAMERA)).is_err());
+        // misspelled or misplaced keys are not ignored
+        for texture in &["{type: perlin, scael: 2}","{type: checker, octaves: 2}","{type: fbm, rings: 4}","{type: marble, path: a.png}"]
+        {
+            assert!(from_str(&format!("{}materials:\n  c: {{texture: {}}}\n",CAMERA,texture)).is_err(),"{}",texture);
+        }
+    }
+
+    #[test]
+    fn test_unknown_keys() {
+        let scene = |yaml:&str| from_str(&format!("{}{}",CAMERA,yaml));
+
+        for material in &["{roughnes: 0.3}","{texture: {type: image, path: a.png, filtr: nearest}}","{bsdf: {type: dielectric, roughnes: 0.3}}"]
+        {
+            assert!(scene(&format!("materials:\n  m: {}\n",material)).is_err(),"{}",material);
+        }
+        let item = |list:&str,item:&str| scene(&format!("scene:\n  background_color: [0,0,0,255]\n  {}:\n    - {}\n",list,item));
+        assert!(item("objects","{type: sphere, center: [0,0,0], radius: 1, material: m, radus: 2}").is_err());
+        assert!(item("objects","{type: sphere, center: [0,0,0], radius: 1, material: [m]}").is_err());
+        assert!(item("lights","{type: point, position: [0,0,0], intensty: 2}").is_err());
+
+        match item("objects","{type: sphere, center: [0,0,0], radius: 1, material: m}").unwrap().scene.objects[0].material.as_deref() {
+            Some("m") => {},
+            _         => panic!("object material lost")
+        }
     }
 
-    (tracer,mesh)
+    #[test]
+    fn test_bsdfs() {
+        let config = from_str(&format!(
+            "{}materials:\n  gold: {{bsdf: {{type: conductor, metal: gold, roughness: 0.3}}}}\n  custom: {{bsdf: {{type: conductor, eta: [1,1,1], k: [2,2,2]}}}}\n  paint: {{bsdf: {{type: principled, clearcoat: 1}}}}\n  matte: {{bsdf: {{type: lambert}}}}\nscene:\n  background_color: [0,0,0,255]\n",
+            CAMERA
+        )).unwrap();
+        let model = |name:&str| config.materials[name].to_engine(Path::new("")).unwrap().model;
 
-}
\ No newline at end of file
+        assert_eq!(EngineModel::Conductor{eta:EngineMetal::Gold.ior().0,k:EngineMetal::Gold.ior().1,roughness:0.3},model("gold"));
+        assert_eq!(EngineModel::Conductor{eta:Color::white(),k:Color::new(2.0,2.0,2.0),roughness:0.0},model("custom"));
+        assert_eq!(EngineModel::Principled{metallic:0.0,roughness:0.5,specular:0.5,sheen:0.0,clearcoat:1.0,clearcoat_gloss:1.0},model("paint"));
+        assert_eq!(EngineModel::Lambert,model("matte"));
+        assert_eq!(EngineModel::Phong,Material::default().to_engine(Path::new("")).unwrap().model);
+        // the same bump height whether the material comes from a file or not
+        assert_eq!(EngineMaterial::default().bump_height,Material::default().to_engine(Path::new("")).unwrap().bump_height);
+
+        for bsdf in &["{type: conductor, eta: [1,1,1]}","{type: conductor, metal: gold, eta: [1,1,1], k: [2,2,2]}","{type: conductor, metal: gold, k: [2,2,2]}"]
+        {
+            let message = semantic(load(&format!("materials:\n  bare: {{bsdf: {}}}\nscene:\n  background_color: [0,0,0,255]\n",bsdf)));
+            assert!(message.contains("conductor"),"{}",message);
+        }
+    }
+
+    #[test]
+    fn test_area_lights() {
+        let yaml = "materials:\n  lamp: {emission: [4,4,4]}\nscene:\n  background_color: [0,0,0,255]\n  objects:\n    - {type: sphere, center: [0,0,0], radius: 1, material: lamp}\n  lights:\n    - {type: point, position: [0,5,0]}\n    - {type: rectangle, corner: [0,2,0], edge1: [1,0,0], edge2: [0,0,1], intensity: 3}\n    - {type: disk, center: [0,3,0], normal: [0,-1,0], radius: 0.5, color: [1,0,0]}\n";
+        let (_,scene) = load(yaml).unwrap();
+
+        // the two area lights are objects of their own after the sphere
+        assert_eq!(3,scene.objects().len());
+        assert_eq!(vec![1,2,3],(0..3).map(|i| scene.object_id(Primitive::Object(i))).collect::<Vec<_>>());
+        let rectangle = scene.material(Primitive::Object(1));
+        assert_eq!(Color::new(3.0,3.0,3.0),rectangle.emission);
+        assert!(rectangle.diffuse.is_black());
+        assert_eq!(Color::new(1.0,0.0,0.0),scene.material(Primitive::Object(2)).emission);
+
+        // the point light and one area light per emissive material
+        assert_eq!(4,scene.lights().len());
+        assert!(matches!(scene.lights()[0],EngineLight::Point{..}));
+        assert!(scene.lights()[1..].iter().all(|light| matches!(light,EngineLight::Area{..})));
+    }
+
+    // file name of the temporary directory that no other test, in this or
+    // another process, uses
+    fn temp_path(name:&str) -> std::path::PathBuf
+    {
+        static COUNT:std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let count = COUNT.fetch_add(1,std::sync::atomic::Ordering::Relaxed);
+        std::env::temp_dir().join(format!("ray_tracer-{}-{}-{}",std::process::id(),count,name))
+    }
+
+    #[test]
+    fn test_environment() {
+        // a 2x1 color float map, with a bright right half
+        let path = temp_path("environment.pfm");
+        let mut data = b"PF\n2 1\n-1.0\n".to_vec();
+        for v in &[0.0f32,0.0,0.0,2.0,2.0,2.0]
+        {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        std::fs::write(&path,&data).unwrap();
+        let yaml = format!("scene:\n  background_color: [0,0,0,255]\n  environment: {{path: {}, rotation: 90, intensity: 3}}\n",path.display());
+        let (_,scene) = load(&yaml).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let environment = scene.environment().unwrap();
+        assert_eq!(2,environment.image.width);
+        assert_eq!(3.0,environment.intensity);
+        assert!((environment.rotation-std::f64::consts::FRAC_PI_2).abs() < 1e-12);
+
+        let message = semantic(load("scene:\n  background_color: [0,0,0,255]\n  environment: {path: sky.png}\n"));
+        assert!(message.contains("sky.png"),"{}",message);
+        match load("scene:\n  background_color: [0,0,0,255]\n  environment: {path: /does/not/exist.hdr}\n") {
+            Err(LoadError::Io{..}) => {},
+            _                      => panic!("missing environment accepted")
+        }
+    }
+
+    #[test]
+    fn test_mtl_textures() {
+        // the library sits in a subdirectory of the obj file, the texture it
+        // names is looked up next to it
+        let directory = temp_path("mtl");
+        std::fs::create_dir_all(directory.join("mats")).unwrap();
+        std::fs::write(directory.join("mats/lib.mtl"),"newmtl red\nmap_Kd tex.png\n").unwrap();
+        std::fs::write(directory.join("model.obj"),"mtllib mats/lib.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nusemtl red\nf 1 2 3\n").unwrap();
+        let yaml   = format!("scene:\n  background_color: [0,0,0,255]\n  mesh: {}\n",directory.join("model.obj").display());
+        let loaded = load(&yaml);
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        match loaded {
+            Err(LoadError::Io{path,..}) => assert_eq!(directory.join("mats/tex.png"),path),
+            _                           => panic!("missing texture accepted")
+        }
+    }
+
+    #[test]
+    fn test_mtl_errors() {
+        let directory = temp_path("mtl-errors");
+        std::fs::create_dir_all(&directory).unwrap();
+        std::fs::write(directory.join("lib.mtl"),"newmtl red\nmap_Kd -s 2 2 1 red.png\n").unwrap();
+        std::fs::write(directory.join("model.obj"),"mtllib lib.mtl\n").unwrap();
+        std::fs::write(directory.join("faces.obj"),"v 0 0 0\nf 1 2 3\n").unwrap();
+        let mesh    = |name:&str| semantic(load(&format!("scene:\n  background_color: [0,0,0,255]\n  mesh: {}\n",directory.join(name).display())));
+        let library = mesh("model.obj");
+        let faces   = mesh("faces.obj");
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // each message names the file the faulty line is in, once
+        assert_eq!(format!("{} line 2 : unsupported map_Kd option -s",directory.join("lib.mtl").display()),library);
+        assert_eq!(format!("{} line 2 : face has invalid vertex reference",directory.join("faces.obj").display()),faces);
+    }
+
+    #[test]
+    fn test_missing_file() {
+        match from_path("/does/not/exist.yml") {
+            Err(LoadError::Io{..}) => {},
+            _                      => panic!("missing file accepted")
+        }
+    }
+}
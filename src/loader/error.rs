@@ -0,0 +1,61 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+// everything that can go wrong between a scene file and the engine
+#[derive(Debug)]
+pub enum LoadError
+{
+    // a file could not be opened or read
+    Io{ path:PathBuf, error:io::Error },
+    // the scene file is not valid yaml or does not match the expected layout,
+    // line and column are 1-based when serde_yaml knows them
+    Yaml{ line:Option<usize>, column:Option<usize>, error:serde_yaml::Error },
+    // the file parses but describes something that can not be rendered
+    Semantic(String)
+}
+
+impl LoadError
+{
+    pub fn io(path:PathBuf,error:io::Error) -> LoadError
+    {
+        LoadError::Io{path,error}
+    }
+
+    pub fn yaml(error:serde_yaml::Error) -> LoadError
+    {
+        let location = error.location();
+        LoadError::Yaml{
+            line:location.as_ref().map(|l| l.line()),
+            column:location.as_ref().map(|l| l.column()),
+            error
+        }
+    }
+}
+
+impl fmt::Display for LoadError
+{
+    fn fmt(&self, f:&mut fmt::Formatter) -> fmt::Result
+    {
+        match self {
+            LoadError::Io{path,error}        => write!(f,"unable to read {} : {}",path.display(),error),
+            LoadError::Yaml{line:Some(line),column:Some(column),error}
+                                             => write!(f,"invalid scene at {}:{} : {}",line,column,error),
+            LoadError::Yaml{error,..}        => write!(f,"invalid scene : {}",error),
+            LoadError::Semantic(message)     => write!(f,"invalid scene : {}",message)
+        }
+    }
+}
+
+impl Error for LoadError
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)>
+    {
+        match self {
+            LoadError::Io{error,..}   => Some(error),
+            LoadError::Yaml{error,..} => Some(error),
+            LoadError::Semantic(_)    => None
+        }
+    }
+}
@@ -0,0 +1,171 @@
+// Radiance picture reader : RGBE pixels stored top row first, flat or with
+// the run length encoding of one channel at a time per scanline
+use crate::engine::color::Color;
+use crate::engine::texture::Image;
+
+fn byte(data:&[u8],position:&mut usize) -> Result<u8,String>
+{
+    let b = *data.get(*position).ok_or("truncated pixels")?;
+    *position += 1;
+    Ok(b)
+}
+
+fn line<'a>(data:&'a [u8],position:&mut usize) -> Result<&'a str,String>
+{
+    let end  = data[*position..].iter().position(|b| *b == b'\n').ok_or("truncated header")?;
+    let line = std::str::from_utf8(&data[*position..*position+end]).map_err(|_| "header is not text")?;
+    *position += end + 1;
+    Ok(line.trim())
+}
+
+fn from_rgbe(p:[u8;4]) -> Color
+{
+    if p[3] == 0 { return Color::black(); }
+    let f = 2.0_f64.powi(p[3] as i32 - 136);
+    Color::new((p[0] as f64 + 0.5)*f,(p[1] as f64 + 0.5)*f,(p[2] as f64 + 0.5)*f)
+}
+
+// fewest bytes a scanline of width pixels takes : runs of 127 pixels for
+// every channel when it can be run length encoded, else flat pixels
+fn scanline_size(width:usize) -> Option<usize>
+{
+    if (8..0x8000).contains(&width) { Some(4 + 8*((width + 126)/127)) } else { width.checked_mul(4) }
+}
+
+// one scanline of width pixels starting at position
+fn scanline(data:&[u8],position:&mut usize,width:usize) -> Result<Vec<[u8;4]>,String>
+{
+    let mut pixels = vec![[0u8;4];width];
+    let start      = data.get(*position..*position+4).unwrap_or(&[]);
+    let encoded    = (8..0x8000).contains(&width) && start.len() == 4 && start[0] == 2 && start[1] == 2 && start[2] & 0x80 == 0;
+    if !encoded
+    {
+        for pixel in pixels.iter_mut()
+        {
+            for c in pixel.iter_mut() { *c = byte(data,position)?; }
+        }
+        return Ok(pixels);
+    }
+
+    if ((start[2] as usize) << 8 | start[3] as usize) != width
+    {
+        return Err(String::from("scanline length does not match the width"));
+    }
+    *position += 4;
+    for channel in 0..4
+    {
+        let mut x = 0;
+        while x < width
+        {
+            let count = byte(data,position)? as usize;
+            // above 128 a run of one value, else as many literal values
+            let (count,run) = if count > 128 { (count - 128,true) } else { (count,false) };
+            if count == 0 || x + count > width
+            {
+                return Err(String::from("run overflows the scanline"));
+            }
+            let value = if run { byte(data,position)? } else { 0 };
+            for pixel in &mut pixels[x..x+count]
+            {
+                pixel[channel] = if run { value } else { byte(data,position)? };
+            }
+            x += count;
+        }
+    }
+    Ok(pixels)
+}
+
+pub fn decode(data:&[u8]) -> Result<Image,String>
+{
+    if !data.starts_with(b"#?")
+    {
+        return Err(String::from("missing #? signature"));
+    }
+
+    // header lines up to an empty one, then the resolution
+    let mut position = 0;
+    loop {
+        let line = line(data,&mut position)?;
+        if line.is_empty() { break; }
+        if let Some(format) = line.strip_prefix("FORMAT=")
+        {
+            if format != "32-bit_rle_rgbe"
+            {
+                return Err(format!("unsupported format {}",format));
+            }
+        }
+    }
+    let resolution:Vec<&str> = line(data,&mut position)?.split_whitespace().collect();
+    let (height,width) = match resolution.as_slice() {
+        ["-Y",height,"+X",width] => (
+            height.parse::<usize>().map_err(|_| "invalid height")?,
+            width.parse::<usize>().map_err(|_| "invalid width")?
+        ),
+        _ => return Err(String::from("only -Y height +X width pictures are supported"))
+    };
+    if width == 0 || height == 0
+    {
+        return Err(String::from("picture has no pixel"));
+    }
+    match scanline_size(width).and_then(|size| size.checked_mul(height)) {
+        Some(size) if size <= data.len() - position => {},
+        _                                           => return Err(String::from("truncated pixels"))
+    }
+
+    let mut texels = Vec::with_capacity(width*height);
+    for _ in 0..height
+    {
+        texels.extend(scanline(data,&mut position,width)?.into_iter().map(from_rgbe));
+    }
+    Ok(Image{width,height,texels})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER:&[u8] = b"#?RADIANCE\n# made by hand\nFORMAT=32-bit_rle_rgbe\n\n";
+
+    #[test]
+    fn test_flat() {
+        let mut data = HEADER.to_vec();
+        data.extend_from_slice(b"-Y 2 +X 1\n");
+        data.extend_from_slice(&[128,64,0,129,0,0,0,0]);
+        let image = decode(&data).unwrap();
+
+        assert_eq!((1,2),(image.width,image.height));
+        assert!((image.texels[0].r-1.0).abs() < 0.01);
+        assert!((image.texels[0].g-0.5).abs() < 0.01);
+        assert!(image.texels[1].is_black());
+
+        assert!(decode(&data[..data.len()-1]).is_err());
+        assert!(decode(b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n\0\0\0\0").is_err());
+        assert!(decode(b"#?RADIANCE\n\n+Y 1 +X 1\n\0\0\0\0").is_err());
+        // a width whose flat scanline does not fit in memory
+        assert!(decode(b"#?RADIANCE\n\n-Y 2 +X 18446744073709551615\n\0\0\0\0").is_err());
+    }
+
+    #[test]
+    fn test_run_length() {
+        // eight pixels : red as one run, green as literals, then runs
+        let mut data = HEADER.to_vec();
+        data.extend_from_slice(b"-Y 1 +X 8\n");
+        data.extend_from_slice(&[2,2,0,8]);
+        data.extend_from_slice(&[136,128]);
+        data.extend_from_slice(&[8,0,16,32,48,64,80,96,112]);
+        data.extend_from_slice(&[132,0,132,255]);
+        data.extend_from_slice(&[136,129]);
+        let image = decode(&data).unwrap();
+
+        assert_eq!(8,image.texels.len());
+        assert!((image.texels[0].r-1.0).abs() < 0.01);
+        assert!((image.texels[4].g-0.5).abs() < 0.01);
+        assert!(image.texels[3].b < 0.01);
+        assert!(image.texels[4].b > 1.9);
+
+        // a run past the end of the scanline
+        let last = data.len() - 2;
+        data[last] = 137;
+        assert!(decode(&data).is_err());
+    }
+}
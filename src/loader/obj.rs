@@ -0,0 +1,395 @@
+// wavefront obj reader : positions, texture coordinates, normals, polygonal
+// faces split in triangle fans, groups and materials from mtl libraries
+use super::{Material,Rgb,Texture,Filtering,Wrap,read_file};
+use super::error::LoadError;
+use crate::math::vector3::Vec3;
+
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+pub struct Corner
+{
+    // 0-based indices in the tables of the obj
+    pub position:usize,
+    pub uv:Option<usize>,
+    pub normal:Option<usize>
+}
+
+pub struct Triangle
+{
+    pub corners:[Corner;3],
+    pub material:Option<String>,
+    // index in groups
+    pub group:usize
+}
+
+pub struct Obj
+{
+    pub positions:Vec<Vec3>,
+    pub uvs:Vec<(f64,f64)>,
+    pub normals:Vec<Vec3>,
+    // faces before the first g or o statement belong to "default"
+    pub groups:Vec<String>,
+    pub triangles:Vec<Triangle>,
+    pub materials:BTreeMap<String,Material>
+}
+
+// texture paths of the materials are made relative to the directory of the
+// obj file, where the mesh materials are resolved. Errors of a library are
+// located in the library, not in the obj file that names it.
+pub fn from_path(path:&Path) -> Result<Obj,LoadError>
+{
+    let directory    = path.parent().unwrap_or_else(|| Path::new(""));
+    let from_library = Cell::new(false);
+    parse(&read_file(path)?,|library| {
+        let file          = directory.join(library);
+        let mut materials = read_file(&file).and_then(|input| parse_mtl(&input).map_err(|e| located(&file,e)))
+            .map_err(|e| { from_library.set(true); e })?;
+        let relative      = Path::new(library).parent().unwrap_or_else(|| Path::new(""));
+        for material in materials.values_mut()
+        {
+            for texture in [&mut material.texture,&mut material.bump_map,&mut material.normal_map]
+            {
+                if let Some(Texture::Image{path,..}) = texture
+                {
+                    *path = relative.join(&*path).to_string_lossy().into_owned();
+                }
+            }
+        }
+        Ok(materials)
+    }).map_err(|e| if from_library.get() { e } else { located(path,e) })
+}
+
+// semantic errors are prefixed with the file they come from
+fn located(path:&Path,error:LoadError) -> LoadError
+{
+    match error {
+        LoadError::Semantic(message) => LoadError::Semantic(format!("{} {}",path.display(),message)),
+        error                        => error
+    }
+}
+
+fn numbers(fields:&[&str]) -> Option<Vec<f64>>
+{
+    fields.iter().map(|f| f.parse::<f64>().ok()).collect()
+}
+
+// 1-based index, negative ones counting back from the last element read
+fn resolve(index:&str,count:usize) -> Option<usize>
+{
+    let index = index.parse::<i64>().ok()?;
+    let resolved = if index > 0 { index - 1 } else { count as i64 + index };
+    if index == 0 || resolved < 0 || resolved >= count as i64 { return None; }
+    Some(resolved as usize)
+}
+
+// v, v/vt, v//vn or v/vt/vn
+fn corner(field:&str,obj:&Obj) -> Option<Corner>
+{
+    let mut parts  = field.split('/');
+    let position   = resolve(parts.next()?,obj.positions.len())?;
+    let uv         = match parts.next() {
+        None | Some("") => None,
+        Some(index)     => Some(resolve(index,obj.uvs.len())?)
+    };
+    let normal     = match parts.next() {
+        None | Some("") => None,
+        Some(index)     => Some(resolve(index,obj.normals.len())?)
+    };
+    Some(Corner{position,uv,normal})
+}
+
+// mtl libraries are read through load so that the parser does not depend
+// on the file system
+pub fn parse<F: Fn(&str) -> Result<BTreeMap<String,Material>,LoadError>>(input:&str,load:F) -> Result<Obj,LoadError>
+{
+    let mut obj = Obj{
+        positions:Vec::new(),
+        uvs:Vec::new(),
+        normals:Vec::new(),
+        groups:vec![String::from("default")],
+        triangles:Vec::new(),
+        materials:BTreeMap::new()
+    };
+    let mut material = None;
+    let mut group    = 0;
+
+    for (number,line) in input.lines().enumerate()
+    {
+        let invalid = |what:&str| LoadError::Semantic(format!("line {} : {}",number+1,what));
+        let line   = line.split('#').next().unwrap_or("");
+        let fields:Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() { continue; }
+
+        match fields[0] {
+            "v" => match numbers(&fields[1..]) {
+                Some(v) if v.len() >= 3 => obj.positions.push(Vec3::new(v[0],v[1],v[2])),
+                _                       => return Err(invalid("invalid vertex"))
+            },
+            "vt" => match numbers(&fields[1..]) {
+                Some(v) if !v.is_empty() => obj.uvs.push((v[0],*v.get(1).unwrap_or(&0.0))),
+                _                        => return Err(invalid("invalid texture coordinate"))
+            },
+            "vn" => match numbers(&fields[1..]) {
+                Some(v) if v.len() >= 3 => obj.normals.push(Vec3::new(v[0],v[1],v[2])),
+                _                       => return Err(invalid("invalid normal"))
+            },
+            "f" => {
+                let corners:Option<Vec<Corner>> = fields[1..].iter().map(|f| corner(f,&obj)).collect();
+                match corners {
+                    Some(corners) if corners.len() >= 3 => {
+                        let mut corners = corners.into_iter();
+                        let first       = corners.next().unwrap();
+                        let mut last    = corners.next().unwrap();
+                        for next in corners
+                        {
+                            let copy = |c:&Corner| Corner{position:c.position,uv:c.uv,normal:c.normal};
+                            obj.triangles.push(Triangle{
+                                corners:[copy(&first),copy(&last),copy(&next)],
+                                material:material.clone(),
+                                group
+                            });
+                            last = next;
+                        }
+                    },
+                    Some(_) => return Err(invalid("face with less than 3 vertices")),
+                    None    => return Err(invalid("face has invalid vertex reference"))
+                }
+            },
+            "g" | "o" => {
+                let name = fields[1..].join(" ");
+                group = match obj.groups.iter().position(|g| *g == name) {
+                    Some(index) => index,
+                    None        => { obj.groups.push(name); obj.groups.len()-1 }
+                };
+            },
+            "usemtl" => material = fields.get(1).map(|name| name.to_string()),
+            "mtllib" => for library in &fields[1..] {
+                obj.materials.append(&mut load(library)?);
+            },
+            // smoothing groups, lines, points and free form geometry
+            _ => {}
+        }
+    }
+    Ok(obj)
+}
+
+// the file name of a map after its options : only the bump multiplier -bm of
+// bump maps is read, as the bump height, the others (-s, -o, -clamp...) being
+// rejected
+fn map(fields:&[&str]) -> Result<(String,Option<f64>),String>
+{
+    let mut multiplier = None;
+    let mut rest       = &fields[1..];
+    while let Some(option) = rest.first().filter(|f| f.starts_with('-'))
+    {
+        match *option {
+            "-bm" if fields[0] != "map_Kd" && fields[0] != "norm" => {
+                multiplier = Some(rest.get(1).and_then(|v| v.parse::<f64>().ok())
+                    .ok_or_else(|| String::from("-bm needs a number"))?);
+                rest = &rest[2..];
+            },
+            _ => return Err(format!("unsupported {} option {}",fields[0],option))
+        }
+    }
+    if rest.is_empty() { return Err(format!("{} without a file name",fields[0])); }
+    Ok((rest.join(" "),multiplier))
+}
+
+// Kd, Ks, Ke, Ns, Ni, d (or Tr), map_Kd, bump (or map_Bump) and norm are mapped
+// to the scene materials, texture paths staying relative to the library
+pub fn parse_mtl(input:&str) -> Result<BTreeMap<String,Material>,LoadError>
+{
+    let mut materials = BTreeMap::<String,Material>::new();
+    let mut current:Option<String> = None;
+    // transmission filters, only turned into refraction by the illumination
+    // models 6 and 7 : d and Tr are a coverage, not glass
+    let mut filters    = BTreeMap::<String,f64>::new();
+    let mut refracting = Vec::<String>::new();
+
+    for (number,line) in input.lines().enumerate()
+    {
+        let invalid = |what:String| LoadError::Semantic(format!("line {} : {}",number+1,what));
+        let line   = line.split('#').next().unwrap_or("");
+        let fields:Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() { continue; }
+
+        if fields[0] == "newmtl"
+        {
+            let name = fields[1..].join(" ");
+            materials.insert(name.clone(),Material::default());
+            current = Some(name);
+            continue;
+        }
+        let name     = match &current {
+            Some(name) => name.clone(),
+            None       => continue
+        };
+        let material = match materials.get_mut(&name) {
+            Some(material) => material,
+            None           => continue
+        };
+        if let "map_Kd" | "bump" | "map_Bump" | "norm" = fields[0]
+        {
+            let (path,multiplier) = map(&fields).map_err(invalid)?;
+            let texture = Some(Texture::Image{path,filter:Filtering::default(),wrap:Wrap::default()});
+            match fields[0] {
+                "map_Kd" => material.texture    = texture,
+                "norm"   => material.normal_map = texture,
+                _        => {
+                    material.bump_map = texture;
+                    if let Some(multiplier) = multiplier { material.bump_height = multiplier; }
+                }
+            }
+            continue;
+        }
+        let values = match numbers(&fields[1..]) {
+            Some(values) if !values.is_empty() => values,
+            _                                  => continue
+        };
+        let rgb = || Rgb(values[0],*values.get(1).unwrap_or(&values[0]),*values.get(2).unwrap_or(&values[0]));
+        match fields[0] {
+            "Kd" => material.diffuse      = rgb(),
+            "Ks" => material.specular     = rgb(),
+            "Ke" => material.emission     = rgb(),
+            "Ns" => material.shininess    = values[0],
+            // exporters write Ni 0 for materials that do not refract
+            "Ni" if values[0] > 0.0 => material.ior = values[0],
+            "d"  => material.opacity      = values[0],
+            "Tr" => material.opacity      = 1.0 - values[0],
+            "Tf" => { filters.insert(name,values.iter().copied().fold(0.0,f64::max)); },
+            "illum" if values[0] == 6.0 || values[0] == 7.0 => refracting.push(name),
+            _    => {}
+        }
+    }
+    for name in refracting
+    {
+        if let Some(material) = materials.get_mut(&name)
+        {
+            material.transparency = filters.get(&name).copied().unwrap_or(1.0);
+        }
+    }
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn no_library(_:&str) -> Result<BTreeMap<String,Material>,LoadError> { Ok(BTreeMap::new()) }
+
+    #[test]
+    fn test_quad_is_split() {
+        let obj = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nf 1 2 3 4\n",no_library).unwrap();
+
+        assert_eq!(4,obj.positions.len());
+        assert_eq!(2,obj.triangles.len());
+        let second:Vec<usize> = obj.triangles[1].corners.iter().map(|c| c.position).collect();
+        assert_eq!(vec![0,2,3],second);
+    }
+
+    #[test]
+    fn test_negative_indices() {
+        let obj = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nvt 0 0\nvt 1 0\nvt 1 1\nvn 0 0 1\nf -3/-3/-1 -2/-2/-1 -1/-1/-1\n",no_library).unwrap();
+
+        let corners = &obj.triangles[0].corners;
+        assert_eq!(0,corners[0].position);
+        assert_eq!(2,corners[2].position);
+        assert_eq!(Some(1),corners[1].uv);
+        assert_eq!(Some(0),corners[2].normal);
+    }
+
+    #[test]
+    fn test_normal_without_uv() {
+        let obj = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nvn 0 0 1\nf 1//1 2//1 3//1\n",no_library).unwrap();
+
+        assert_eq!(None,obj.triangles[0].corners[0].uv);
+        assert_eq!(Some(0),obj.triangles[0].corners[0].normal);
+    }
+
+    #[test]
+    fn test_invalid_faces() {
+        let vertices = "v 0 0 0\nv 1 0 0\nv 1 1 0\n";
+        for face in &["f 1 2 4","f 0 1 2","f 1 2","f 1 -4 2","f 1/1 2 3"]
+        {
+            match parse(&format!("{}{}\n",vertices,face),no_library) {
+                Err(LoadError::Semantic(message)) => assert!(message.starts_with("line 4 "),"{}",message),
+                _                                 => panic!("{} accepted",face)
+            }
+        }
+    }
+
+    #[test]
+    fn test_groups_and_materials() {
+        let input = "mtllib scene.mtl\nv 0 0 0\nv 1 0 0\nv 1 1 0\nf 1 2 3\ng lid\nusemtl red\nf 1 2 3\ng body\nf 3 2 1\ng lid\nf 2 3 1\n";
+        let obj   = parse(input,|library| {
+            assert_eq!("scene.mtl",library);
+            parse_mtl("newmtl red\nKd 1 0 0\nKe 2\nNs 10\nd 0.25\nmap_Kd textures/red.png\nbump -bm 0.5 textures/red_bump.png\nnorm textures/red_normal.png\n")
+        }).unwrap();
+
+        assert_eq!(vec!["default","lid","body"],obj.groups);
+        let groups:Vec<usize> = obj.triangles.iter().map(|t| t.group).collect();
+        assert_eq!(vec![0,1,2,1],groups);
+        assert_eq!(None,obj.triangles[0].material);
+        assert_eq!(Some(String::from("red")),obj.triangles[3].material);
+
+        let red = &obj.materials["red"];
+        assert_eq!(Rgb(1.0,0.0,0.0),red.diffuse);
+        assert_eq!(Rgb(2.0,2.0,2.0),red.emission);
+        assert_eq!(10.0,red.shininess);
+        assert_eq!(0.25,red.opacity);
+        assert_eq!(0.0,red.transparency);
+        match &red.texture {
+            Some(Texture::Image{path,..}) => assert_eq!("textures/red.png",path),
+            _                             => panic!("map_Kd ignored")
+        }
+        match (&red.bump_map,&red.normal_map) {
+            (Some(Texture::Image{path:bump,..}),Some(Texture::Image{path:normal,..})) => {
+                assert_eq!("textures/red_bump.png",bump);
+                assert_eq!("textures/red_normal.png",normal);
+                assert_eq!(0.5,red.bump_height);
+            },
+            _ => panic!("bump or norm ignored")
+        }
+    }
+
+    #[test]
+    fn test_map_options() {
+        for line in &["map_Kd -s 2 2 1 red.png","map_Kd -o 0.5 0 0 red.png","norm -bm 2 red.png","bump -bm red.png","bump -clamp on red.png","map_Kd"]
+        {
+            match parse_mtl(&format!("newmtl red\n{}\n",line)) {
+                Err(LoadError::Semantic(message)) => assert!(message.starts_with("line 2 "),"{}",message),
+                _                                 => panic!("{} accepted",line)
+            }
+        }
+        let materials = parse_mtl("newmtl red\nmap_Kd my texture.png\n").unwrap();
+        match &materials["red"].texture {
+            Some(Texture::Image{path,..}) => assert_eq!("my texture.png",path),
+            _                             => panic!("map_Kd ignored")
+        }
+    }
+
+    #[test]
+    fn test_dissolve_and_refraction() {
+        let materials = parse_mtl("newmtl lace
+Tr 0.6
+Tf 0.9 0.9 0.9
+newmtl glass
+Ni 1.33
+Tf 0.2 0.8 0.4
+illum 7
+newmtl clear
+illum 6
+d 1
+").unwrap();
+
+        // dissolve is a coverage, the transmission filter needs refraction
+        assert!((materials["lace"].opacity-0.4).abs() < 1e-12);
+        assert_eq!(0.0,materials["lace"].transparency);
+        assert_eq!(1.0,materials["glass"].opacity);
+        assert_eq!(0.8,materials["glass"].transparency);
+        assert_eq!(1.33,materials["glass"].ior);
+        assert_eq!(1.0,materials["clear"].transparency);
+    }
+}
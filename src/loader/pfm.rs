@@ -0,0 +1,115 @@
+// portable float map reader : PF for color and Pf for grey pictures, 32
+// bits floats stored bottom row first, little endian when the scale is
+// negative
+use crate::engine::color::Color;
+use crate::engine::texture::Image;
+
+// next whitespace separated word of the header and the single whitespace
+// following it
+fn word<'a>(data:&'a [u8],position:&mut usize) -> Result<&'a str,String>
+{
+    while data.get(*position).map_or(false,|b| b.is_ascii_whitespace()) { *position += 1; }
+    let start = *position;
+    while data.get(*position).map_or(false,|b| !b.is_ascii_whitespace()) { *position += 1; }
+    if *position >= data.len()
+    {
+        return Err(String::from("truncated header"));
+    }
+    let word = std::str::from_utf8(&data[start..*position]).map_err(|_| "header is not text")?;
+    *position += 1;
+    Ok(word)
+}
+
+pub fn decode(data:&[u8]) -> Result<Image,String>
+{
+    let mut position = 0;
+    let channels = match word(data,&mut position)? {
+        "PF" => 3,
+        "Pf" => 1,
+        _    => return Err(String::from("missing PF or Pf signature"))
+    };
+    let width  = word(data,&mut position)?.parse::<usize>().map_err(|_| "invalid width")?;
+    let height = word(data,&mut position)?.parse::<usize>().map_err(|_| "invalid height")?;
+    let scale  = word(data,&mut position)?.parse::<f64>().map_err(|_| "invalid scale")?;
+    if width == 0 || height == 0
+    {
+        return Err(String::from("picture has no pixel"));
+    }
+
+    let floats = &data[position..];
+    match width.checked_mul(height).and_then(|n| n.checked_mul(channels*4)) {
+        Some(size) if size <= floats.len() => {},
+        _                                  => return Err(String::from("truncated pixels"))
+    }
+    // radiance is neither negative nor infinite, such texels are black
+    let value = |i:usize| {
+        let bytes = [floats[4*i],floats[4*i+1],floats[4*i+2],floats[4*i+3]];
+        let value = (if scale < 0.0 { f32::from_le_bytes(bytes) } else { f32::from_be_bytes(bytes) }) as f64;
+        if value.is_finite() && value > 0.0 { value } else { 0.0 }
+    };
+
+    let mut texels = Vec::with_capacity(width*height);
+    for y in (0..height).rev()
+    {
+        for x in 0..width
+        {
+            let i = (y*width + x)*channels;
+            texels.push(match channels {
+                3 => Color::new(value(i),value(i+1),value(i+2)),
+                _ => Color::new(value(i),value(i),value(i))
+            });
+        }
+    }
+    Ok(Image{width,height,texels})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_color() {
+        let mut data = b"PF\n2 1\n-1.0\n".to_vec();
+        for v in &[1.0f32,0.5,0.25,4.0,0.0,2.0]
+        {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        let image = decode(&data).unwrap();
+
+        assert_eq!((2,1),(image.width,image.height));
+        assert_eq!(Color::new(1.0,0.5,0.25),image.texels[0]);
+        assert_eq!(Color::new(4.0,0.0,2.0),image.texels[1]);
+
+        assert!(decode(&data[..data.len()-1]).is_err());
+        assert!(decode(b"P6\n1 1\n255\n\0\0\0").is_err());
+        // width times height overflows
+        assert!(decode(b"Pf\n4294967296 4294967296\n-1.0\n\0\0\0\0").is_err());
+    }
+
+    #[test]
+    fn test_invalid_texels() {
+        let mut data = b"PF\n2 1\n-1.0\n".to_vec();
+        for v in &[f32::NAN,f32::INFINITY,-1.0,f32::NEG_INFINITY,0.5,2.0]
+        {
+            data.extend_from_slice(&v.to_le_bytes());
+        }
+        let image = decode(&data).unwrap();
+
+        assert!(image.texels[0].is_black());
+        assert_eq!(Color::new(0.0,0.5,2.0),image.texels[1]);
+    }
+
+    #[test]
+    fn test_grey() {
+        // big endian, the bottom row comes first
+        let mut data = b"Pf 1 2 1.0\n".to_vec();
+        for v in &[3.0f32,7.0]
+        {
+            data.extend_from_slice(&v.to_be_bytes());
+        }
+        let image = decode(&data).unwrap();
+
+        assert_eq!(Color::new(7.0,7.0,7.0),image.texels[0]);
+        assert_eq!(Color::new(3.0,3.0,3.0),image.texels[1]);
+    }
+}
@@ -1,18 +1,74 @@
-mod loader;
-mod math;
-mod engine;
+mod cli;
 
-use std::time::{Duration, Instant};
+use ray_tracer::loader;
+use ray_tracer::writer;
+use std::path::Path;
+use std::process::exit;
+use std::time::Instant;
+
+fn main()
+{
+	let options = match cli::parse(std::env::args().skip(1)) {
+		Ok(options) => options,
+		Err(error)  => {
+			eprintln!("{}\n\n{}",error,cli::USAGE);
+			exit(cli::EXIT_USAGE);
+		}
+	};
+	if options.help
+	{
+		println!("{}",cli::USAGE);
+		return;
+	}
+
+	let loaded = loader::from_path(&options.scene).and_then(|mut config| {
+		if let Some(width)   = options.width   { config.camera.screen.width  = width; }
+		if let Some(height)  = options.height  { config.camera.screen.height = height; }
+		if let Some(fov)     = options.fov     { config.camera.fov           = fov; }
+		if let Some(samples) = options.samples { config.render.samples       = samples; }
+		let (tracer,scene) = loader::to_engine(&config)?;
+		Ok((config,tracer,scene))
+	});
+	let (config,mut tracer,scene) = match loaded {
+		Ok(loaded) => loaded,
+		Err(error) => {
+			eprintln!("{}",error);
+			exit(cli::EXIT_LOAD);
+		}
+	};
+	tracer.quiet = options.quiet;
 
-fn main() 
-{   
-	let config = loader::from_path("in.yml");
-	let (tracer,mesh) = loader::to_engine(&config);
 	let now = Instant::now();
-    let pixels = tracer.render(&mesh);
-    println!("Rendering time : {}s", now.elapsed().as_secs());
-    let w = config.camera.screen.width;
-    let h = config.camera.screen.height;
+    let image = match tracer.render(&scene) {
+        Ok(image)  => image,
+        Err(error) => {
+            eprintln!("{}",error);
+            exit(cli::EXIT_RENDER);
+        }
+    };
+    if !options.quiet { println!("Rendering time : {}s", now.elapsed().as_secs()); }
 
-    lodepng::encode32_file("out.png", &pixels,w,h).expect("Cannot write output image");
-}
\ No newline at end of file
+    let output    = Path::new(&options.output);
+    let passes    = config.output.passes();
+    let precision = config.output.precision();
+    let written   = if config.output.layers && writer::Format::from_path(output) == writer::Format::Exr
+    {
+        writer::write_layers(output,&image,&tracer.output,precision,&passes).map_err(|e| (output.to_path_buf(),e))
+    }
+    else
+    {
+        writer::write(output,&image,&tracer.output,precision).map_err(|e| (output.to_path_buf(),e)).and_then(|_| {
+            for pass in &passes
+            {
+                let path = writer::pass_path(output,*pass);
+                writer::write_pass(&path,&image,*pass,precision).map_err(|e| (path,e))?;
+            }
+            Ok(())
+        })
+    };
+    if let Err((path,error)) = written
+    {
+        eprintln!("unable to write {} : {}",path.display(),error);
+        exit(cli::EXIT_OUTPUT);
+    }
+}
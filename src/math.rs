@@ -1,3 +1,7 @@
 pub mod vector3;
 pub mod matrix3;
 pub mod matrix4;
+pub mod aabb;
+pub mod random;
+pub mod noise;
+pub mod distribution;
\ No newline at end of file
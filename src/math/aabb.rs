@@ -0,0 +1,167 @@
+use crate::math::vector3::Vec3;
+
+#[derive(Debug, Clone)]
+pub struct Aabb
+{
+	pub min:Vec3,
+	pub max:Vec3,
+}
+
+impl Aabb {
+	pub fn new(min:&Vec3,max:&Vec3) -> Aabb { Aabb{min:min.clone(),max:max.clone()} }
+	pub fn empty() -> Aabb
+	{
+		Aabb::new(
+			&Vec3::new( f64::INFINITY, f64::INFINITY, f64::INFINITY),
+			&Vec3::new(-f64::INFINITY,-f64::INFINITY,-f64::INFINITY)
+		)
+	}
+	pub fn from_triangle(triangle:&[Vec3;3]) -> Aabb
+	{
+		let mut aabb = Aabb::empty();
+		for v in triangle
+		{
+			aabb = aabb.grow(v);
+		}
+		aabb
+	}
+	pub fn is_empty(&self) -> bool
+	{
+		self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+	}
+	pub fn grow(&self, p:&Vec3) -> Aabb
+	{
+		Aabb::new(
+			&Vec3::new(self.min.x.min(p.x),self.min.y.min(p.y),self.min.z.min(p.z)),
+			&Vec3::new(self.max.x.max(p.x),self.max.y.max(p.y),self.max.z.max(p.z))
+		)
+	}
+	pub fn union(&self, b:&Aabb) -> Aabb
+	{
+		self.grow(&b.min).grow(&b.max)
+	}
+	pub fn centroid(&self) -> Vec3
+	{
+		self.min.add(&self.max).mul(0.5)
+	}
+	pub fn extent(&self) -> Vec3
+	{
+		self.max.sub(&self.min)
+	}
+	pub fn surface_area(&self) -> f64
+	{
+		if self.is_empty() { return 0.0; }
+		let e = self.extent();
+		2.0*(e.x*e.y + e.y*e.z + e.z*e.x)
+	}
+
+	// slab test : inv_ray holds 1/ray on each axis so that axis aligned rays
+	// give infinite slopes instead of a division by zero
+	pub fn intersect(&self, origin:&Vec3, inv_ray:&Vec3, t_max:f64) -> Option<f64>
+	{
+		let mut t_near = 0.0_f64;
+		let mut t_far  = t_max;
+
+		let axes = [
+			(self.min.x,self.max.x,origin.x,inv_ray.x),
+			(self.min.y,self.max.y,origin.y,inv_ray.y),
+			(self.min.z,self.max.z,origin.z,inv_ray.z),
+		];
+
+		for (min,max,o,inv) in axes.iter()
+		{
+			let t0 = (min - o)*inv;
+			let t1 = (max - o)*inv;
+			let (t0,t1) = if t0 > t1 { (t1,t0) } else { (t0,t1) };
+
+			// a NaN only shows up when the origin lies on a slab plane with a
+			// null direction on that axis, in which case the slab is not limiting
+			if t0 > t_near { t_near = t0; }
+			if t1 < t_far  { t_far  = t1; }
+			if t_near > t_far { return None; }
+		}
+		Some(t_near)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_empty() {
+		let b = Aabb::empty();
+
+		assert!(b.is_empty());
+		assert_eq!(0.0,b.surface_area());
+	}
+
+	#[test]
+	fn test_from_triangle() {
+		let triangle = [
+			Vec3::new( 1.0, 2.0, 3.0),
+			Vec3::new(-1.0, 5.0, 0.0),
+			Vec3::new( 4.0,-2.0, 1.0)
+		];
+		let b = Aabb::from_triangle(&triangle);
+
+		assert_eq!(-1.0,b.min.x);
+		assert_eq!(-2.0,b.min.y);
+		assert_eq!( 0.0,b.min.z);
+		assert_eq!( 4.0,b.max.x);
+		assert_eq!( 5.0,b.max.y);
+		assert_eq!( 3.0,b.max.z);
+	}
+
+	#[test]
+	fn test_union() {
+		let b1 = Aabb::new(&Vec3::new(0.0,0.0,0.0),&Vec3::new(1.0,1.0,1.0));
+		let b2 = Aabb::new(&Vec3::new(2.0,-1.0,0.5),&Vec3::new(3.0,0.5,0.7));
+		let b  = b1.union(&b2);
+
+		assert_eq!( 0.0,b.min.x);
+		assert_eq!(-1.0,b.min.y);
+		assert_eq!( 0.0,b.min.z);
+		assert_eq!( 3.0,b.max.x);
+		assert_eq!( 1.0,b.max.y);
+		assert_eq!( 1.0,b.max.z);
+	}
+
+	#[test]
+	fn test_surface_area() {
+		let b = Aabb::new(&Vec3::new(0.0,0.0,0.0),&Vec3::new(1.0,2.0,3.0));
+
+		assert_eq!(22.0,b.surface_area());
+	}
+
+	#[test]
+	fn test_intersect() {
+		let b       = Aabb::new(&Vec3::new(-1.0,-1.0,-1.0),&Vec3::new(1.0,1.0,1.0));
+		let origin  = Vec3::new(0.0,0.0,-5.0);
+		let ray     = Vec3::new(0.0,0.0,2.0);
+		let inv_ray = Vec3::new(1.0/ray.x,1.0/ray.y,1.0/ray.z);
+
+		assert_eq!(Some(2.0),b.intersect(&origin,&inv_ray,10.0));
+		assert_eq!(None,b.intersect(&origin,&inv_ray,1.0));
+	}
+
+	#[test]
+	fn test_intersect_miss() {
+		let b       = Aabb::new(&Vec3::new(-1.0,-1.0,-1.0),&Vec3::new(1.0,1.0,1.0));
+		let origin  = Vec3::new(0.0,3.0,-5.0);
+		let ray     = Vec3::new(0.0,0.0,1.0);
+		let inv_ray = Vec3::new(1.0/ray.x,1.0/ray.y,1.0/ray.z);
+
+		assert_eq!(None,b.intersect(&origin,&inv_ray,100.0));
+	}
+
+	#[test]
+	fn test_intersect_from_inside() {
+		let b       = Aabb::new(&Vec3::new(-1.0,-1.0,-1.0),&Vec3::new(1.0,1.0,1.0));
+		let origin  = Vec3::new(0.0,0.0,0.0);
+		let ray     = Vec3::new(1.0,1.0,0.0);
+		let inv_ray = Vec3::new(1.0/ray.x,1.0/ray.y,1.0/ray.z);
+
+		assert_eq!(Some(0.0),b.intersect(&origin,&inv_ray,100.0));
+	}
+}
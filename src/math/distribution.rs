@@ -0,0 +1,123 @@
+// piecewise constant density over [0,1) made of cells proportional to the
+// given values, uniform when they are all zero
+pub struct Distribution1d
+{
+	values:Vec<f64>,
+	// cumulated share of the cells, from 0 to 1
+	cdf:Vec<f64>,
+	// mean of the values
+	pub integral:f64
+}
+
+impl Distribution1d {
+	pub fn new(values:Vec<f64>) -> Distribution1d
+	{
+		let n       = values.len() as f64;
+		let mut cdf = vec![0.0];
+		for value in &values
+		{
+			cdf.push(cdf[cdf.len()-1] + value.max(0.0)/n);
+		}
+		let integral = cdf[cdf.len()-1];
+		for (i,c) in cdf.iter_mut().enumerate()
+		{
+			*c = if integral > 0.0 { *c/integral } else { (i as f64)/n };
+		}
+		Distribution1d{values,cdf,integral}
+	}
+
+	// x drawn with the density for u uniform in [0,1), with its cell and
+	// its density
+	pub fn sample(&self, u:f64) -> (f64,usize,f64)
+	{
+		let cell  = (self.cdf.partition_point(|c| *c <= u).max(1) - 1).min(self.values.len() - 1);
+		let width = self.cdf[cell+1] - self.cdf[cell];
+		let t     = if width > 0.0 { (u - self.cdf[cell])/width } else { 0.0 };
+		let x     = ((cell as f64) + t)/(self.values.len() as f64);
+		(x.min(1.0 - f64::EPSILON),cell,self.pdf(x))
+	}
+
+	pub fn pdf(&self, x:f64) -> f64
+	{
+		let n    = self.values.len();
+		let cell = ((x*(n as f64)) as usize).min(n - 1);
+		if self.integral > 0.0 { self.values[cell].max(0.0)/self.integral } else { 1.0 }
+	}
+}
+
+// piecewise constant density over [0,1)^2 : a row is drawn along v by the
+// marginal density, then a point of the row along u
+pub struct Distribution2d
+{
+	rows:Vec<Distribution1d>,
+	marginal:Distribution1d
+}
+
+impl Distribution2d {
+	// values of width cells per row, row after row
+	pub fn new(values:&[f64], width:usize, height:usize) -> Distribution2d
+	{
+		let rows:Vec<Distribution1d> = (0..height).map(|y| Distribution1d::new(values[y*width..(y+1)*width].to_vec())).collect();
+		let marginal = Distribution1d::new(rows.iter().map(|row| row.integral).collect());
+		Distribution2d{rows,marginal}
+	}
+
+	// (u,v) drawn with the density and its density
+	pub fn sample(&self, u1:f64, u2:f64) -> ((f64,f64),f64)
+	{
+		let (v,row,pdf_v) = self.marginal.sample(u2);
+		let (u,_,pdf_u)   = self.rows[row].sample(u1);
+		((u,v),pdf_u*pdf_v)
+	}
+
+	pub fn pdf(&self, u:f64, v:f64) -> f64
+	{
+		let row = ((v*(self.rows.len() as f64)) as usize).min(self.rows.len() - 1);
+		self.marginal.pdf(v)*self.rows[row].pdf(u)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::math::random::Rng;
+
+	#[test]
+	fn test_distribution1d() {
+		let d = Distribution1d::new(vec![1.0,0.0,3.0]);
+
+		assert!((d.integral-4.0/3.0).abs() < 1e-12);
+		assert_eq!(0.75,d.pdf(0.1));
+		assert_eq!(0.0,d.pdf(0.5));
+		assert_eq!(2.25,d.pdf(0.9));
+
+		// a quarter of the samples in the first cell, none in the empty one
+		let (x,cell,pdf) = d.sample(0.125);
+		assert!((x-1.0/6.0).abs() < 1e-12);
+		assert_eq!((0,0.75),(cell,pdf));
+		let (x,cell,_) = d.sample(0.25);
+		assert!((x-2.0/3.0).abs() < 1e-12);
+		assert_eq!(2,cell);
+		assert!(d.sample(0.999999).0 < 1.0);
+
+		let flat = Distribution1d::new(vec![0.0,0.0]);
+		assert_eq!(1.0,flat.pdf(0.3));
+		assert!((flat.sample(0.75).0-0.75).abs() < 1e-12);
+	}
+
+	#[test]
+	fn test_distribution2d() {
+		// a bright cell in the bottom right corner
+		let d = Distribution2d::new(&[1.0,1.0,1.0,5.0],2,2);
+		let mut rng = Rng::new(4,4);
+		let mut count = 0;
+		for _ in 0..10000
+		{
+			let ((u,v),pdf) = d.sample(rng.next_f64(),rng.next_f64());
+			assert!((pdf-d.pdf(u,v)).abs() < 1e-12);
+			if u >= 0.5 && v >= 0.5 { count += 1; }
+		}
+		assert!((count as f64/10000.0-0.625).abs() < 0.02,"{}",count);
+		assert_eq!(2.5,d.pdf(0.75,0.75));
+	}
+}
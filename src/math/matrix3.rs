@@ -1,11 +1,16 @@
 use crate::math::vector3::Vec3;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Mat3
 {
 	pub coef:[f64;9]
 }
 
+impl Default for Mat3
+{
+	fn default() -> Mat3 { Mat3::new() }
+}
+
 impl Mat3 {
 	pub fn new() -> Mat3
 	{
@@ -16,10 +21,7 @@ impl Mat3 {
 	pub fn from(coef:&[f64;9]) -> Mat3
 	{
 		let mut mat = Mat3::new();
-		for i in 0..9
-		{
-			mat.coef[i] = coef[i];
-		}
+		mat.coef.copy_from_slice(coef);
 		mat
 	}
     pub fn identity() -> Mat3
@@ -38,10 +40,6 @@ impl Mat3 {
 			v1.z, v2.z, v3.z,
 		])
 	}
-    pub fn clone(&self) -> Mat3 
-    { 
-    	Mat3::from(&self.coef)
-    }
 
     pub fn add(& self,m:&Mat3) -> Mat3
     {
@@ -127,13 +125,14 @@ impl Mat3 {
 			self.coef[0]*self.coef[4]-self.coef[1]*self.coef[3]
     	]);
 		inv = inv.div(det);
-		return Some(inv);
+		Some(inv)
     }
 }
 
 
 
 #[cfg(test)]
+#[allow(clippy::needless_range_loop)]
 mod tests {
 	use super::*;
 
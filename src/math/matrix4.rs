@@ -1,11 +1,16 @@
 use crate::math::vector3::Vec3;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Mat4
 {
 	pub coef:[f64;16]
 }
 
+impl Default for Mat4
+{
+	fn default() -> Mat4 { Mat4::new() }
+}
+
 impl Mat4 {
 	pub fn new() -> Mat4
 	{
@@ -16,10 +21,7 @@ impl Mat4 {
 	pub fn from(coef:&[f64;16]) -> Mat4
 	{
 		let mut mat = Mat4::new();
-		for i in 0..16
-		{
-			mat.coef[i] = coef[i];
-		}
+		mat.coef.copy_from_slice(coef);
 		mat
 	}
     pub fn identity() -> Mat4
@@ -77,10 +79,6 @@ impl Mat4 {
 			0.0, 0.0, 0.0, 1.0
 		])
     }
-    pub fn clone(&self) -> Mat4 
-    { 
-    	Mat4::from(&self.coef)
-    }
     pub fn add(&self,m:&Mat4) -> Mat4
     {
     	let mut out = Mat4::new();
@@ -107,6 +105,8 @@ impl Mat4 {
 		out
     }
 
+    // rows are spelled 4 * row, the first two included
+    #[allow(clippy::erasing_op, clippy::identity_op)]
     pub fn mul_vec3(&self,v:&Vec3) -> Vec3
     {
 		let mut v_as_mat = Mat4::new();
@@ -130,6 +130,7 @@ impl Mat4 {
 
 
 #[cfg(test)]
+#[allow(clippy::needless_range_loop)]
 mod tests {
 	use super::*;
 
@@ -0,0 +1,242 @@
+use crate::math::vector3::Vec3;
+
+// Gradient and cellular noises of a 3d point. The lattice is hashed instead
+// of read from a permutation table so that the noise never repeats.
+
+// murmur3 finalizer, every bit of the input flips half of the output
+fn mix(h:u32) -> u32
+{
+	let mut h = h;
+	h ^= h >> 16;
+	h  = h.wrapping_mul(0x85eb_ca6b);
+	h ^= h >> 13;
+	h  = h.wrapping_mul(0xc2b2_ae35);
+	h ^ (h >> 16)
+}
+
+fn hash(i:i64,j:i64,k:i64) -> u32
+{
+	mix((i as u32).wrapping_mul(0x8da6_b343) ^ (j as u32).wrapping_mul(0xd816_3841) ^ (k as u32).wrapping_mul(0xcb1a_b31f))
+}
+
+// uniform in [0,1)
+fn unit(h:u32) -> f64
+{
+	(h as f64)/4294967296.0
+}
+
+// dot product with one of the 12 directions to the edges of a cube, picked
+// by the hash as in Perlin's improved noise
+fn gradient(h:u32,x:f64,y:f64,z:f64) -> f64
+{
+	let h = h & 15;
+	let u = if h < 8 { x } else { y };
+	let v = if h < 4 { y } else if h == 12 || h == 14 { x } else { z };
+	(if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+}
+
+fn fade(t:f64) -> f64
+{
+	t*t*t*(t*(t*6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t:f64,a:f64,b:f64) -> f64
+{
+	a + t*(b - a)
+}
+
+// Perlin's improved noise, in [-1,1] and 0 on the integer lattice
+pub fn perlin(p:&Vec3) -> f64
+{
+	let (fx,fy,fz) = (p.x.floor(),p.y.floor(),p.z.floor());
+	let (x,y,z)    = (p.x - fx,p.y - fy,p.z - fz);
+	let (i,j,k)    = (fx as i64,fy as i64,fz as i64);
+	let (u,v,w)    = (fade(x),fade(y),fade(z));
+
+	let corner = |di:i64,dj:i64,dk:i64| gradient(hash(i+di,j+dj,k+dk),x - di as f64,y - dj as f64,z - dk as f64);
+	lerp(w,
+		lerp(v,lerp(u,corner(0,0,0),corner(1,0,0)),lerp(u,corner(0,1,0),corner(1,1,0))),
+		lerp(v,lerp(u,corner(0,0,1),corner(1,0,1)),lerp(u,corner(0,1,1),corner(1,1,1)))
+	)
+}
+
+// Perlin's simplex noise after Gustavson's notes : the gradients of the 4
+// corners of the tetrahedron holding the point, in [-1,1]
+pub fn simplex(p:&Vec3) -> f64
+{
+	const F3:f64 = 1.0/3.0;
+	const G3:f64 = 1.0/6.0;
+
+	// skew to find the cube of the lattice, then unskew back
+	let s       = (p.x + p.y + p.z)*F3;
+	let (i,j,k) = ((p.x + s).floor(),(p.y + s).floor(),(p.z + s).floor());
+	let t       = (i + j + k)*G3;
+	let x0      = [p.x - (i - t),p.y - (j - t),p.z - (k - t)];
+
+	// the cube is split in 6 tetrahedra along the order of the coordinates
+	let (o1,o2) = if x0[0] >= x0[1] {
+		if x0[1] >= x0[2]      { ([1,0,0],[1,1,0]) }
+		else if x0[0] >= x0[2] { ([1,0,0],[1,0,1]) }
+		else                   { ([0,0,1],[1,0,1]) }
+	} else if x0[1] < x0[2]    { ([0,0,1],[0,1,1]) }
+	else if x0[0] < x0[2]      { ([0,1,0],[0,1,1]) }
+	else                       { ([0,1,0],[1,1,0]) };
+
+	let (i,j,k) = (i as i64,j as i64,k as i64);
+	let corners = [([0,0,0],0.0),(o1,G3),(o2,2.0*G3),([1,1,1],3.0*G3)];
+	let mut n = 0.0;
+	for (o,g) in &corners
+	{
+		let x = x0[0] - o[0] as f64 + g;
+		let y = x0[1] - o[1] as f64 + g;
+		let z = x0[2] - o[2] as f64 + g;
+		let falloff = 0.6 - x*x - y*y - z*z;
+		if falloff <= 0.0 { continue; }
+		n += falloff.powi(4)*gradient(hash(i+o[0],j+o[1],k+o[2]),x,y,z);
+	}
+	32.0*n
+}
+
+// Worley's cellular noise : distance to the closest of the points scattered
+// one per cell of the lattice, in [0,sqrt(3)] but mostly below 1
+pub fn worley(p:&Vec3) -> f64
+{
+	let (i,j,k) = (p.x.floor() as i64,p.y.floor() as i64,p.z.floor() as i64);
+	let mut closest = f64::INFINITY;
+	for di in -1..=1
+	{
+		for dj in -1..=1
+		{
+			for dk in -1..=1
+			{
+				let (ci,cj,ck) = (i+di,j+dj,k+dk);
+				let h       = hash(ci,cj,ck);
+				let feature = Vec3::new(ci as f64 + unit(h),cj as f64 + unit(mix(h)),ck as f64 + unit(mix(mix(h))));
+				closest     = closest.min(feature.sub(p).norm());
+			}
+		}
+	}
+	closest
+}
+
+// layers of noise adding finer and finer details
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Octaves
+{
+	pub count:u32,
+	// frequency ratio between two octaves
+	pub lacunarity:f64,
+	// amplitude ratio between two octaves
+	pub gain:f64
+}
+
+impl Default for Octaves {
+	fn default() -> Octaves
+	{
+		Octaves{count:6,lacunarity:2.0,gain:0.5}
+	}
+}
+
+impl Octaves {
+	fn sum(&self,p:&Vec3,noise:impl Fn(&Vec3) -> f64) -> f64
+	{
+		let mut total     = 0.0;
+		let mut amplitude = 1.0;
+		let mut frequency = 1.0;
+		for _ in 0..self.count
+		{
+			total     += amplitude*noise(&p.mul(frequency));
+			amplitude *= self.gain;
+			frequency *= self.lacunarity;
+		}
+		total
+	}
+}
+
+// fractal brownian motion : perlin noise summed over the octaves
+pub fn fbm(p:&Vec3,octaves:&Octaves) -> f64
+{
+	octaves.sum(p,perlin)
+}
+
+// sum of the absolute values of the octaves, creased where the noise is 0
+pub fn turbulence(p:&Vec3,octaves:&Octaves) -> f64
+{
+	octaves.sum(p,|p| perlin(p).abs())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// points spread over a few cells of the lattice
+	fn points() -> Vec<Vec3>
+	{
+		(0..2000).map(|n| {
+			let n = n as f64;
+			Vec3::new((n*0.618).sin()*7.3,(n*0.377).cos()*5.1,n*0.013 - 9.0)
+		}).collect()
+	}
+
+	#[test]
+	fn test_perlin() {
+		// zero on the lattice, continuous and bounded elsewhere
+		assert_eq!(0.0,perlin(&Vec3::new(3.0,-2.0,5.0)));
+		let mut spread = 0.0_f64;
+		for p in points()
+		{
+			let n = perlin(&p);
+			assert!(n.abs() <= 1.0);
+			assert!((n - perlin(&p.add(&Vec3::new(1e-7,0.0,0.0)))).abs() < 1e-5);
+			spread = spread.max(n.abs());
+		}
+		assert!(spread > 0.3);
+	}
+
+	#[test]
+	fn test_simplex() {
+		let mut spread = 0.0_f64;
+		for p in points()
+		{
+			let n = simplex(&p);
+			assert!(n.abs() <= 1.0);
+			assert!((n - simplex(&p.add(&Vec3::new(0.0,1e-7,0.0)))).abs() < 1e-5);
+			spread = spread.max(n.abs());
+		}
+		assert!(spread > 0.3);
+	}
+
+	#[test]
+	fn test_worley() {
+		for p in points()
+		{
+			let d = worley(&p);
+			assert!((0.0..=3.0_f64.sqrt()).contains(&d));
+		}
+		// 0 on the feature point of a cell, whichever cell
+		for (i,j,k) in [(0,0,0),(3,-2,7),(-5,1,-1)]
+		{
+			let h       = hash(i,j,k);
+			let feature = Vec3::new(i as f64 + unit(h),j as f64 + unit(mix(h)),k as f64 + unit(mix(mix(h))));
+			assert!(worley(&feature) < 1e-12);
+		}
+		// a distance moves no faster than the point
+		let shift = Vec3::new(0.01,-0.02,0.005);
+		for p in points()
+		{
+			assert!((worley(&p) - worley(&p.add(&shift))).abs() <= shift.norm() + 1e-12);
+		}
+	}
+
+	#[test]
+	fn test_octaves() {
+		let p = Vec3::new(0.37,1.21,-2.9);
+		let one = Octaves{count:1,..Octaves::default()};
+
+		assert_eq!(perlin(&p),fbm(&p,&one));
+		assert_eq!(perlin(&p).abs(),turbulence(&p,&one));
+		assert_eq!(0.0,fbm(&p,&Octaves{count:0,..Octaves::default()}));
+		// every octave is at most half the previous one
+		assert!(turbulence(&p,&Octaves::default()) <= 2.0);
+	}
+}
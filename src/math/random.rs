@@ -0,0 +1,88 @@
+use crate::math::vector3::Vec3;
+
+// PCG32 generator (pcg-random.org) : tiny, fast and seedable so that every
+// pixel draws the same numbers whatever thread renders it
+pub struct Rng
+{
+	state:u64,
+	inc:u64
+}
+
+impl Rng {
+	pub fn new(seed:u64, stream:u64) -> Rng
+	{
+		let mut rng = Rng{state:0,inc:(stream << 1) | 1};
+		rng.next_u32();
+		rng.state = rng.state.wrapping_add(seed);
+		rng.next_u32();
+		rng
+	}
+
+	pub fn next_u32(&mut self) -> u32
+	{
+		let old = self.state;
+		self.state = old.wrapping_mul(6364136223846793005).wrapping_add(self.inc);
+		let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+		let rot = (old >> 59) as u32;
+		xorshifted.rotate_right(rot)
+	}
+
+	// uniform in [0,1)
+	pub fn next_f64(&mut self) -> f64
+	{
+		(self.next_u32() as f64)/4294967296.0
+	}
+}
+
+// direction around the z axis with a density proportional to its cosine,
+// u1 and u2 being uniform in [0,1)
+pub fn cosine_hemisphere(u1:f64, u2:f64) -> Vec3
+{
+	let r   = u1.sqrt();
+	let phi = 2.0*std::f64::consts::PI*u2;
+	Vec3::new(r*phi.cos(), r*phi.sin(), (1.0 - u1).max(0.0).sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_reproducible() {
+		let mut a = Rng::new(42,7);
+		let mut b = Rng::new(42,7);
+		let mut c = Rng::new(43,7);
+
+		let first = a.next_u32();
+		assert_eq!(first,b.next_u32());
+		assert_ne!(first,c.next_u32());
+	}
+
+	#[test]
+	fn test_next_f64_range() {
+		let mut rng = Rng::new(1,1);
+		let mut sum = 0.0;
+		for _ in 0..10000
+		{
+			let u = rng.next_f64();
+			assert!((0.0..1.0).contains(&u));
+			sum += u;
+		}
+		assert!((sum/10000.0-0.5).abs() < 0.02);
+	}
+
+	#[test]
+	fn test_cosine_hemisphere() {
+		let mut rng = Rng::new(3,5);
+		let mut mean_cos = 0.0;
+		for _ in 0..10000
+		{
+			let d = cosine_hemisphere(rng.next_f64(),rng.next_f64());
+			assert!((d.norm()-1.0).abs() < 1e-9);
+			assert!(d.z >= 0.0);
+			mean_cos += d.z;
+		}
+		// E[cos] = 2/3 for a cosine weighted distribution
+		assert!((mean_cos/10000.0-2.0/3.0).abs() < 0.02);
+	}
+}
@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Vec3
 {
 	pub x:f64,
@@ -9,7 +9,6 @@ pub struct Vec3
 impl Vec3 {
 	pub fn null() -> Vec3 { Vec3{x:0.0,y:0.0,z:0.0} }
 	pub fn new(x:f64,y:f64,z:f64) -> Vec3 { Vec3{x,y,z} }
-	pub fn clone(&self) -> Vec3 { Vec3{x:self.x,y:self.y,z:self.z} }
 	pub fn add(&self, v: &Vec3) -> Vec3
 	{
 		Vec3::new(
@@ -62,6 +61,30 @@ impl Vec3 {
 	{
 		self.div(self.dot(self).sqrt())
 	}
+	// mirror of self around the unit normal n
+	pub fn reflect(&self,n:&Vec3) -> Vec3
+	{
+		self.sub(&n.mul(2.0*self.dot(n)))
+	}
+	// snell's law for the unit vector self crossing a surface of unit normal n
+	// facing against it, eta being the ratio of the indices n1/n2. None on
+	// total internal reflection.
+	pub fn refract(&self,n:&Vec3,eta:f64) -> Option<Vec3>
+	{
+		let cos_i  = -self.dot(n);
+		let sin2_t = eta*eta*(1.0 - cos_i*cos_i);
+		if sin2_t > 1.0 { return None; }
+		let cos_t  = (1.0 - sin2_t).sqrt();
+		Some(self.mul(eta).add(&n.mul(eta*cos_i - cos_t)))
+	}
+	// two unit vectors completing the unit vector self into an orthonormal basis
+	pub fn basis(&self) -> (Vec3,Vec3)
+	{
+		let helper  = if self.x.abs() < 0.9 { Vec3::new(1.0,0.0,0.0) } else { Vec3::new(0.0,1.0,0.0) };
+		let tangent = self.cross(&helper).normalize();
+		let bitangent = self.cross(&tangent);
+		(tangent,bitangent)
+	}
 	pub fn project_on(&self,v:&Vec3) -> Vec3
 	{	
 		let mut projection = v.clone();
@@ -230,6 +253,49 @@ mod tests {
 
 	}		
 
+	#[test]
+	fn test_reflect() {
+		let v = Vec3::new(1.0,-1.0,0.0);
+		let n = Vec3::new(0.0,1.0,0.0);
+
+		let r = v.reflect(&n);
+
+		assert_eq!(1.0,r.x);
+		assert_eq!(1.0,r.y);
+		assert_eq!(0.0,r.z);
+	}
+
+	#[test]
+	fn test_refract() {
+		let n = Vec3::new(0.0,1.0,0.0);
+
+		// straight through whatever the indices
+		let r = Vec3::new(0.0,-1.0,0.0).refract(&n,1.0/1.5).unwrap();
+		assert!((r.y+1.0).abs() < 1e-12);
+		assert!(r.x.abs() < 1e-12);
+
+		// 45 degrees into glass : sin(t) = sin(45)/1.5
+		let v = Vec3::new(1.0,-1.0,0.0).normalize();
+		let r = v.refract(&n,1.0/1.5).unwrap();
+		assert!((r.x-(0.5_f64.sqrt()/1.5)).abs() < 1e-12);
+		assert!((r.norm()-1.0).abs() < 1e-12);
+
+		// total internal reflection going out of glass
+		assert!(v.refract(&n,1.5).is_none());
+	}
+
+	#[test]
+	fn test_basis() {
+		let n = Vec3::new(0.3,-0.5,0.8).normalize();
+		let (t,b) = n.basis();
+
+		assert!(n.dot(&t).abs() < 1e-12);
+		assert!(n.dot(&b).abs() < 1e-12);
+		assert!(t.dot(&b).abs() < 1e-12);
+		assert!((t.norm()-1.0).abs() < 1e-12);
+		assert!((b.norm()-1.0).abs() < 1e-12);
+	}
+
 	#[test]
 	fn test_project() {
 		let x1 = 1.0;let y1 = 1.0 ;let z1 = 1.0;
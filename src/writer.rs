@@ -0,0 +1,214 @@
+// image files, the format is picked from the extension of the path
+mod exr;
+mod hdr;
+mod pfm;
+
+use crate::engine::framebuffer::Framebuffer;
+use crate::engine::tonemap::{OutputTransform,linear_to_srgb};
+use crate::engine::aov::Pass;
+use crate::engine::color::Color;
+use crate::engine::tracer::Pixel;
+
+use std::fs;
+use std::io;
+use std::path::{Path,PathBuf};
+
+// size of the floats written to OpenEXR files
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Precision
+{
+	Half,
+	Float
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Format
+{
+	// 8 bits rgba through the output transform
+	Png,
+	// float formats keep the linear radiance, only scaled by the exposure
+	Exr,
+	Hdr,
+	Pfm
+}
+
+impl Format {
+	// anything that is not a float format is written as png
+	pub fn from_path(path:&Path) -> Format
+	{
+		let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase());
+		match extension.as_deref() {
+			Some("exr") => Format::Exr,
+			Some("hdr") => Format::Hdr,
+			Some("pfm") => Format::Pfm,
+			_           => Format::Png
+		}
+	}
+}
+
+pub fn write(path:&Path,image:&Framebuffer,transform:&OutputTransform,precision:Precision) -> io::Result<()>
+{
+	let scale = 2.0_f64.powf(transform.exposure);
+	match Format::from_path(path) {
+		Format::Png => lodepng::encode32_file(path,&image.to_pixels(transform),image.width,image.height)
+			.map_err(|e| io::Error::new(io::ErrorKind::Other,e.to_string())),
+		Format::Exr => fs::write(path,exr::encode(image.width,image.height,exr::beauty(image,scale,precision))),
+		Format::Hdr => fs::write(path,hdr::encode(image,scale)),
+		Format::Pfm => fs::write(path,pfm::encode(image,scale))
+	}
+}
+
+// a single OpenEXR file with the passes as extra channels next to the beauty
+// image, the alpha pass being its A channel
+pub fn write_layers(path:&Path,image:&Framebuffer,transform:&OutputTransform,precision:Precision,passes:&[Pass]) -> io::Result<()>
+{
+	if Format::from_path(path) != Format::Exr
+	{
+		return Err(io::Error::new(io::ErrorKind::InvalidInput,"layers are only written to OpenEXR files"));
+	}
+	let mut channels = exr::beauty(image,2.0_f64.powf(transform.exposure),precision);
+	for pass in passes.iter().filter(|p| **p != Pass::Alpha)
+	{
+		channels.extend(exr::pass(image,*pass,precision).ok_or_else(|| not_rendered(*pass))?);
+	}
+	fs::write(path,exr::encode(image.width,image.height,channels))
+}
+
+fn not_rendered(pass:Pass) -> io::Error
+{
+	io::Error::new(io::ErrorKind::InvalidInput,format!("the {} pass was not rendered",pass.name()))
+}
+
+// out.png becomes out.depth.png
+pub fn pass_path(path:&Path,pass:Pass) -> PathBuf
+{
+	let stem = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+	match path.extension() {
+		Some(extension) => path.with_file_name(format!("{}.{}.{}",stem,pass.name(),extension.to_string_lossy())),
+		None            => path.with_file_name(format!("{}.{}",stem,pass.name()))
+	}
+}
+
+// float formats keep the values of the pass, one channel passes being
+// written as gray, png gets a preview of it
+pub fn write_pass(path:&Path,image:&Framebuffer,pass:Pass,precision:Precision) -> io::Result<()>
+{
+	let values = || {
+		let channels = image.channels(pass).ok_or_else(|| not_rendered(pass))?;
+		let colors   = (0..image.colors.len()).map(|i| {
+			let value = |c:usize| channels[c.min(channels.len()-1)].1[i];
+			Color::new(value(0),value(1),value(2))
+		}).collect();
+		Ok::<_,io::Error>(Framebuffer::new(image.width,image.height,colors,vec![1.0;image.alpha.len()]))
+	};
+	match Format::from_path(path) {
+		Format::Png => {
+			let pixels = preview(image,pass).ok_or_else(|| not_rendered(pass))?;
+			lodepng::encode32_file(path,&pixels,image.width,image.height).map_err(|e| io::Error::new(io::ErrorKind::Other,e.to_string()))
+		},
+		Format::Exr => {
+			let channels = exr::pass(image,pass,precision).ok_or_else(|| not_rendered(pass))?;
+			fs::write(path,exr::encode(image.width,image.height,channels))
+		},
+		Format::Hdr => fs::write(path,hdr::encode(&values()?,1.0)),
+		Format::Pfm => fs::write(path,pfm::encode(&values()?,1.0))
+	}
+}
+
+// distinct colors for neighbouring ids
+fn id_color(id:usize) -> Pixel
+{
+	let hash = (id as u32).wrapping_add(1).wrapping_mul(2654435761);
+	Pixel((hash >> 24) as u8,(hash >> 16) as u8,(hash >> 8) as u8,255)
+}
+
+// 8 bits picture of a pass : depth is shown as the nearest depth over the
+// depth of the pixel, normals are mapped from [-1,1] to [0,1], the
+// background is black. None when the pass was not rendered.
+fn preview(image:&Framebuffer,pass:Pass) -> Option<Vec<Pixel>>
+{
+	if pass == Pass::Alpha
+	{
+		return Some(image.alpha.iter().map(|a| Pixel::from_color(&Color::new(*a,*a,*a))).collect());
+	}
+	let surfaces = image.surfaces.as_ref()?;
+	let near     = surfaces.iter().flatten().map(|s| s.depth).fold(f64::INFINITY,f64::min);
+	Some(surfaces.iter().map(|surface| {
+		let surface = match surface {
+			Some(surface) => surface,
+			None          => return Pixel::black()
+		};
+		match pass {
+			Pass::Depth     => {
+				let v = if surface.depth > 0.0 { near/surface.depth } else { 1.0 };
+				Pixel::from_color(&Color::new(v,v,v))
+			},
+			Pass::Normal    => {
+				let n = &surface.normal;
+				Pixel::from_color(&Color::new(n.x*0.5+0.5,n.y*0.5+0.5,n.z*0.5+0.5))
+			},
+			Pass::Albedo    => {
+				let a = &surface.albedo;
+				Pixel::from_color(&Color::new(linear_to_srgb(a.r),linear_to_srgb(a.g),linear_to_srgb(a.b)))
+			},
+			Pass::Primitive => id_color(surface.primitive),
+			Pass::Object    => id_color(surface.object),
+			Pass::Alpha     => unreachable!()
+		}
+	}).collect())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::engine::aov::Surface;
+	use crate::math::vector3::Vec3;
+
+	#[test]
+	fn test_format_from_path() {
+		assert_eq!(Format::Exr,Format::from_path(Path::new("renders/a.exr")));
+		assert_eq!(Format::Hdr,Format::from_path(Path::new("a.HDR")));
+		assert_eq!(Format::Pfm,Format::from_path(Path::new("a.pfm")));
+		assert_eq!(Format::Png,Format::from_path(Path::new("a.png")));
+		assert_eq!(Format::Png,Format::from_path(Path::new("out")));
+	}
+
+	#[test]
+	fn test_pass_path() {
+		assert_eq!(PathBuf::from("renders/a.depth.png"),pass_path(Path::new("renders/a.png"),Pass::Depth));
+		assert_eq!(PathBuf::from("a.object.exr"),pass_path(Path::new("a.exr"),Pass::Object));
+		assert_eq!(PathBuf::from("out.normal"),pass_path(Path::new("out"),Pass::Normal));
+	}
+
+	#[test]
+	fn test_pass_not_rendered() {
+		let image = Framebuffer::new(1,1,vec![Color::white()],vec![1.0]);
+
+		// nothing is written, the missing pass is reported instead
+		assert!(preview(&image,Pass::Depth).is_none());
+		assert!(preview(&image,Pass::Alpha).is_some());
+		let path = Path::new("missing-directory/out.exr");
+		for path in [path.to_path_buf(),path.with_extension("png"),path.with_extension("pfm")]
+		{
+			let error = write_pass(&path,&image,Pass::Normal,Precision::Half).unwrap_err();
+			assert_eq!(io::ErrorKind::InvalidInput,error.kind());
+		}
+		let error = write_layers(Path::new("missing-directory/out.exr"),&image,&OutputTransform::default(),Precision::Half,&[Pass::Depth]).unwrap_err();
+		assert_eq!(io::ErrorKind::InvalidInput,error.kind());
+	}
+
+	#[test]
+	fn test_depth_pass_hdr() {
+		// the background pixel has an infinite depth
+		let mut image  = Framebuffer::new(2,1,vec![Color::white();2],vec![1.0;2]);
+		let surface    = Surface{depth:1.0,normal:Vec3::new(0.0,0.0,1.0),albedo:Color::white(),primitive:0,object:0};
+		image.surfaces = Some(vec![Some(surface),None]);
+
+		let path = std::env::temp_dir().join(format!("ray_tracer-{}-depth.hdr",std::process::id()));
+		write_pass(&path,&image,Pass::Depth,Precision::Half).unwrap();
+		let data = fs::read(&path).unwrap();
+		fs::remove_file(&path).unwrap();
+
+		assert!(data.ends_with(&[128,128,128,129,255,255,255,255]));
+	}
+}
@@ -0,0 +1,197 @@
+// single part scanline OpenEXR without compression, one line per chunk
+use crate::engine::framebuffer::Framebuffer;
+use crate::engine::aov::Pass;
+use crate::writer::Precision;
+
+// f32 to IEEE 754 half, rounding to the nearest even
+pub fn to_half(value:f32) -> u16
+{
+	let bits     = value.to_bits();
+	let sign     = ((bits >> 16) & 0x8000) as u16;
+	let exponent = ((bits >> 23) & 0xff) as i32;
+	let mantissa = bits & 0x7fffff;
+
+	if exponent == 255 { return sign | 0x7c00 | if mantissa != 0 { 0x200 } else { 0 }; }
+	let exponent = exponent - 127 + 15;
+	if exponent >= 31 { return sign | 0x7c00; }
+
+	let round = |value:u32,shift:u32| -> u32 {
+		let rest    = value & ((1 << shift) - 1);
+		let halfway = 1 << (shift - 1);
+		let value   = value >> shift;
+		if rest > halfway || (rest == halfway && value & 1 == 1) { value + 1 } else { value }
+	};
+	if exponent <= 0
+	{
+		// subnormal
+		if exponent < -10 { return sign; }
+		return sign | round(mantissa | 0x800000,(14 - exponent) as u32) as u16;
+	}
+	// a carry out of the mantissa correctly bumps the exponent
+	sign | round(((exponent as u32) << 23) | mantissa,13) as u16
+}
+
+fn attribute(out:&mut Vec<u8>,name:&str,kind:&str,value:&[u8])
+{
+	out.extend_from_slice(name.as_bytes());
+	out.push(0);
+	out.extend_from_slice(kind.as_bytes());
+	out.push(0);
+	out.extend_from_slice(&(value.len() as i32).to_le_bytes());
+	out.extend_from_slice(value);
+}
+
+// one named channel of the file, row after row from the top left corner
+pub struct Channel
+{
+	pub name:String,
+	pub precision:Precision,
+	pub values:Vec<f64>
+}
+
+// rgb scaled by the exposure plus the coverage
+pub fn beauty(image:&Framebuffer,scale:f64,precision:Precision) -> Vec<Channel>
+{
+	let channel = |name:&str,value:&dyn Fn(usize) -> f64| Channel{
+		name:String::from(name),
+		precision,
+		values:(0..image.colors.len()).map(value).collect()
+	};
+	vec![
+		channel("R",&|i| image.colors[i].r*scale),
+		channel("G",&|i| image.colors[i].g*scale),
+		channel("B",&|i| image.colors[i].b*scale),
+		channel("A",&|i| image.alpha[i])
+	]
+}
+
+// channels of an auxiliary pass, depths and ids are always full floats.
+// None when the pass was not rendered.
+pub fn pass(image:&Framebuffer,pass:Pass,precision:Precision) -> Option<Vec<Channel>>
+{
+	let precision = if pass.needs_float() { Precision::Float } else { precision };
+	Some(image.channels(pass)?.into_iter().map(|(name,values)| Channel{name,precision,values}).collect())
+}
+
+// channels are stored in alphabetical order
+pub fn encode(width:usize,height:usize,channels:Vec<Channel>) -> Vec<u8>
+{
+	let mut channels = channels;
+	channels.sort_by(|a,b| a.name.cmp(&b.name));
+	let size = |precision:Precision| -> usize {
+		match precision {
+			Precision::Half  => 2,
+			Precision::Float => 4
+		}
+	};
+
+	let mut list = Vec::<u8>::new();
+	for channel in &channels
+	{
+		let pixel_type:i32 = match channel.precision {
+			Precision::Half  => 1,
+			Precision::Float => 2
+		};
+		list.extend_from_slice(channel.name.as_bytes());
+		list.push(0);
+		list.extend_from_slice(&pixel_type.to_le_bytes());
+		// linear flag and reserved bytes, then x and y sampling
+		list.extend_from_slice(&[0,0,0,0]);
+		list.extend_from_slice(&1_i32.to_le_bytes());
+		list.extend_from_slice(&1_i32.to_le_bytes());
+	}
+	list.push(0);
+
+	let mut window = Vec::<u8>::new();
+	for v in &[0,0,width as i32-1,height as i32-1]
+	{
+		window.extend_from_slice(&v.to_le_bytes());
+	}
+
+	let mut out = vec![0x76,0x2f,0x31,0x01,2,0,0,0];
+	attribute(&mut out,"channels","chlist",&list);
+	attribute(&mut out,"compression","compression",&[0]);
+	attribute(&mut out,"dataWindow","box2i",&window);
+	attribute(&mut out,"displayWindow","box2i",&window);
+	attribute(&mut out,"lineOrder","lineOrder",&[0]);
+	attribute(&mut out,"pixelAspectRatio","float",&1.0_f32.to_le_bytes());
+	attribute(&mut out,"screenWindowCenter","v2f",&[0;8]);
+	attribute(&mut out,"screenWindowWidth","float",&1.0_f32.to_le_bytes());
+	out.push(0);
+
+	// offset table, then every scanline as y, byte count and its channels
+	let line_size = channels.iter().map(|c| size(c.precision)).sum::<usize>()*width;
+	let first     = out.len() + 8*height;
+	for y in 0..height
+	{
+		out.extend_from_slice(&((first + y*(8 + line_size)) as u64).to_le_bytes());
+	}
+	for y in 0..height
+	{
+		out.extend_from_slice(&(y as i32).to_le_bytes());
+		out.extend_from_slice(&(line_size as i32).to_le_bytes());
+		for channel in &channels
+		{
+			for value in &channel.values[y*width..(y+1)*width]
+			{
+				let value = *value as f32;
+				match channel.precision {
+					Precision::Half  => out.extend_from_slice(&to_half(value).to_le_bytes()),
+					Precision::Float => out.extend_from_slice(&value.to_le_bytes())
+				}
+			}
+		}
+	}
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::engine::color::Color;
+
+	#[test]
+	fn test_to_half() {
+		assert_eq!(0x3c00,to_half(1.0));
+		assert_eq!(0x3800,to_half(0.5));
+		assert_eq!(0xc000,to_half(-2.0));
+		assert_eq!(0x7bff,to_half(65504.0));
+		assert_eq!(0x7c00,to_half(1e6));
+		assert_eq!(0x0001,to_half(2.0_f32.powi(-24)));
+		assert_eq!(0x0000,to_half(1e-10));
+		// 1 + 2^-11 is halfway between two halfs and rounds to the even one
+		assert_eq!(0x3c00,to_half(1.0 + 2.0_f32.powi(-11)));
+	}
+
+	#[test]
+	fn test_layout() {
+		let image = Framebuffer::new(3,2,vec![Color::new(1.0,0.5,0.25);6],vec![1.0;6]);
+		let half  = encode(3,2,beauty(&image,1.0,Precision::Half));
+		let float = encode(3,2,beauty(&image,1.0,Precision::Float));
+
+		assert_eq!(&[0x76,0x2f,0x31,0x01],&half[0..4]);
+		// same header and offsets, twice the pixel data
+		assert_eq!(float.len()-half.len(),2*(4*2*3));
+
+		// last scanline : y, size, then A B G R
+		let line = &half[half.len()-(8+4*2*3)..];
+		assert_eq!(1,i32::from_le_bytes([line[0],line[1],line[2],line[3]]));
+		assert_eq!(24,i32::from_le_bytes([line[4],line[5],line[6],line[7]]));
+		assert_eq!(0x3c00,u16::from_le_bytes([line[8],line[9]]));
+		assert_eq!(0x3400,u16::from_le_bytes([line[14],line[15]]));
+	}
+
+	#[test]
+	fn test_pass_channels() {
+		let mut image  = Framebuffer::new(1,1,vec![Color::white()],vec![1.0]);
+		image.surfaces = Some(vec![None]);
+		let mut channels = beauty(&image,1.0,Precision::Half);
+		channels.extend(pass(&image,Pass::Depth,Precision::Half).unwrap());
+		let data = encode(1,1,channels);
+
+		// a single scanline : y, size, A B G R as halfs then Z as a float
+		let line = &data[data.len()-(8+4*2+4)..];
+		assert_eq!(12,i32::from_le_bytes([line[4],line[5],line[6],line[7]]));
+		assert_eq!(f32::INFINITY,f32::from_le_bytes([line[16],line[17],line[18],line[19]]));
+	}
+}
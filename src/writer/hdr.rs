@@ -0,0 +1,75 @@
+// Radiance picture with flat (not run length encoded) RGBE pixels
+use crate::engine::framebuffer::Framebuffer;
+
+// shared exponent encoding of a linear color, values too large for the
+// format, infinite ones included, saturate
+pub fn to_rgbe(r:f64,g:f64,b:f64) -> [u8;4]
+{
+	let (r,g,b) = (r.max(0.0),g.max(0.0),b.max(0.0));
+	let v = r.max(g).max(b);
+	if v < 1e-32 { return [0,0,0,0]; }
+	if !v.is_finite() { return [255,255,255,255]; }
+
+	// v = m*2^e with m in [0.5,1[
+	let mut e = v.log2().floor() as i32 + 1;
+	let mut m = v/2.0_f64.powi(e);
+	if m >= 1.0 { m /= 2.0; e += 1; }
+	if m < 0.5  { m *= 2.0; e -= 1; }
+	if e > 127  { return [255,255,255,255]; }
+	if e < -128 { return [0,0,0,0]; }
+
+	let scale = m*256.0/v;
+	[(r*scale) as u8,(g*scale) as u8,(b*scale) as u8,(e + 128) as u8]
+}
+
+pub fn encode(image:&Framebuffer,scale:f64) -> Vec<u8>
+{
+	let mut out = format!(
+		"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n",
+		image.height,image.width
+	).into_bytes();
+	for c in &image.colors
+	{
+		out.extend_from_slice(&to_rgbe(c.r*scale,c.g*scale,c.b*scale));
+	}
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::engine::color::Color;
+
+	fn from_rgbe(p:[u8;4]) -> (f64,f64,f64)
+	{
+		if p[3] == 0 { return (0.0,0.0,0.0); }
+		let f = 2.0_f64.powi(p[3] as i32 - 136);
+		((p[0] as f64 + 0.5)*f,(p[1] as f64 + 0.5)*f,(p[2] as f64 + 0.5)*f)
+	}
+
+	#[test]
+	fn test_rgbe() {
+		assert_eq!([128,64,0,129],to_rgbe(1.0,0.5,0.0));
+		assert_eq!([0,0,0,0],to_rgbe(0.0,0.0,0.0));
+		assert_eq!([255,255,255,255],to_rgbe(f64::INFINITY,0.0,0.0));
+		assert_eq!([0,0,0,0],to_rgbe(f64::NAN,f64::NEG_INFINITY,0.0));
+
+		for value in &[0.001,0.7,3.0,1234.5]
+		{
+			let (r,g,_) = from_rgbe(to_rgbe(*value,value/3.0,0.0));
+			assert!((r-value).abs()/value < 0.01);
+			assert!((g-value/3.0).abs()/value < 0.01);
+		}
+	}
+
+	#[test]
+	fn test_header() {
+		let image = Framebuffer::new(3,2,vec![Color::white();6],vec![1.0;6]);
+		let data  = encode(&image,1.0);
+		let text  = String::from_utf8_lossy(&data);
+
+		assert!(text.starts_with("#?RADIANCE\n"));
+		assert!(text.contains("\n-Y 2 +X 3\n"));
+		assert!(data.ends_with(&[128,128,128,129]));
+	}
+}
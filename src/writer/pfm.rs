@@ -0,0 +1,36 @@
+// portable float map : little endian rgb floats, bottom row first
+use crate::engine::framebuffer::Framebuffer;
+
+pub fn encode(image:&Framebuffer,scale:f64) -> Vec<u8>
+{
+	// a negative scale tells the data is little endian
+	let mut out = format!("PF\n{} {}\n-1.0\n",image.width,image.height).into_bytes();
+	for y in (0..image.height).rev()
+	{
+		for c in &image.colors[y*image.width..(y+1)*image.width]
+		{
+			for value in &[c.r,c.g,c.b]
+			{
+				out.extend_from_slice(&((value*scale) as f32).to_le_bytes());
+			}
+		}
+	}
+	out
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::engine::color::Color;
+
+	#[test]
+	fn test_rows_are_flipped() {
+		let image = Framebuffer::new(1,2,vec![Color::new(1.0,2.0,3.0),Color::new(4.0,5.0,6.0)],vec![1.0;2]);
+		let data  = encode(&image,2.0);
+		let pixels= &data[b"PF\n1 2\n-1.0\n".len()..];
+
+		assert_eq!(24,pixels.len());
+		assert_eq!(8.0,f32::from_le_bytes([pixels[0],pixels[1],pixels[2],pixels[3]]));
+		assert_eq!(2.0,f32::from_le_bytes([pixels[12],pixels[13],pixels[14],pixels[15]]));
+	}
+}